@@ -0,0 +1,217 @@
+use core::fmt;
+
+use ::embedded_io::{ErrorKind, ErrorType, Write};
+
+use crate::indenter::{LineIndenter, Next};
+
+/// The error type of an [`IndentWriter`]
+///
+/// This is either an error from the wrapped writer, or
+/// [`WriteZero`][Error::WriteZero] if the wrapped writer accepted no bytes of
+/// an indent which had to be written before it could be flushed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error<E> {
+    /// An error from the wrapped writer
+    Inner(E),
+
+    /// The wrapped writer returned `Ok(0)` while the rest of an indent was
+    /// being written
+    WriteZero,
+}
+
+impl<E: fmt::Display> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Inner(err) => err.fmt(f),
+            Error::WriteZero => f.write_str("failed to write the whole indent"),
+        }
+    }
+}
+
+impl<E: core::error::Error> core::error::Error for Error<E> {}
+
+impl<E: ::embedded_io::Error> ::embedded_io::Error for Error<E> {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Inner(err) => err.kind(),
+            Error::WriteZero => ErrorKind::WriteZero,
+        }
+    }
+}
+
+/// Adapter for [`embedded_io::Write`] writers to indent
+/// each line
+///
+/// An `IndentWriter` adapts an [`embedded_io::Write`]
+/// object to insert an indent before each non-empty line. Specifically, this
+/// means it will insert an indent between each newline when followed by a
+/// non-newline. It works the same way as `io::IndentWriter`, but is available
+/// without `std`, and never allocates.
+///
+/// These writers can be nested to provide increasing levels of indentation.
+///
+/// # Example
+///
+/// ```
+/// use embedded_io::Write;
+/// use indent_write::embedded_io::IndentWriter;
+///
+/// let mut buffer = [0; 64];
+/// let mut output = &mut buffer[..];
+///
+/// {
+///     let mut indented = IndentWriter::new("\t", &mut output);
+///
+///     // Lines will be indented
+///     write!(indented, "Line 1\nLine 2\n").unwrap();
+///
+///     // Empty lines will not be indented
+///     write!(indented, "\n\nLine 3\n\n").unwrap();
+/// }
+///
+/// let len = 64 - output.len();
+/// assert_eq!(&buffer[..len], b"\tLine 1\n\tLine 2\n\n\n\tLine 3\n\n");
+/// ```
+///
+/// # Indent levels
+///
+/// Each line is prefixed with `indent` repeated once per indent level. A new
+/// writer starts at level 1; the level can be adjusted at any time with
+/// [`inc`][IndentWriter::inc], [`dec`][IndentWriter::dec],
+/// [`reset`][IndentWriter::reset], and
+/// [`set_indent_level`][IndentWriter::set_indent_level]. Changes to the level
+/// take effect at the start of the next line.
+#[derive(Debug, Clone)]
+pub struct IndentWriter<'i, W> {
+    writer: W,
+    indenter: LineIndenter<'i>,
+}
+
+impl<'i, W: Write> IndentWriter<'i, W> {
+    /// Create a new [`IndentWriter`].
+    #[inline]
+    pub fn new(indent: &'i str, writer: W) -> Self {
+        Self {
+            writer,
+            indenter: LineIndenter::new(indent, false),
+        }
+    }
+
+    /// Create a new [`IndentWriter`] which will not add an indent to the first
+    /// written line.
+    #[inline]
+    pub fn new_skip_initial(indent: &'i str, writer: W) -> Self {
+        Self {
+            writer,
+            indenter: LineIndenter::new(indent, true),
+        }
+    }
+
+    /// Extract the writer from the `IndentWriter`, discarding any in-progress
+    /// indent state.
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Get a reference to the wrapped writer
+    #[inline]
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Get a mutable reference to the wrapped writer. Writing to it directly
+    /// bypasses the indentation, and may interleave with a partially written
+    /// indent.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Get the string being used as an indent for each line
+    #[inline]
+    pub fn indent(&self) -> &'i str {
+        self.indenter.indent()
+    }
+
+    /// Get the current indent level; that is, the number of times the indent
+    /// is repeated at the start of each line.
+    #[inline]
+    pub fn indent_level(&self) -> u16 {
+        self.indenter.indent_level()
+    }
+
+    /// Increase the indent level by one.
+    #[inline]
+    pub fn inc(&mut self) {
+        self.set_indent_level(self.indent_level().saturating_add(1));
+    }
+
+    /// Decrease the indent level by one. Has no effect if the level is
+    /// already 0.
+    #[inline]
+    pub fn dec(&mut self) {
+        self.set_indent_level(self.indent_level().saturating_sub(1));
+    }
+
+    /// Reset the indent level to 0, so that no indent is inserted.
+    #[inline]
+    pub fn reset(&mut self) {
+        self.set_indent_level(0);
+    }
+
+    /// Set the indent level directly.
+    ///
+    /// If a line is currently being written, it is unaffected; the new level
+    /// takes effect at the start of the next line.
+    #[inline]
+    pub fn set_indent_level(&mut self, level: u16) {
+        self.indenter.set_indent_level(level);
+    }
+}
+
+impl<W: ErrorType> ErrorType for IndentWriter<'_, W> {
+    type Error = Error<W::Error>;
+}
+
+impl<W: Write> Write for IndentWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        loop {
+            match self.indenter.next(buf) {
+                // Write a piece of the current line (or of a run of empty
+                // lines), and let the indenter know how much was written, so
+                // that it knows whether an indent is due.
+                Next::Input(len) => {
+                    let n = self.writer.write(&buf[..len]).map_err(Error::Inner)?;
+                    self.indenter.input_written(buf, len, n);
+                    break Ok(n);
+                }
+
+                // We are writing an indent unconditionally, before the start
+                // of a non-empty line.
+                Next::Indent(part) => match self.writer.write(part).map_err(Error::Inner)? {
+                    // The writer broke its contract by accepting nothing
+                    // without an error; stop work immediately
+                    0 => break Ok(0),
+
+                    // Some or all of the indent was written. Update our state
+                    // to keep it consistent in case the next write is an
+                    // error
+                    n => self.indenter.indent_written(n),
+                },
+            }
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        // If we're currently in the middle of writing an indent, flush it
+        while let Some(part) = self.indenter.unwritten_indent() {
+            match self.writer.write(part).map_err(Error::Inner)? {
+                0 => return Err(Error::WriteZero),
+                n => self.indenter.indent_written(n),
+            }
+        }
+
+        self.writer.flush().map_err(Error::Inner)
+    }
+}
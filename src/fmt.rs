@@ -25,11 +25,22 @@ use core::fmt;
 ///
 /// assert_eq!(indented.get_ref(), "\tLine 1\n\tLine 2\n\n\n\tLine 3\n\n");
 /// ```
+///
+/// # Indent levels
+///
+/// Each line is prefixed with `indent` repeated once per indent level. A new
+/// writer starts at level 1; the level can be adjusted at any time with
+/// [`inc`][IndentWriter::inc], [`dec`][IndentWriter::dec],
+/// [`reset`][IndentWriter::reset], and
+/// [`set_indent_level`][IndentWriter::set_indent_level]. Changes to the level
+/// take effect at the start of the next line.
 #[derive(Debug, Clone)]
 pub struct IndentWriter<'i, W> {
     writer: W,
     indent: &'i str,
+    indent_level: u16,
     need_indent: bool,
+    indent_empty_lines: bool,
 }
 
 impl<'i, W: fmt::Write> IndentWriter<'i, W> {
@@ -39,7 +50,9 @@ impl<'i, W: fmt::Write> IndentWriter<'i, W> {
         Self {
             writer,
             indent,
+            indent_level: 1,
             need_indent: true,
+            indent_empty_lines: false,
         }
     }
 
@@ -66,28 +79,305 @@ impl<'i, W: fmt::Write> IndentWriter<'i, W> {
         Self {
             writer,
             indent,
+            indent_level: 1,
             need_indent: false,
+            indent_empty_lines: false,
         }
     }
 
     /// Extract the writer from the `IndentWriter`, discarding any in-progress
     /// indent state.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::fmt::Write;
+    /// use indent_write::fmt::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("  ", String::new());
+    /// writeln!(writer, "Line 1\nLine 2").unwrap();
+    ///
+    /// let output: String = writer.into_inner();
+    /// assert_eq!(output, "  Line 1\n  Line 2\n");
+    /// ```
     #[inline]
     pub fn into_inner(self) -> W {
         self.writer
     }
 
     /// Get a reference to the wrapped writer
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::fmt::Write;
+    /// use indent_write::fmt::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("  ", String::new());
+    /// write!(writer, "Line 1").unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), "  Line 1");
+    /// ```
     #[inline]
     pub fn get_ref(&self) -> &W {
         &self.writer
     }
 
+    /// Get a mutable reference to the wrapped writer
+    ///
+    /// Anything written directly to the wrapped writer bypasses the
+    /// [`IndentWriter`] entirely: it won't be indented, and the
+    /// [`IndentWriter`] won't know about any newlines it contains, so
+    /// subsequent indentation may be inserted in the wrong place.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::fmt::Write;
+    /// use indent_write::fmt::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("  ", String::new());
+    /// writeln!(writer, "Line 1").unwrap();
+    /// writer.get_mut().push_str("Unindented\n");
+    /// writeln!(writer, "Line 2").unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), "  Line 1\nUnindented\n  Line 2\n");
+    /// ```
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
     /// Get the string being used as an indent for each line
     #[inline]
     pub fn indent(&self) -> &'i str {
         self.indent
     }
+
+    /// Get the current indent level; that is, the number of times the indent
+    /// is repeated at the start of each line.
+    #[inline]
+    pub fn indent_level(&self) -> u16 {
+        self.indent_level
+    }
+
+    /// Increase the indent level by one.
+    #[inline]
+    pub fn inc(&mut self) {
+        self.set_indent_level(self.indent_level.saturating_add(1));
+    }
+
+    /// Decrease the indent level by one. Has no effect if the level is
+    /// already 0.
+    #[inline]
+    pub fn dec(&mut self) {
+        self.set_indent_level(self.indent_level.saturating_sub(1));
+    }
+
+    /// Reset the indent level to 0, so that no indent is inserted.
+    #[inline]
+    pub fn reset(&mut self) {
+        self.set_indent_level(0);
+    }
+
+    /// Set the indent level directly.
+    ///
+    /// If a line is currently being written, it is unaffected; the new level
+    /// takes effect at the start of the next line.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::fmt::Write;
+    /// use indent_write::fmt::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("  ", String::new());
+    ///
+    /// writeln!(writer, "Level 1").unwrap();
+    /// writer.set_indent_level(3);
+    /// writeln!(writer, "Level 3").unwrap();
+    /// writer.dec();
+    /// writeln!(writer, "Level 2").unwrap();
+    /// writer.reset();
+    /// writeln!(writer, "Level 0").unwrap();
+    ///
+    /// assert_eq!(
+    ///     writer.get_ref(),
+    ///     "  Level 1\n      Level 3\n    Level 2\nLevel 0\n"
+    /// );
+    /// ```
+    #[inline]
+    pub fn set_indent_level(&mut self, level: u16) {
+        self.indent_level = level;
+    }
+
+    /// Enable or disable indentation of empty lines. By default, only
+    /// non-empty lines are indented; when this is enabled, every line is
+    /// indented, including empty ones. This behaves the same way as
+    /// `io::IndentWriter::set_indent_empty_lines`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::fmt::Write;
+    /// use indent_write::fmt::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("> ", String::new());
+    /// writer.set_indent_empty_lines(true);
+    ///
+    /// write!(writer, "Line 1\n\nLine 2\n").unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), "> Line 1\n> \n> Line 2\n");
+    /// ```
+    #[inline]
+    pub fn set_indent_empty_lines(&mut self, indent_empty_lines: bool) {
+        self.indent_empty_lines = indent_empty_lines;
+    }
+
+    /// Write `value` through this writer, using its [`Display`][fmt::Display]
+    /// implementation, so that each of its lines is indented. This is the same
+    /// as `write!(writer, "{}", value)`, and like it, writes each piece of the
+    /// output as it's formatted, without building an intermediate string.
+    /// This makes it convenient for composing [`Display`][fmt::Display]
+    /// implementations.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::fmt::{self, Display, Formatter, Write};
+    /// use indent_write::fmt::IndentWriter;
+    ///
+    /// struct Section<'a> {
+    ///     title: &'a str,
+    ///     body: &'a dyn Display,
+    /// }
+    ///
+    /// impl Display for Section<'_> {
+    ///     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    ///         writeln!(f, "{}:", self.title)?;
+    ///         IndentWriter::new("  ", f).display(self.body)
+    ///     }
+    /// }
+    ///
+    /// let section = Section {
+    ///     title: "Outer",
+    ///     body: &Section { title: "Inner", body: &"Line 1\nLine 2" },
+    /// };
+    ///
+    /// assert_eq!(
+    ///     section.to_string(),
+    ///     "Outer:\n  Inner:\n    Line 1\n    Line 2"
+    /// );
+    /// ```
+    #[inline]
+    pub fn display<D: fmt::Display + ?Sized>(&mut self, value: &D) -> fmt::Result {
+        fmt::Write::write_fmt(self, format_args!("{}", value))
+    }
+
+    // Find the start of the next line in `s` that needs an indent.
+    #[inline]
+    fn next_line_start(&self, s: &str) -> Option<usize> {
+        match self.indent_empty_lines {
+            true if s.is_empty() => None,
+            true => Some(0),
+            false => s.as_bytes().iter().position(|&b| b != b'\n'),
+        }
+    }
+
+    // Write the indent, once per indent level.
+    fn write_indent(&mut self) -> fmt::Result {
+        (0..self.indent_level).try_for_each(|_| self.writer.write_str(self.indent))
+    }
+}
+
+/// Indent each non-empty line of `text` with `indent`, returning the result
+/// as a new [`String`]. Empty lines are left unindented, the same as with an
+/// [`IndentWriter`].
+///
+/// # Example
+///
+/// ```
+/// use indent_write::fmt::indent_lines;
+///
+/// let result = indent_lines("Line 1\n\nLine 2", "    ");
+///
+/// assert_eq!(result, "    Line 1\n\n    Line 2");
+/// ```
+#[cfg(feature = "std")]
+pub fn indent_lines(text: &str, indent: &str) -> String {
+    use fmt::Write;
+
+    let mut result = String::with_capacity(text.len());
+
+    IndentWriter::new(indent, &mut result)
+        .write_str(text)
+        .expect("writing to a String can't fail");
+
+    result
+}
+
+/// Indent each non-empty line of `s` with `indent`, returning the result as a
+/// new [`String`]. This is the same as [`indent_lines`].
+///
+/// # Example
+///
+/// ```
+/// use indent_write::fmt::indent;
+///
+/// assert_eq!(indent("Line 1\n\nLine 2\n", "\t"), "\tLine 1\n\n\tLine 2\n");
+/// ```
+#[cfg(feature = "std")]
+#[inline]
+pub fn indent(s: &str, indent: &str) -> String {
+    indent_lines(s, indent)
+}
+
+/// Indent each non-empty line of `text` with `indent`, returning an iterator
+/// over the indented lines, rather than a single [`String`]. Each line
+/// includes its trailing `\n` (if it has one), so concatenating the lines
+/// gives the same result as [`indent_lines`]. Empty lines, which don't need
+/// an indent, are borrowed from `text`.
+///
+/// # Example
+///
+/// ```
+/// use std::borrow::Cow;
+/// use indent_write::fmt::indent_lines_iter;
+///
+/// let lines: Vec<Cow<str>> = indent_lines_iter("Line 1\n\nLine 2", "    ").collect();
+///
+/// assert_eq!(lines, ["    Line 1\n", "\n", "    Line 2"]);
+/// assert!(matches!(lines[1], Cow::Borrowed(_)));
+/// ```
+#[cfg(feature = "std")]
+#[inline]
+pub fn indent_lines_iter<'a>(text: &'a str, indent: &'a str) -> IndentLines<'a> {
+    IndentLines {
+        lines: text.split_inclusive('\n'),
+        indent,
+    }
+}
+
+/// Iterator over indented lines, created by [`indent_lines_iter`].
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct IndentLines<'a> {
+    lines: core::str::SplitInclusive<'a, char>,
+    indent: &'a str,
+}
+
+#[cfg(feature = "std")]
+impl<'a> Iterator for IndentLines<'a> {
+    type Item = std::borrow::Cow<'a, str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = self.lines.next()?;
+
+        Some(match line == "\n" || self.indent.is_empty() {
+            true => line.into(),
+            false => [self.indent, line].concat().into(),
+        })
+    }
 }
 
 impl<'i, W: fmt::Write> fmt::Write for IndentWriter<'i, W> {
@@ -109,8 +399,9 @@ impl<'i, W: fmt::Write> fmt::Write for IndentWriter<'i, W> {
                     }
                 },
                 // We need an indent. Scan for the beginning of the next
-                // non-empty line.
-                true => match s.as_bytes().iter().position(|&b| b != b'\n') {
+                // non-empty line (or the next line, if empty lines are
+                // indented too).
+                true => match self.next_line_start(s) {
                     // No non-empty lines in input, write the entire string
                     None => break self.writer.write_str(s),
 
@@ -120,7 +411,7 @@ impl<'i, W: fmt::Write> fmt::Write for IndentWriter<'i, W> {
                     Some(len) => {
                         let (head, tail) = s.split_at(len);
                         self.writer.write_str(head)?;
-                        self.writer.write_str(self.indent)?;
+                        self.write_indent()?;
                         self.need_indent = false;
                         s = tail;
                     }
@@ -130,10 +421,10 @@ impl<'i, W: fmt::Write> fmt::Write for IndentWriter<'i, W> {
     }
 
     fn write_char(&mut self, c: char) -> fmt::Result {
-        // We need an indent, and this is the start of a non-empty line.
-        // Insert the indent.
-        if self.need_indent && c != '\n' {
-            self.writer.write_str(self.indent)?;
+        // We need an indent, and this is the start of a non-empty line (or
+        // any line, if empty lines are indented too). Insert the indent.
+        if self.need_indent && (c != '\n' || self.indent_empty_lines) {
+            self.write_indent()?;
             self.need_indent = false;
         }
 
@@ -1,8 +1,12 @@
-use core::fmt::{self, Display, Formatter, Write};
+use core::fmt::{self, Debug, Display, Formatter, Write};
 
 use crate::fmt::IndentWriter;
 
 /// Methods for adapting [`Display`] objects to indent themselves when printed.
+///
+/// This trait is implemented for every type which implements [`Display`],
+/// such as `&str`, `String`, and references to other [`Display`] types, so it
+/// only needs to be imported to be used.
 pub trait Indentable: Sized + Display {
     /// Wrap this object so that its [`Display`] representation is indented
     /// with the given `indent`. Each non-empty line of the formatted output
@@ -24,6 +28,65 @@ pub trait Indentable: Sized + Display {
         Indented { item: self, indent }
     }
 
+    /// Wrap this object so that its [`Display`] representation is indented
+    /// with the given `indent`. Every line of the formatted output, including
+    /// empty lines, will be prefixed with the indent.
+    ///
+    /// This differs from [`indented`][Indentable::indented] only in how empty
+    /// lines are treated: both prefix the first line, but `indented` leaves
+    /// empty lines untouched, while `indent_all` prefixes them too. This is
+    /// useful when the prefix is significant, such as when quoting a block
+    /// with `"> "`.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use indent_write::indentable::Indentable;
+    ///
+    /// let content = "Line 1\nLine 2";
+    /// assert_eq!(content.indented("> ").to_string(), "> Line 1\n> Line 2");
+    /// assert_eq!(content.indent_all("> ").to_string(), "> Line 1\n> Line 2");
+    ///
+    /// let content = "Para 1\n\nPara 2\n";
+    /// assert_eq!(content.indented("> ").to_string(), "> Para 1\n\n> Para 2\n");
+    /// assert_eq!(content.indent_all("> ").to_string(), "> Para 1\n> \n> Para 2\n");
+    /// ```
+    #[must_use = "Indentables do nothing unless used"]
+    fn indent_all(self, indent: &str) -> IndentedAll<'_, Self> {
+        IndentedAll { item: self, indent }
+    }
+
+    /// Wrap this object so that each non-empty line of its [`Display`]
+    /// representation is prefixed with the output of `prefix`. `prefix` is
+    /// called once for each non-empty line with the zero-based index of that
+    /// line, so that each line can have a different prefix. Empty lines are
+    /// counted, but not prefixed.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use indent_write::indentable::Indentable;
+    ///
+    /// let content = "Line 1\nLine 2\n\nLine 4\n";
+    /// let numbered = content.indent_with(|i| format!("{:>2}: ", i + 1));
+    ///
+    /// assert_eq!(
+    ///     numbered.to_string(),
+    ///     " 1: Line 1\n 2: Line 2\n\n 4: Line 4\n"
+    /// );
+    ///
+    /// let striped = "a\nb\nc".indent_with(|i| if i % 2 == 0 { "| " } else { ": " });
+    /// assert_eq!(striped.to_string(), "| a\n: b\n| c");
+    /// ```
+    #[must_use = "Indentables do nothing unless used"]
+    fn indent_with<F, P>(self, prefix: F) -> IndentedWith<Self, F>
+    where
+        F: Fn(usize) -> P,
+        P: Display,
+    {
+        IndentedWith { item: self, prefix }
+    }
+
     /// Wrap this object so that its [`Display`] representation is indented
     /// with the given `indent`. Each non-empty line *except for the first*
     /// of the formatted output will be prefixed with the indent.
@@ -43,10 +106,214 @@ pub trait Indentable: Sized + Display {
     fn indented_skip_initial(self, indent: &str) -> IndentedSkipIntial<'_, Self> {
         IndentedSkipIntial { item: self, indent }
     }
+
+    /// Wrap this object so that its [`Display`] representation is indented
+    /// with the given `indent` repeated `level` times. Each non-empty line of
+    /// the formatted output will be prefixed with the repeated indent.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use std::fmt::{self, Display, Formatter};
+    /// use indent_write::indentable::Indentable;
+    ///
+    /// struct Node {
+    ///     name: &'static str,
+    ///     children: Vec<Node>,
+    /// }
+    ///
+    /// impl Node {
+    ///     fn fmt_at(&self, f: &mut Formatter<'_>, depth: u16) -> fmt::Result {
+    ///         writeln!(f, "{}", self.name.indented_with_level("  ", depth))?;
+    ///         self.children
+    ///             .iter()
+    ///             .try_for_each(|child| child.fmt_at(f, depth + 1))
+    ///     }
+    /// }
+    ///
+    /// impl Display for Node {
+    ///     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    ///         self.fmt_at(f, 0)
+    ///     }
+    /// }
+    ///
+    /// let tree = Node {
+    ///     name: "root",
+    ///     children: vec![
+    ///         Node {
+    ///             name: "a",
+    ///             children: vec![Node { name: "b", children: vec![] }],
+    ///         },
+    ///         Node { name: "c", children: vec![] },
+    ///     ],
+    /// };
+    ///
+    /// assert_eq!(tree.to_string(), "root\n  a\n    b\n  c\n");
+    /// ```
+    #[must_use = "Indentables do nothing unless used"]
+    fn indented_with_level(self, indent: &str, level: u16) -> IndentedWithLevel<'_, Self> {
+        IndentedWithLevel {
+            item: self,
+            indent,
+            level,
+        }
+    }
+
+    /// Format this object with each non-empty line indented with the given
+    /// `indent`, returning the result as a new [`String`]. Unlike
+    /// [`indented`][Indentable::indented], this formats the object
+    /// immediately, allocating a `String` for the whole output; prefer
+    /// `indented` when writing to a formatter or writer.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use indent_write::indentable::Indentable;
+    ///
+    /// let content = "Line 1\nLine 2\n\nLine 3\n";
+    /// let result = content.indent_to_string("    ");
+    ///
+    /// assert_eq!(result, "    Line 1\n    Line 2\n\n    Line 3\n");
+    /// assert_eq!(42.indent_to_string("> "), "> 42");
+    /// ```
+    #[cfg(feature = "std")]
+    fn indent_to_string(&self, indent: &str) -> String {
+        Indented { item: self, indent }.to_string()
+    }
 }
 
 impl<T: Display> Indentable for T {}
 
+/// Methods for indenting the value in an [`Option`] when printed, without
+/// needing to `match` on it first.
+pub trait IndentableOption<T: Display>: Sized {
+    /// Wrap this option so that, when printed with [`Display`], the value in
+    /// [`Some`] is indented as with [`Indentable::indented`]. [`None`] is
+    /// printed verbatim, as `None`, without an indent.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use indent_write::indentable::IndentableOption;
+    ///
+    /// let value = Some("Line 1\nLine 2");
+    /// assert_eq!(value.indented("  ").to_string(), "  Line 1\n  Line 2");
+    ///
+    /// let value: Option<&str> = None;
+    /// assert_eq!(value.indented("  ").to_string(), "None");
+    /// ```
+    #[must_use = "Indentables do nothing unless used"]
+    fn indented(self, indent: &str) -> IndentedOption<'_, T>;
+}
+
+impl<T: Display> IndentableOption<T> for Option<T> {
+    #[inline]
+    fn indented(self, indent: &str) -> IndentedOption<'_, T> {
+        IndentedOption { item: self, indent }
+    }
+}
+
+/// Methods for indenting the value in a [`Result`] when printed, without
+/// needing to `match` on it first.
+pub trait IndentableResult<T: Display, E: Display>: Sized {
+    /// Wrap this result so that, when printed with [`Display`], its value is
+    /// indented as with [`Indentable::indented`]. Both the [`Ok`] and the
+    /// [`Err`] values are indented in the same way; neither is wrapped in
+    /// `Ok(...)` or `Err(...)`.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use indent_write::indentable::IndentableResult;
+    ///
+    /// let value: Result<&str, &str> = Ok("Line 1\nLine 2");
+    /// assert_eq!(value.indented("  ").to_string(), "  Line 1\n  Line 2");
+    ///
+    /// let value: Result<&str, &str> = Err("error: failed\n\ncaused by: oops");
+    /// assert_eq!(
+    ///     value.indented("  ").to_string(),
+    ///     "  error: failed\n\n  caused by: oops"
+    /// );
+    /// ```
+    #[must_use = "Indentables do nothing unless used"]
+    fn indented(self, indent: &str) -> IndentedResult<'_, T, E>;
+}
+
+impl<T: Display, E: Display> IndentableResult<T, E> for Result<T, E> {
+    #[inline]
+    fn indented(self, indent: &str) -> IndentedResult<'_, T, E> {
+        IndentedResult { item: self, indent }
+    }
+}
+
+/// Methods for indenting the [`Debug`] representation of an object, for types
+/// which don't implement [`Display`].
+pub trait IndentableDebug: Debug {
+    /// Wrap a reference to this object so that, when printed with [`Display`],
+    /// each non-empty line of its [`Debug`] representation is indented with the
+    /// given `indent`.
+    ///
+    /// The alternate flag is passed through: printing the wrapper with `{}`
+    /// formats the object with `{:?}`, while `{:#}` formats it with `{:#?}`.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use indent_write::indentable::IndentableDebug;
+    ///
+    /// #[derive(Debug)]
+    /// struct Point {
+    ///     x: i32,
+    ///     y: i32,
+    /// }
+    ///
+    /// let point = Point { x: 1, y: 2 };
+    /// assert_eq!(
+    ///     point.indent_debug("  ").to_string(),
+    ///     "  Point { x: 1, y: 2 }"
+    /// );
+    /// assert_eq!(
+    ///     format!("{:#}", point.indent_debug("  ")),
+    ///     "  Point {\n      x: 1,\n      y: 2,\n  }"
+    /// );
+    /// ```
+    #[must_use = "Indentables do nothing unless used"]
+    fn indent_debug<'a>(&'a self, indent: &'a str) -> IndentedDebug<'a, &'a Self> {
+        IndentedDebug { item: self, indent }
+    }
+}
+
+impl<T: Debug + ?Sized> IndentableDebug for T {}
+
+/// Wrap a collection of items so that, when printed with [`Display`], each
+/// item is printed on its own line, with every non-empty line indented with
+/// the given `indent`. Items are separated by newlines; no newline is added
+/// after the last item.
+///
+/// `items` is iterated each time the wrapper is printed, so it must be
+/// [`Clone`]; pass a reference to a collection (or an iterator over one) to
+/// avoid copying it.
+///
+/// # Example:
+///
+/// ```
+/// use indent_write::indentable::indent_each;
+///
+/// let items = vec!["Item 1", "Item 2\ncontinued", "Item 3"];
+/// let result = indent_each(&items, "  ").to_string();
+///
+/// assert_eq!(result, "  Item 1\n  Item 2\n  continued\n  Item 3");
+/// assert_eq!(indent_each(1..=3, "- ").to_string(), "- 1\n- 2\n- 3");
+/// ```
+#[must_use = "Indentables do nothing unless used"]
+pub fn indent_each<I>(items: I, indent: &str) -> IndentedEach<'_, I>
+where
+    I: IntoIterator + Clone,
+    I::Item: Display,
+{
+    IndentedEach { items, indent }
+}
+
 /// Wrapper struct that indents the [`Display`] representation of an item. When
 /// printed with [`Display`], it will insert [`indent`][Self::indent] before
 /// each non-empty line of the underlying [`item`][Self::item]'s [`Display`]
@@ -68,6 +335,176 @@ impl<T: Display> Display for Indented<'_, T> {
     }
 }
 
+/// Wrapper struct that indents the [`Debug`] representation of an item. When
+/// printed with [`Display`], it will insert [`indent`][Self::indent] before
+/// each non-empty line of the underlying [`item`][Self::item]'s [`Debug`]
+/// output, using the pretty-printed form if the alternate flag (`{:#}`) is set.
+///
+/// Created with [`IndentableDebug::indent_debug`]; see its documentation for
+/// an example.
+#[derive(Debug, Clone, Copy)]
+pub struct IndentedDebug<'i, T: Debug> {
+    /// The item to indent.
+    pub item: T,
+
+    /// The indentation to insert before each non-empty line.
+    pub indent: &'i str,
+}
+
+impl<T: Debug> Display for IndentedDebug<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let alternate = f.alternate();
+        let mut writer = IndentWriter::new(self.indent, f);
+
+        match alternate {
+            true => write!(writer, "{:#?}", self.item),
+            false => write!(writer, "{:?}", self.item),
+        }
+    }
+}
+
+/// Wrapper struct that indents the [`Display`] representation of the value in
+/// an [`Option`]. When printed with [`Display`], it will insert
+/// [`indent`][Self::indent] before each non-empty line of the value in
+/// [`item`][Self::item], or print `None` if there is no value.
+///
+/// Created with [`IndentableOption::indented`]; see its documentation for an
+/// example.
+#[derive(Debug, Clone, Copy)]
+pub struct IndentedOption<'i, T: Display> {
+    /// The optional item to indent.
+    pub item: Option<T>,
+
+    /// The indentation to insert before each non-empty line.
+    pub indent: &'i str,
+}
+
+impl<T: Display> Display for IndentedOption<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.item {
+            Some(ref item) => write!(IndentWriter::new(self.indent, f), "{}", item),
+            None => f.write_str("None"),
+        }
+    }
+}
+
+/// Wrapper struct that indents the [`Display`] representation of the value in
+/// a [`Result`]. When printed with [`Display`], it will insert
+/// [`indent`][Self::indent] before each non-empty line of the [`Ok`] or
+/// [`Err`] value in [`item`][Self::item].
+///
+/// Created with [`IndentableResult::indented`]; see its documentation for an
+/// example.
+#[derive(Debug, Clone, Copy)]
+pub struct IndentedResult<'i, T: Display, E: Display> {
+    /// The result to indent.
+    pub item: Result<T, E>,
+
+    /// The indentation to insert before each non-empty line.
+    pub indent: &'i str,
+}
+
+impl<T: Display, E: Display> Display for IndentedResult<'_, T, E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut writer = IndentWriter::new(self.indent, f);
+
+        match self.item {
+            Ok(ref item) => write!(writer, "{}", item),
+            Err(ref err) => write!(writer, "{}", err),
+        }
+    }
+}
+
+/// Wrapper struct that indents every line of the [`Display`] representation of
+/// an item. When printed with [`Display`], it will insert
+/// [`indent`][Self::indent] before each line of the underlying
+/// [`item`][Self::item]'s [`Display`] output, including empty lines.
+///
+/// Created with [`Indentable::indent_all`]; see its documentation for an
+/// example.
+#[derive(Debug, Clone, Copy)]
+pub struct IndentedAll<'i, T: Display> {
+    /// The item to indent.
+    pub item: T,
+
+    /// The indentation to insert before each line.
+    pub indent: &'i str,
+}
+
+impl<T: Display> Display for IndentedAll<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut writer = IndentWriter::new(self.indent, f);
+        writer.set_indent_empty_lines(true);
+        write!(writer, "{}", self.item)
+    }
+}
+
+/// Wrapper struct that prefixes each line of the [`Display`] representation of
+/// an item with a computed prefix. When printed with [`Display`], it will call
+/// [`prefix`][Self::prefix] with the zero-based index of each non-empty line of
+/// the underlying [`item`][Self::item]'s [`Display`] output, and insert the
+/// result before that line.
+///
+/// Created with [`Indentable::indent_with`]; see its documentation for an
+/// example.
+#[derive(Clone, Copy)]
+pub struct IndentedWith<T: Display, F> {
+    /// The item to indent.
+    pub item: T,
+
+    /// The function computing the prefix for each non-empty line.
+    pub prefix: F,
+}
+
+impl<T: Display + Debug, F> Debug for IndentedWith<T, F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IndentedWith")
+            .field("item", &self.item)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: Display, F: Fn(usize) -> P, P: Display> Display for IndentedWith<T, F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            IndentWithWriter {
+                writer: f,
+                prefix: &self.prefix,
+                line: 0,
+                line_start: true,
+            },
+            "{}",
+            self.item
+        )
+    }
+}
+
+// Adapter which inserts a prefix, computed from the line index, at the start
+// of every non-empty line.
+struct IndentWithWriter<'f, W, F> {
+    writer: W,
+    prefix: &'f F,
+    line: usize,
+    line_start: bool,
+}
+
+impl<W: Write, F: Fn(usize) -> P, P: Display> Write for IndentWithWriter<'_, W, F> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        s.split_inclusive('\n').try_for_each(|line| {
+            if self.line_start && line != "\n" {
+                write!(self.writer, "{}", (self.prefix)(self.line))?;
+            }
+
+            self.line_start = line.ends_with('\n');
+            if self.line_start {
+                self.line += 1;
+            }
+
+            self.writer.write_str(line)
+        })
+    }
+}
+
 /// Wrapper struct that indents the [`Display`] representation of an item. When
 /// printed with [`Display`], it will insert [`indent`][Self::indent] before
 /// each non-empty line _after the first_ of the underlying
@@ -92,3 +529,69 @@ impl<T: Display> Display for IndentedSkipIntial<'_, T> {
         )
     }
 }
+
+/// Wrapper struct that indents the [`Display`] representation of an item
+/// by several levels. When printed with [`Display`], it will insert
+/// [`indent`][Self::indent], repeated [`level`][Self::level] times, before
+/// each non-empty line of the underlying [`item`][Self::item]'s [`Display`]
+/// output.
+///
+/// Created with [`Indentable::indented_with_level`]; see its documentation for
+/// an example.
+#[derive(Debug, Clone, Copy)]
+pub struct IndentedWithLevel<'i, T: Display> {
+    /// The item to indent.
+    pub item: T,
+
+    /// The indentation to insert, once per level, before each non-empty line.
+    pub indent: &'i str,
+
+    /// The number of times to repeat the indentation.
+    pub level: u16,
+}
+
+impl<T: Display> Display for IndentedWithLevel<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut writer = IndentWriter::new(self.indent, f);
+        writer.set_indent_level(self.level);
+        write!(writer, "{}", self.item)
+    }
+}
+
+/// Wrapper struct that indents the [`Display`] representation of each of a
+/// collection of items. When printed with [`Display`], it will print each of
+/// the [`items`][Self::items] on its own line, inserting
+/// [`indent`][Self::indent] before each non-empty line.
+///
+/// Created with [`indent_each`]; see its documentation for an example.
+#[derive(Debug, Clone, Copy)]
+pub struct IndentedEach<'i, I>
+where
+    I: IntoIterator + Clone,
+    I::Item: Display,
+{
+    /// The items to indent.
+    pub items: I,
+
+    /// The indentation to insert before each non-empty line.
+    pub indent: &'i str,
+}
+
+impl<I> Display for IndentedEach<'_, I>
+where
+    I: IntoIterator + Clone,
+    I::Item: Display,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut writer = IndentWriter::new(self.indent, f);
+
+        self.items
+            .clone()
+            .into_iter()
+            .enumerate()
+            .try_for_each(|(i, item)| match i {
+                0 => write!(writer, "{}", item),
+                _ => write!(writer, "\n{}", item),
+            })
+    }
+}
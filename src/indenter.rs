@@ -0,0 +1,174 @@
+// The state machine shared by the adapters which insert a fixed indent,
+// repeated once per level, before each non-empty line, and do nothing else
+// (`embedded_io::IndentWriter` and `tokio::IndentWriter`). The adapter asks
+// `next` what to write to its wrapped writer, then reports how much was
+// accepted, so that the state is kept consistent across partial writes,
+// errors, and (for async writers) pending polls.
+
+#[derive(Debug, Copy, Clone)]
+enum IndentState {
+    // We are currently writing a line. Forward writes until the end of the
+    // line.
+    MidLine,
+
+    // An indent has been requested. Write empty lines, then write an indent
+    // before the next non empty line.
+    NeedIndent,
+
+    // We are currently writing an indent. The payload is the number of bytes
+    // of the indent that remain to be written.
+    WritingIndent(usize),
+}
+
+use IndentState::*;
+
+// What to write to the wrapped writer next.
+pub(crate) enum Next<'i> {
+    // Write this part of the indent, then report how much of it was written
+    // with `indent_written`.
+    Indent(&'i [u8]),
+
+    // Write this many bytes from the start of the input buffer, then report
+    // how many were written with `input_written`.
+    Input(usize),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct LineIndenter<'i> {
+    indent: &'i str,
+    indent_level: u16,
+    state: IndentState,
+}
+
+impl<'i> LineIndenter<'i> {
+    // Create a new indenter at level 1, which will indent the first line
+    // unless `skip_initial` is set.
+    #[inline]
+    pub(crate) fn new(indent: &'i str, skip_initial: bool) -> Self {
+        Self {
+            indent,
+            indent_level: 1,
+            state: match skip_initial {
+                true => MidLine,
+                false => NeedIndent,
+            },
+        }
+    }
+
+    #[inline]
+    pub(crate) fn indent(&self) -> &'i str {
+        self.indent
+    }
+
+    #[inline]
+    pub(crate) fn indent_level(&self) -> u16 {
+        self.indent_level
+    }
+
+    // A line which is already being written (or indented) is unaffected; the
+    // new level takes effect at the start of the next line.
+    #[inline]
+    pub(crate) fn set_indent_level(&mut self, level: u16) {
+        self.indent_level = level;
+    }
+
+    // Find what to write next, given that `buf` is the rest of the input.
+    pub(crate) fn next(&mut self, buf: &[u8]) -> Next<'i> {
+        loop {
+            match self.state {
+                // We're currently writing a line. Write up to and including
+                // the end of the line, or the entire buffer if there are no
+                // newlines in it.
+                MidLine => {
+                    return Next::Input(match buf.iter().position(|&b| b == b'\n') {
+                        None => buf.len(),
+                        Some(len) => len + 1,
+                    })
+                }
+
+                // We need an indent. Scan for the next non-empty line.
+                NeedIndent => match buf.iter().position(|&b| b != b'\n') {
+                    // No non-empty lines in the input buffer, so write the
+                    // entire thing
+                    None => return Next::Input(buf.len()),
+
+                    // We are at the beginning of a non-empty line presently.
+                    // Begin inserting an indent now, then continue looping
+                    // (since we haven't yet attempted to write user data)
+                    Some(0) => self.begin_indent(),
+
+                    // There's an upcoming non-empty line. Write out the
+                    // remainder of the empty lines.
+                    Some(len) => return Next::Input(len),
+                },
+
+                // We've written the entire indent. Continue with writing the
+                // input buffer.
+                WritingIndent(0) => self.state = MidLine,
+
+                // We are writing an indent unconditionally. If we're in this
+                // state, the input buffer is known to be the start of a non-
+                // empty line.
+                WritingIndent(remaining) => return Next::Indent(self.indent_part(remaining)),
+            }
+        }
+    }
+
+    // Record that `n` bytes were written of the `len` bytes of `buf` which
+    // `next` asked for.
+    pub(crate) fn input_written(&mut self, buf: &[u8], len: usize, n: usize) {
+        if n < len {
+            return;
+        }
+
+        match self.state {
+            // If the entire line was written, request an indent on the
+            // subsequent call to write.
+            MidLine if buf[..len].ends_with(b"\n") => self.state = NeedIndent,
+
+            // If all the empty lines before a non-empty line were written,
+            // force an indent on the subsequent call to write.
+            NeedIndent if len < buf.len() => self.begin_indent(),
+
+            _ => {}
+        }
+    }
+
+    // Record that `n` bytes of the indent part which `next` asked for were
+    // written.
+    #[inline]
+    pub(crate) fn indent_written(&mut self, n: usize) {
+        if let WritingIndent(remaining) = self.state {
+            self.state = WritingIndent(remaining - n);
+        }
+    }
+
+    // The rest of a partially written indent, if there is one, which must be
+    // written before the wrapped writer is flushed. Report how much of it was
+    // written with `indent_written`.
+    pub(crate) fn unwritten_indent(&mut self) -> Option<&'i [u8]> {
+        match self.state {
+            WritingIndent(0) => {
+                self.state = MidLine;
+                None
+            }
+            WritingIndent(remaining) => Some(self.indent_part(remaining)),
+            _ => None,
+        }
+    }
+
+    // Start writing a full indent.
+    #[inline]
+    fn begin_indent(&mut self) {
+        self.state = WritingIndent(self.indent.len() * usize::from(self.indent_level));
+    }
+
+    // The part of the indent to write next, when `remaining` bytes of it are
+    // still to be written. The indent is `indent` repeated, so this is the
+    // tail of a single `indent`.
+    #[inline]
+    fn indent_part(&self, remaining: usize) -> &'i [u8] {
+        let indent = self.indent.as_bytes();
+        &indent[(indent.len() - remaining % indent.len()) % indent.len()..]
+    }
+}
@@ -1,9 +1,15 @@
-use std::io;
+// `iter::repeat_n` would need a newer Rust than the rest of the crate.
+#![allow(clippy::manual_repeat_n)]
 
-use super::Inspect;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, IoSlice, Write};
+use std::iter;
+use std::mem;
+use std::ops::{Deref, DerefMut};
 
 #[derive(Debug, Copy, Clone)]
-enum IndentState<'a> {
+enum IndentState {
     // We are currently writing a line. Forward writes until the end of the
     // line.
     MidLine,
@@ -12,12 +18,627 @@ enum IndentState<'a> {
     // before the next non empty line.
     NeedIndent,
 
-    // We are currently writing an indent.
-    WritingIndent(&'a [u8]),
+    // We are currently writing an indent. The payload is the number of bytes
+    // of the required indent that have already been written.
+    WritingIndent(usize),
 }
 
 use IndentState::*;
 
+/// A line ending sequence. See [`IndentWriter::set_line_ending`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LineEnding {
+    /// `\n`
+    Lf,
+
+    /// `\r\n`
+    CrLf,
+
+    /// `\r`
+    Cr,
+}
+
+impl LineEnding {
+    /// Get the line ending sequence as a string.
+    #[inline]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+}
+
+/// The position of an [`IndentWriter`] within the current line, as returned by
+/// [`IndentWriter::line_phase`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LinePhase {
+    /// The writer is in the middle of a line. Content is forwarded unchanged
+    /// until the end of the line.
+    MidLine,
+
+    /// The writer is at the start of a line, and none of its indent has been
+    /// written yet. The indent will be written before the next non-empty
+    /// line.
+    NeedIndent,
+
+    /// The indent for the current line has been partially written, usually
+    /// because the wrapped writer returned an error. The rest of it will be
+    /// written before any more content; see
+    /// [`IndentWriter::pending_indent_len`].
+    WritingIndent,
+}
+
+/// What [`IndentWriter::finish_with`] should do if the output doesn't end at
+/// the start of a line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FinishPolicy {
+    /// End the partial line with a newline. This is what
+    /// [`IndentWriter::finish`] does.
+    AddNewline,
+
+    /// Leave the partial line as it is, without a newline.
+    Keep,
+
+    /// Return an error of kind [`InvalidData`][io::ErrorKind::InvalidData].
+    Error,
+}
+
+// A line filter (see `IndentWriter::set_line_filter`), if there is one.
+// Filters can't be cloned, so a clone of the writer has no filter.
+type FilterFn<'i> = dyn FnMut(&[u8]) -> bool + 'i;
+
+struct LineFilter<'i>(Option<Box<FilterFn<'i>>>);
+
+impl Clone for LineFilter<'_> {
+    #[inline]
+    fn clone(&self) -> Self {
+        LineFilter(None)
+    }
+}
+
+impl fmt::Debug for LineFilter<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("LineFilter { .. }")
+    }
+}
+
+// A newline callback (see `IndentWriter::set_on_newline`), if there is one.
+// Like filters, callbacks can't be cloned.
+struct OnNewline<'i>(Option<Box<dyn FnMut(u64) + 'i>>);
+
+impl Clone for OnNewline<'_> {
+    #[inline]
+    fn clone(&self) -> Self {
+        OnNewline(None)
+    }
+}
+
+impl fmt::Debug for OnNewline<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OnNewline { .. }")
+    }
+}
+
+// The string used as an indent. A single char is stored inline, so that it
+// doesn't need to be borrowed from elsewhere, and an indent with nowhere to
+// borrow it from (such as a long run of spaces) is owned. A byte indent need
+// not be valid UTF-8.
+#[derive(Debug, Clone)]
+enum Indent<'i> {
+    Str(&'i str),
+    Bytes(&'i [u8]),
+    Char { encoded: [u8; 4], len: usize },
+    Owned(Box<str>),
+}
+
+impl Indent<'_> {
+    #[inline]
+    fn from_char(c: char) -> Self {
+        let mut encoded = [0; 4];
+        let len = c.encode_utf8(&mut encoded).len();
+        Indent::Char { encoded, len }
+    }
+
+    #[inline]
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            Indent::Str(indent) => indent.as_bytes(),
+            Indent::Bytes(indent) => indent,
+            Indent::Char { encoded, len } => &encoded[..*len],
+            Indent::Owned(indent) => indent.as_bytes(),
+        }
+    }
+}
+
+/// Configuration for a line number gutter. See
+/// [`IndentWriter::set_line_numbers`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LineNumberConfig<'a> {
+    /// The number of the first line.
+    pub start: u64,
+
+    /// The minimum width of the line numbers. Shorter numbers are padded
+    /// with spaces on the left, so that they're right-aligned.
+    pub min_width: usize,
+
+    /// The separator written between the line number and the line.
+    pub separator: &'a str,
+}
+
+impl Default for LineNumberConfig<'_> {
+    /// Number lines from 1, with a minimum width of 4 and a separator of
+    /// `" | "`.
+    #[inline]
+    fn default() -> Self {
+        Self {
+            start: 1,
+            min_width: 4,
+            separator: " | ",
+        }
+    }
+}
+
+// Wrapper for the inner writer, which translates line endings and expands tabs
+// (if requested) and keeps track of what has actually been written to it.
+#[derive(Debug, Clone)]
+struct Tracked<'i, W> {
+    writer: W,
+    line_ending: Option<LineEnding>,
+    tab_width: Option<usize>,
+
+    // The unwritten remainder of a translated line ending.
+    pending_ending: &'static [u8],
+
+    // The number of spaces of an expanded tab that are still to be written.
+    pending_spaces: usize,
+
+    // Set if a '\r' was accepted but not yet written, because we don't yet
+    // know if it's part of a "\r\n" to be translated.
+    held_cr: bool,
+
+    // If set, ANSI escape sequences aren't counted in `column_chars`.
+    // `escape` tracks how far through an escape sequence the output is.
+    ansi_aware: bool,
+    escape: Escape,
+
+    bytes_written: u64,
+    lines_written: u64,
+    column: usize,
+    column_chars: usize,
+
+    // Called with `lines_written` each time a newline is written.
+    on_newline: OnNewline<'i>,
+
+    // If set, the writer is flushed after each newline. `flush_pending` is
+    // set if a newline has been written since the writer was last flushed.
+    flush_on_newline: bool,
+    flush_pending: bool,
+
+    // The display width of the current line, and the bytes of a character
+    // which has only been partly written.
+    #[cfg(feature = "unicode-width")]
+    column_width: usize,
+    #[cfg(feature = "unicode-width")]
+    partial_char: ([u8; 4], usize),
+}
+
+// Progress through an ANSI escape sequence in the output.
+#[derive(Debug, Clone, Copy)]
+enum Escape {
+    // Not in an escape sequence.
+    None,
+
+    // After an ESC.
+    Esc,
+
+    // In a control sequence, which ends with a byte in the range 0x40-0x7E.
+    Csi,
+}
+
+// The first non-empty buffer in `bufs`, which is what a non-vectored write
+// would use.
+#[inline]
+fn first_nonempty<'a>(bufs: &'a [IoSlice<'_>]) -> &'a [u8] {
+    bufs.iter()
+        .find(|buf| !buf.is_empty())
+        .map_or(&[], |buf| buf)
+}
+
+impl<'i, W> Tracked<'i, W> {
+    fn map<U>(self, f: impl FnOnce(W) -> U) -> Tracked<'i, U> {
+        Tracked {
+            writer: f(self.writer),
+            line_ending: self.line_ending,
+            tab_width: self.tab_width,
+            pending_ending: self.pending_ending,
+            pending_spaces: self.pending_spaces,
+            held_cr: self.held_cr,
+            ansi_aware: self.ansi_aware,
+            escape: self.escape,
+            bytes_written: self.bytes_written,
+            lines_written: self.lines_written,
+            column: self.column,
+            column_chars: self.column_chars,
+            on_newline: self.on_newline,
+            flush_on_newline: self.flush_on_newline,
+            flush_pending: self.flush_pending,
+            #[cfg(feature = "unicode-width")]
+            column_width: self.column_width,
+            #[cfg(feature = "unicode-width")]
+            partial_char: self.partial_char,
+        }
+    }
+}
+
+const SPACES: &[u8] = b"                                ";
+
+// The number of columns `s` takes up when aligning output: its display width
+// with the `unicode-width` feature, or its number of chars without it.
+// Control characters, which have no display width, count as one column, as
+// they do in `column_chars`.
+#[cfg(feature = "unicode-width")]
+#[inline]
+fn display_width(s: &str) -> usize {
+    use unicode_width::UnicodeWidthChar;
+
+    s.chars().map(|c| c.width().unwrap_or(1)).sum()
+}
+
+#[cfg(not(feature = "unicode-width"))]
+#[inline]
+fn display_width(s: &str) -> usize {
+    s.chars().count()
+}
+
+// True if `b` separates words when wrapping lines.
+#[inline]
+fn is_wrap_space(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\r')
+}
+
+// The start and end of each UTF-8 character in `bytes`, so that wrapping
+// never splits a character.
+fn char_bounds(bytes: &[u8]) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let starts = bytes
+        .iter()
+        .enumerate()
+        .filter(|&(_, &b)| b & 0xC0 != 0x80)
+        .map(|(i, _)| i);
+
+    starts
+        .clone()
+        .zip(starts.skip(1).chain(iter::once(bytes.len())))
+}
+
+impl<W: io::Write> Tracked<'_, W> {
+    fn write_raw(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer
+            .write(buf)
+            .inspect(|&n| self.bytes_written += n as u64)
+    }
+
+    // Write out a partially written line ending or tab. Returns false if the
+    // writer wouldn't accept any of it.
+    fn write_pending_output(&mut self) -> io::Result<bool> {
+        while !self.pending_ending.is_empty() {
+            match self.write_raw(self.pending_ending)? {
+                0 => return Ok(false),
+                n => self.pending_ending = &self.pending_ending[n..],
+            }
+        }
+
+        while self.pending_spaces > 0 {
+            match self.write_raw(&SPACES[..self.pending_spaces.min(SPACES.len())])? {
+                0 => return Ok(false),
+                n => self.pending_spaces -= n,
+            }
+        }
+
+        if self.flush_pending {
+            self.writer.flush()?;
+            self.flush_pending = false;
+        }
+
+        Ok(true)
+    }
+
+    // Flush the writer if a newline has just been written. If it can't be
+    // flushed yet, it's tried again (and any error is reported) before
+    // anything else is written.
+    #[inline]
+    fn flush_newline(&mut self) {
+        if self.flush_pending {
+            let _ = self.write_pending_output();
+        }
+    }
+
+    // Write a tab as the number of spaces needed to reach the next tab stop.
+    fn write_tab(&mut self, width: usize) -> io::Result<usize> {
+        let spaces = width - self.align_column() % width;
+
+        match self.write_raw(&SPACES[..spaces.min(SPACES.len())])? {
+            0 => Ok(0),
+            n => {
+                self.pending_spaces = spaces - n;
+                Ok(1)
+            }
+        }
+    }
+
+    // Write content, translating each "\n" or "\r\n" into the line ending and
+    // expanding each tab (if requested). Returns the number of bytes of `buf`
+    // consumed, which may differ from the number written.
+    fn write_content(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let ending = self.line_ending.map(|ending| ending.as_str().as_bytes());
+        let tab_width = self.tab_width;
+
+        loop {
+            if !self.write_pending_output()? {
+                break Ok(0);
+            } else if self.held_cr {
+                match buf.first() {
+                    None => break Ok(0),
+
+                    // The held '\r' was part of a "\r\n", so drop it; the
+                    // '\n' will be translated.
+                    Some(b'\n') => self.held_cr = false,
+
+                    Some(_) => match self.write_raw(b"\r")? {
+                        0 => break Ok(0),
+                        _ => self.held_cr = false,
+                    },
+                }
+            } else {
+                let special = |&b: &u8| match b {
+                    b'\n' | b'\r' => ending.is_some(),
+                    b'\t' => tab_width.is_some(),
+                    _ => false,
+                };
+
+                break match buf.iter().position(special) {
+                    None => self.write_raw(buf),
+                    Some(0) => match (buf, ending, tab_width) {
+                        ([b'\t', ..], _, Some(width)) => self.write_tab(width),
+
+                        ([b'\n', ..], Some(ending), _) => self.write_raw(ending).map(|n| match n {
+                            0 => 0,
+                            n => {
+                                self.pending_ending = &ending[n..];
+                                1
+                            }
+                        }),
+
+                        // A lone '\r' might be part of a "\r\n" we can't see
+                        // yet; hold it until the next write.
+                        ([b'\r'], Some(_), _) => {
+                            self.held_cr = true;
+                            Ok(1)
+                        }
+
+                        // Drop the '\r' from "\r\n"; the '\n' will be
+                        // translated.
+                        ([b'\r', b'\n', ..], Some(_), _) => Ok(1),
+
+                        _ => self.write_raw(&buf[..1]),
+                    },
+                    Some(len) => self.write_raw(&buf[..len]),
+                };
+            }
+        }
+        .inspect(|&n| self.track(&buf[..n], tab_width))
+    }
+
+    // Write an indent (or other raw bytes), which is never translated or
+    // expanded.
+    fn write_verbatim(&mut self, indent: &[u8]) -> io::Result<usize> {
+        if !self.write_pending_output()? {
+            return Ok(0);
+        }
+
+        if self.held_cr {
+            match self.write_raw(b"\r")? {
+                0 => return Ok(0),
+                _ => self.held_cr = false,
+            }
+        }
+
+        self.write_raw(indent)
+            .inspect(|&n| self.track(&indent[..n], None))
+    }
+
+    // Update the line and column counts with bytes consumed from the input.
+    fn track(&mut self, consumed: &[u8], tab_width: Option<usize>) {
+        let newlines = consumed.iter().filter(|&&b| b == b'\n').count() as u64;
+
+        if newlines > 0 && self.flush_on_newline {
+            self.flush_pending = true;
+        }
+
+        match self.on_newline.0 {
+            None => self.lines_written += newlines,
+            Some(ref mut on_newline) => {
+                for _ in 0..newlines {
+                    self.lines_written += 1;
+                    on_newline(self.lines_written);
+                }
+            }
+        }
+
+        let line = match consumed.iter().rposition(|&b| b == b'\n') {
+            Some(newline) => {
+                self.column = 0;
+                self.column_chars = 0;
+                self.escape = Escape::None;
+                #[cfg(feature = "unicode-width")]
+                self.reset_width();
+                &consumed[newline + 1..]
+            }
+            None => consumed,
+        };
+
+        match (tab_width, self.ansi_aware) {
+            // Count chars by counting every byte that isn't a UTF-8
+            // continuation byte
+            (None, false) => {
+                self.column += line.len();
+                self.column_chars += line.iter().filter(|&&b| b & 0xC0 != 0x80).count();
+                #[cfg(feature = "unicode-width")]
+                line.iter().for_each(|&b| self.track_width(b));
+            }
+
+            _ => {
+                for &b in line {
+                    match tab_width {
+                        _ if self.skip_escape(b) => self.column += 1,
+
+                        Some(width) if b == b'\t' => {
+                            let spaces = width - self.align_column() % width;
+                            self.column += spaces;
+                            self.column_chars += spaces;
+                            #[cfg(feature = "unicode-width")]
+                            {
+                                self.partial_char.1 = 0;
+                                self.column_width += spaces;
+                            }
+                        }
+
+                        _ => {
+                            self.column += 1;
+                            self.column_chars += usize::from(b & 0xC0 != 0x80);
+                            #[cfg(feature = "unicode-width")]
+                            self.track_width(b);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // The column used for alignment, such as when expanding tabs: the
+    // display width of the line with the `unicode-width` feature, or the
+    // number of chars without it.
+    #[cfg(feature = "unicode-width")]
+    #[inline]
+    fn align_column(&self) -> usize {
+        self.column_width
+    }
+
+    #[cfg(not(feature = "unicode-width"))]
+    #[inline]
+    fn align_column(&self) -> usize {
+        self.column_chars
+    }
+
+    // Add the width of the character ending with `b`, once all of its bytes
+    // have been written. Invalid UTF-8 has no width.
+    #[cfg(feature = "unicode-width")]
+    fn track_width(&mut self, b: u8) {
+        let (bytes, len) = &mut self.partial_char;
+
+        if b & 0xC0 != 0x80 || *len == bytes.len() {
+            *len = 0;
+        }
+
+        bytes[*len] = b;
+        *len += 1;
+
+        if let Ok(c) = core::str::from_utf8(&bytes[..*len]) {
+            self.column_width += display_width(c);
+            *len = 0;
+        }
+    }
+
+    #[cfg(feature = "unicode-width")]
+    #[inline]
+    fn reset_width(&mut self) {
+        self.column_width = 0;
+        self.partial_char.1 = 0;
+    }
+
+    // If ANSI escape sequences are recognised, advance through them with
+    // `b`, returning true if `b` is part of one (and so takes up no space).
+    #[inline]
+    fn skip_escape(&mut self, b: u8) -> bool {
+        if !self.ansi_aware {
+            return false;
+        }
+
+        self.escape = match (self.escape, b) {
+            (Escape::None, 0x1B) => Escape::Esc,
+            (Escape::None, _) => return false,
+            (Escape::Esc, b'[') => Escape::Csi,
+
+            // Anything else ends the sequence; ESC and one other byte is the
+            // shortest escape sequence.
+            (Escape::Esc, _) => Escape::None,
+            (Escape::Csi, 0x40..=0x7E) => Escape::None,
+            (Escape::Csi, _) => Escape::Csi,
+        };
+
+        true
+    }
+
+    // Write out a held '\r' or a partially written line ending or tab.
+    fn write_pending(&mut self) -> io::Result<()> {
+        if !self.write_pending_output()? {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+
+        while self.held_cr {
+            match self.write_raw(b"\r")? {
+                0 => return Err(io::ErrorKind::WriteZero.into()),
+                _ => self.held_cr = false,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<W: io::Write> io::Write for Tracked<'_, W> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_content(buf).inspect(|_| self.flush_newline())
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        // Vectored writes can only be forwarded if there's nothing to
+        // translate or expand, and nothing pending.
+        let plain = self.line_ending.is_none()
+            && self.tab_width.is_none()
+            && self.pending_ending.is_empty()
+            && self.pending_spaces == 0
+            && !self.held_cr
+            && !self.flush_pending;
+
+        if !plain {
+            return self.write(first_nonempty(bufs));
+        }
+
+        let written = self.writer.write_vectored(bufs)?;
+        self.bytes_written += written as u64;
+
+        let mut remaining = written;
+
+        for buf in bufs {
+            let consumed = remaining.min(buf.len());
+            self.track(&buf[..consumed], None);
+            remaining -= consumed;
+        }
+
+        self.flush_newline();
+        Ok(written)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_pending()?;
+        self.writer.flush()
+    }
+}
+
 /// Adapter for writers to indent each line
 ///
 /// An `IndentWriter` adapts an [`io::Write`] object to insert an indent before
@@ -44,25 +665,3769 @@ use IndentState::*;
 ///
 /// assert_eq!(indented.get_ref(), b"\tLine 1\n\tLine 2\n\n\n\tLine 3\n\n");
 /// ```
+///
+/// # Indent levels
+///
+/// Each line is prefixed with `indent` repeated once per indent level. A new
+/// writer starts at level 1; the level can be adjusted at any time with
+/// [`inc`][IndentWriter::inc], [`dec`][IndentWriter::dec],
+/// [`reset`][IndentWriter::reset], and
+/// [`set_indent_level`][IndentWriter::set_indent_level]. Changes to the level
+/// take effect at the start of the next line.
+///
+/// Changing the level never writes anything by itself, even if the writer is
+/// flushed: an indent is only written once there's content for it to
+/// precede. So changing the level and then changing it back, without
+/// writing anything in between, has no effect on the output.
 #[derive(Debug, Clone)]
 pub struct IndentWriter<'i, W> {
-    writer: W,
-    indent: &'i str,
-    state: IndentState<'i>,
+    writer: Tracked<'i, W>,
+    indent: Indent<'i>,
+    indent_level: u16,
+    max_level: Option<u16>,
+    base_level: u16,
+
+    // `indent` repeated `indent_level` times. This is what's actually written
+    // at the start of each line.
+    required_indent: Vec<u8>,
+
+    // Set if the indent or level changed while `required_indent` was being
+    // written; it will be rebuilt once the current indent is finished.
+    rebuild_pending: bool,
+
+    // If set, level `n` is indented with `levels[(n - 1) % levels.len()]`
+    // rather than `indent`.
+    levels: Option<&'i [&'i str]>,
+
+    // If set, this is used in place of the indent for all but the last level.
+    guide: Option<&'i str>,
+
+    // If set, each level is indented with spaces up to the next multiple of
+    // this width, rather than with `indent`.
+    tab_stops: Option<usize>,
+
+    // If set, this is written in place of the indent on the next line, after
+    // which it is discarded.
+    first_line_prefix: Option<&'i str>,
+
+    // If set, once the first line prefix is written, `raw_indent` is set to
+    // spaces of the same width.
+    align_continuation: bool,
+
+    // If set, this is used in place of the indent repeated for each level,
+    // until the level is changed.
+    raw_indent: Option<Vec<u8>>,
+
+    // A `write_aligned` which starts partway through a line sets
+    // `continuation` to the column it started at, and lines which begin
+    // during the call are indented to that column. If `continuation_align`
+    // is set, each piece written by `write!` is written that way.
+    continuation_align: bool,
+    continuation: Option<usize>,
+
+    // If set, each indented line is prefixed with its line number. The line
+    // number is computed from `lines_written`; `first_numbered_line` is the
+    // value it will have at the start of the first numbered line.
+    line_numbers: Option<LineNumberConfig<'i>>,
+    first_numbered_line: u64,
+
+    // If set, the first line of each paragraph isn't indented.
+    hanging_indent: bool,
+
+    // Set if the next non-empty line will be the first line of a paragraph;
+    // that is, if it's the first line or follows an empty line.
+    paragraph_start: bool,
+
+    // If set, empty lines are indented as well.
+    indent_empty_lines: bool,
+
+    // If set, this follows the indent on each line, so that the output is a
+    // block of comments. If `comment_blank_lines` is set, empty lines are
+    // prefixed too, without any trailing whitespace from the prefix.
+    comment_prefix: Option<String>,
+    comment_blank_lines: bool,
+
+    // If set, runs of empty lines are limited to this many lines; any more
+    // are discarded. `blank_lines` is the length of the current run.
+    max_blank_lines: Option<usize>,
+    blank_lines: usize,
+
+    // If set, whitespace at the end of each line is discarded.
+    trim_trailing_whitespace: bool,
+
+    // If set, lines containing only whitespace aren't indented.
+    whitespace_only_blank: bool,
+
+    // If set, whitespace at the start of each line is discarded.
+    reindent: bool,
+
+    // If set, writing a '\r' which isn't followed by '\n' is an error.
+    // `unchecked_cr` is set if the last byte written was a '\r', so the next
+    // write must begin with '\n'.
+    reject_lone_cr: bool,
+    unchecked_cr: bool,
+
+    // If set, errors are wrapped in a `LineError`.
+    annotate_errors: bool,
+
+    // Whitespace that has been accepted but not yet written, because we
+    // don't yet know if it's at the end of a line.
+    pending_whitespace: Vec<u8>,
+
+    // If set, each non-empty line is only indented if the filter returns
+    // true for it. The start of each line is buffered in `filter_buffer`
+    // until it's complete; once the filter has been called, its result is
+    // kept in `filter_result` until the buffer has been written out.
+    line_filter: LineFilter<'i>,
+    filter_buffer: Vec<u8>,
+    filter_result: Option<bool>,
+
+    // If set, each non-empty line is padded on the left so that it ends at
+    // this column (after the indent). Lines are buffered as for the line
+    // filter; `align_padding` is the padding for the buffered line, which is
+    // added to the indent when it's written.
+    right_align: Option<usize>,
+    align_padding: usize,
+
+    // If set, "\r\n" is treated as a newline when detecting empty lines.
+    crlf_aware: bool,
+
+    // Set if a '\r' was accepted from a previous write but not yet written,
+    // because we don't yet know if it's part of an empty "\r\n" line.
+    held_cr: bool,
+
+    // If set, nothing has been written yet, and the first line is left
+    // unindented if it starts with a shebang or BOM. The start of the output
+    // is held in `shebang_held` until it's known whether it does.
+    shebang_check: bool,
+    shebang_held: Vec<u8>,
+
+    // If set, lines are wrapped at this width. The current word, and the
+    // whitespace before it, is held in `wrap_held` until it's complete.
+    wrap_width: Option<usize>,
+    wrap_held: Vec<u8>,
+
+    state: IndentState,
+}
+
+impl<'i, W: io::Write> IndentWriter<'i, W> {
+    /// Create a new [`IndentWriter`].
+    pub fn new(indent: &'i str, writer: W) -> Self {
+        Self::with_state(Indent::Str(indent), 1, writer, NeedIndent)
+    }
+
+    /// Create a new [`IndentWriter`] which uses arbitrary bytes as the indent.
+    /// The indent doesn't need to be valid UTF-8, which is useful for
+    /// byte-oriented protocols; if it isn't,
+    /// [`indent`][IndentWriter::indent] returns `None`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new_bytes(&[0xFF], Vec::new());
+    ///
+    /// writeln!(writer, "Line 1").unwrap();
+    /// writer.inc();
+    /// writeln!(writer, "Line 2").unwrap();
+    ///
+    /// assert_eq!(writer.indent(), None);
+    /// assert_eq!(writer.get_ref(), b"\xFFLine 1\n\xFF\xFFLine 2\n");
+    /// ```
+    pub fn new_bytes(indent: &'i [u8], writer: W) -> Self {
+        Self::with_state(Indent::Bytes(indent), 1, writer, NeedIndent)
+    }
+
+    /// Create a new [`IndentWriter`] which uses `c` as the indent. This
+    /// avoids the need to keep a string for the common case where the indent
+    /// is a single space or tab, repeated for each level.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::with_char('\t', Vec::new());
+    ///
+    /// writeln!(writer, "Line 1").unwrap();
+    /// writer.inc();
+    /// writeln!(writer, "Line 2").unwrap();
+    ///
+    /// assert_eq!(writer.indent(), Some("\t"));
+    /// assert_eq!(writer.get_ref(), b"\tLine 1\n\t\tLine 2\n");
+    /// ```
+    pub fn with_char(c: char, writer: W) -> Self {
+        Self::with_state(Indent::from_char(c), 1, writer, NeedIndent)
+    }
+
+    /// Create a new [`IndentWriter`] which indents each level with `n`
+    /// spaces. Up to 32 spaces are borrowed from a static string; a wider
+    /// indent is allocated.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::spaces(4, Vec::new());
+    ///
+    /// writeln!(writer, "Line 1").unwrap();
+    /// writer.inc();
+    /// writeln!(writer, "Line 2").unwrap();
+    ///
+    /// assert_eq!(writer.indent(), Some("    "));
+    /// assert_eq!(writer.get_ref(), b"    Line 1\n        Line 2\n");
+    ///
+    /// // Any width is allowed
+    /// let mut writer = IndentWriter::spaces(40, Vec::new());
+    /// writeln!(writer, "Wide").unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), format!("{:40}Wide\n", "").as_bytes());
+    /// ```
+    pub fn spaces(n: usize, writer: W) -> Self {
+        let indent = match SPACES.get(..n) {
+            Some(spaces) => Indent::Bytes(spaces),
+            None => Indent::Owned(" ".repeat(n).into()),
+        };
+
+        Self::with_state(indent, 1, writer, NeedIndent)
+    }
+
+    /// Create a new [`IndentWriter`] which indents each level with a tab.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::tabs(Vec::new());
+    ///
+    /// writeln!(writer, "Line 1").unwrap();
+    /// writer.inc();
+    /// writeln!(writer, "Line 2").unwrap();
+    ///
+    /// assert_eq!(writer.indent(), Some("\t"));
+    /// assert_eq!(writer.get_ref(), b"\tLine 1\n\t\tLine 2\n");
+    /// ```
+    #[inline]
+    pub fn tabs(writer: W) -> Self {
+        Self::with_char('\t', writer)
+    }
+
+    /// Create a new [`IndentWriter`] starting at the given indent level. A
+    /// `level` of 0 means no indent at all, until the level is increased.
+    /// [`new`][IndentWriter::new] is unchanged: it starts at level 1, so it's
+    /// the same as a `level` of 1.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::with_indent_level("  ", 2, Vec::new());
+    ///
+    /// writeln!(writer, "Line 1").unwrap();
+    /// writer.dec();
+    /// writeln!(writer, "Line 2").unwrap();
+    /// writer.dec();
+    /// writeln!(writer, "Line 3").unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), b"    Line 1\n  Line 2\nLine 3\n");
+    ///
+    /// // Level 0 doesn't indent
+    /// let mut writer = IndentWriter::with_indent_level("  ", 0, Vec::new());
+    /// writeln!(writer, "Line 1").unwrap();
+    /// assert_eq!(writer.get_ref(), b"Line 1\n");
+    /// ```
+    pub fn with_indent_level(indent: &'i str, level: u16, writer: W) -> Self {
+        Self::with_state(Indent::Str(indent), level, writer, NeedIndent)
+    }
+
+    /// Create a new [`IndentWriter`] which uses a different indent string for
+    /// each level, cycling through `levels`: level 1 is indented with
+    /// `levels[0]`, level 2 with `levels[1]`, and so on, wrapping around to
+    /// the start of `levels` once it's exhausted. This is useful for visually
+    /// distinguishing deeply nested content.
+    ///
+    /// [`indent`][IndentWriter::indent] returns the first of `levels`. If
+    /// `levels` is empty, nothing is indented. Calling
+    /// [`set_indent`][IndentWriter::set_indent] replaces the per-level indents
+    /// with a single indent.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::with_levels(&["| ", ": "], Vec::new());
+    ///
+    /// writeln!(writer, "Level 1").unwrap();
+    /// writer.inc();
+    /// writeln!(writer, "Level 2").unwrap();
+    /// writer.inc();
+    /// writeln!(writer, "Level 3").unwrap();
+    ///
+    /// assert_eq!(
+    ///     String::from_utf8(writer.into_inner()).unwrap(),
+    ///     "| Level 1\n| : Level 2\n| : | Level 3\n"
+    /// );
+    /// ```
+    pub fn with_levels(levels: &'i [&'i str], writer: W) -> Self {
+        let indent = levels.first().copied().unwrap_or("");
+        let mut writer = Self::with_state(Indent::Str(indent), 1, writer, NeedIndent);
+
+        writer.levels = Some(levels);
+        writer.rebuild_indent();
+        writer
+    }
+
+    #[inline]
+    fn with_state(indent: Indent<'i>, level: u16, writer: W, state: IndentState) -> Self {
+        let required_indent = indent.as_bytes().repeat(level.into());
+
+        Self {
+            writer: Tracked {
+                writer,
+                line_ending: None,
+                tab_width: None,
+                pending_ending: &[],
+                pending_spaces: 0,
+                held_cr: false,
+                ansi_aware: false,
+                escape: Escape::None,
+                bytes_written: 0,
+                lines_written: 0,
+                column: 0,
+                column_chars: 0,
+                on_newline: OnNewline(None),
+                flush_on_newline: false,
+                flush_pending: false,
+                #[cfg(feature = "unicode-width")]
+                column_width: 0,
+                #[cfg(feature = "unicode-width")]
+                partial_char: ([0; 4], 0),
+            },
+            indent,
+            indent_level: level,
+            max_level: None,
+            base_level: 0,
+            required_indent,
+            rebuild_pending: false,
+            levels: None,
+            guide: None,
+            tab_stops: None,
+            first_line_prefix: None,
+            align_continuation: false,
+            raw_indent: None,
+            continuation_align: false,
+            continuation: None,
+            line_numbers: None,
+            first_numbered_line: 0,
+            hanging_indent: false,
+            paragraph_start: matches!(state, NeedIndent),
+            indent_empty_lines: false,
+            comment_prefix: None,
+            comment_blank_lines: false,
+            max_blank_lines: None,
+            blank_lines: 0,
+            trim_trailing_whitespace: false,
+            whitespace_only_blank: false,
+            reindent: false,
+            reject_lone_cr: false,
+            unchecked_cr: false,
+            annotate_errors: false,
+            pending_whitespace: Vec::new(),
+            line_filter: LineFilter(None),
+            filter_buffer: Vec::new(),
+            filter_result: None,
+            right_align: None,
+            align_padding: 0,
+            crlf_aware: false,
+            held_cr: false,
+            shebang_check: false,
+            shebang_held: Vec::new(),
+            wrap_width: None,
+            wrap_held: Vec::new(),
+            state,
+        }
+    }
+
+    /// Create a new [`IndentWriter`] which will not add an indent to the first
+    /// written line.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut buffer = Vec::new();
+    /// let mut writer = IndentWriter::new_skip_initial("    ", &mut buffer);
+    ///
+    /// writeln!(writer, "Line 1").unwrap();
+    /// writeln!(writer, "Line 2").unwrap();
+    /// writeln!(writer, "Line 3").unwrap();
+    ///
+    /// assert_eq!(buffer, b"Line 1\n    Line 2\n    Line 3\n")
+    /// ```
+    #[inline]
+    pub fn new_skip_initial(indent: &'i str, writer: W) -> Self {
+        Self::with_state(Indent::Str(indent), 1, writer, MidLine)
+    }
+
+    /// Extract the writer from the [`IndentWriter`], discarding any in-progress
+    /// indent state.
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.writer.writer
+    }
+
+    /// Finish writing, ending the current line with a newline if necessary,
+    /// and return the wrapped writer. This ensures that the output ends with
+    /// exactly one newline, as is conventional for text files (unless nothing
+    /// was written at all).
+    ///
+    /// If any content has been written on the current line, a newline is
+    /// written to end it; if the writer is already at the start of a line,
+    /// nothing is written. Either way, the writer is then
+    /// [flushed][io::Write::flush]. This is the same as
+    /// [`finish_with`][Self::finish_with] with [`FinishPolicy::AddNewline`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    /// write!(writer, "Line 1\nLine 2").unwrap();
+    /// assert_eq!(writer.finish().unwrap(), b"  Line 1\n  Line 2\n");
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    /// write!(writer, "Line 1\nLine 2\n").unwrap();
+    /// assert_eq!(writer.finish().unwrap(), b"  Line 1\n  Line 2\n");
+    /// ```
+    #[inline]
+    pub fn finish(self) -> io::Result<W> {
+        self.finish_with(FinishPolicy::AddNewline)
+    }
+
+    /// Finish writing, handling a partial line at the end of the output
+    /// according to `policy`, and return the wrapped writer.
+    ///
+    /// If the writer is at the start of a line, nothing is written, whatever
+    /// the policy. Otherwise, [`AddNewline`][FinishPolicy::AddNewline] ends
+    /// the line, [`Keep`][FinishPolicy::Keep] leaves it as it is (though
+    /// trailing whitespace is still trimmed, if enabled), and
+    /// [`Error`][FinishPolicy::Error] returns an error without writing
+    /// anything more. Unless there's an error, the writer is then
+    /// [flushed][io::Write::flush].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::{FinishPolicy, IndentWriter};
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    /// write!(writer, "Line 1\nLine 2").unwrap();
+    /// assert_eq!(
+    ///     writer.finish_with(FinishPolicy::Keep).unwrap(),
+    ///     b"  Line 1\n  Line 2"
+    /// );
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    /// write!(writer, "Line 1\nLine 2").unwrap();
+    /// assert!(writer.finish_with(FinishPolicy::Error).is_err());
+    /// ```
+    pub fn finish_with(mut self, policy: FinishPolicy) -> io::Result<W> {
+        let mid_line = match self.state {
+            MidLine => {
+                self.writer.column > 0
+                    || self.held_cr
+                    || !self.pending_whitespace.is_empty()
+                    || !self.wrap_held.is_empty()
+            }
+            NeedIndent => {
+                !self.filter_buffer.is_empty()
+                    || !self.shebang_held.is_empty()
+                    || !self.wrap_held.is_empty()
+                    || (!self.pending_whitespace.is_empty() && !self.trim_trailing_whitespace)
+            }
+            WritingIndent(_) => true,
+        };
+
+        match policy {
+            _ if !mid_line => {}
+            FinishPolicy::AddNewline => self.write_all(b"\n")?,
+            FinishPolicy::Keep => {
+                if self.trim_trailing_whitespace {
+                    self.discard_whitespace();
+                }
+
+                if !self.write_pending_whitespace()? {
+                    return Err(io::ErrorKind::WriteZero.into());
+                }
+            }
+            FinishPolicy::Error => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "the output doesn't end with a complete line",
+                ))
+            }
+        }
+
+        self.flush()?;
+        Ok(self.into_inner())
+    }
+
+    /// Get a reference to the wrapped writer
+    #[inline]
+    pub fn get_ref(&self) -> &W {
+        &self.writer.writer
+    }
+
+    /// Get a mutable reference to the wrapped writer
+    ///
+    /// Anything written directly to the wrapped writer bypasses the
+    /// [`IndentWriter`] entirely: it won't be indented, and the
+    /// [`IndentWriter`] won't know about any newlines it contains, so
+    /// subsequent indentation may be inserted in the wrong place.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer.writer
+    }
+
+    /// Replace the wrapped writer with the result of `f`, which is passed the
+    /// current wrapped writer. All of the configuration and state of the
+    /// [`IndentWriter`] is kept, including the indent level, any partially
+    /// written line or indent, and the output counters, so writing can
+    /// continue as if the writer hadn't changed. This is useful for adding a
+    /// [`BufWriter`][std::io::BufWriter] partway through, for instance.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use std::io::BufWriter;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("    ", Vec::new());
+    /// write!(writer, "Line 1\nLine").unwrap();
+    ///
+    /// let mut writer = writer.map_writer(BufWriter::new);
+    /// write!(writer, " 2\nLine 3\n").unwrap();
+    ///
+    /// let output = writer.into_inner().into_inner().unwrap();
+    /// assert_eq!(output, b"    Line 1\n    Line 2\n    Line 3\n");
+    /// ```
+    pub fn map_writer<U, F: FnOnce(W) -> U>(self, f: F) -> IndentWriter<'i, U> {
+        IndentWriter {
+            writer: self.writer.map(f),
+            indent: self.indent,
+            indent_level: self.indent_level,
+            max_level: self.max_level,
+            base_level: self.base_level,
+            required_indent: self.required_indent,
+            rebuild_pending: self.rebuild_pending,
+            levels: self.levels,
+            guide: self.guide,
+            tab_stops: self.tab_stops,
+            first_line_prefix: self.first_line_prefix,
+            align_continuation: self.align_continuation,
+            raw_indent: self.raw_indent,
+            continuation_align: self.continuation_align,
+            continuation: self.continuation,
+            line_numbers: self.line_numbers,
+            first_numbered_line: self.first_numbered_line,
+            hanging_indent: self.hanging_indent,
+            paragraph_start: self.paragraph_start,
+            indent_empty_lines: self.indent_empty_lines,
+            comment_prefix: self.comment_prefix,
+            comment_blank_lines: self.comment_blank_lines,
+            max_blank_lines: self.max_blank_lines,
+            blank_lines: self.blank_lines,
+            trim_trailing_whitespace: self.trim_trailing_whitespace,
+            whitespace_only_blank: self.whitespace_only_blank,
+            reindent: self.reindent,
+            reject_lone_cr: self.reject_lone_cr,
+            unchecked_cr: self.unchecked_cr,
+            annotate_errors: self.annotate_errors,
+            pending_whitespace: self.pending_whitespace,
+            line_filter: self.line_filter,
+            filter_buffer: self.filter_buffer,
+            filter_result: self.filter_result,
+            right_align: self.right_align,
+            align_padding: self.align_padding,
+            crlf_aware: self.crlf_aware,
+            held_cr: self.held_cr,
+            shebang_check: self.shebang_check,
+            shebang_held: self.shebang_held,
+            wrap_width: self.wrap_width,
+            wrap_held: self.wrap_held,
+            state: self.state,
+        }
+    }
+
+    /// Get the total number of bytes written to the wrapped writer, including
+    /// indents. Bytes which have been accepted but not yet forwarded to the
+    /// wrapped writer (such as held whitespace; see
+    /// [`set_trim_trailing_whitespace`][IndentWriter::set_trim_trailing_whitespace])
+    /// aren't included. Anything written directly to the wrapped writer via
+    /// [`get_mut`][IndentWriter::get_mut] isn't included either.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("    ", Vec::new());
+    /// write!(writer, "Line 1\n\nLine 2\n").unwrap();
+    ///
+    /// assert_eq!(writer.bytes_written(), 23);
+    /// ```
+    #[inline]
+    pub fn bytes_written(&self) -> u64 {
+        self.writer.bytes_written
+    }
+
+    /// Get the number of complete lines (that is, newlines) written to the
+    /// wrapped writer.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("    ", Vec::new());
+    /// write!(writer, "Line 1\n\nLine 2\nLine 3").unwrap();
+    ///
+    /// assert_eq!(writer.lines_written(), 3);
+    /// ```
+    #[inline]
+    pub fn lines_written(&self) -> u64 {
+        self.writer.lines_written
+    }
+
+    /// Get the current column of the output; that is, the number of bytes
+    /// written to the wrapped writer since the most recent newline, including
+    /// the indent. Like [`bytes_written`][IndentWriter::bytes_written], this
+    /// only includes bytes actually written through this [`IndentWriter`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("    ", Vec::new());
+    /// write!(writer, "Line 1\nLine 2").unwrap();
+    ///
+    /// assert_eq!(writer.column(), 10);
+    /// ```
+    #[inline]
+    pub fn column(&self) -> usize {
+        self.writer.column
+    }
+
+    /// Get the current column of the output in `char`s, rather than bytes.
+    /// This assumes the output is UTF-8; a partially written character is
+    /// counted as soon as its first byte is written.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("→ ", Vec::new());
+    /// write!(writer, "😀 😀").unwrap();
+    ///
+    /// assert_eq!(writer.column(), 13);
+    /// assert_eq!(writer.column_chars(), 5);
+    /// ```
+    #[inline]
+    pub fn column_chars(&self) -> usize {
+        self.writer.column_chars
+    }
+
+    /// Get the current column of the output as a display width, as it would
+    /// appear in a terminal: wide characters, such as most emoji and CJK
+    /// characters, count as two columns, and combining characters count as
+    /// none. Control characters, such as tabs that aren't expanded, count as
+    /// one column, as in [`column_chars`][IndentWriter::column_chars]. The
+    /// width of each character is found with the
+    /// [`unicode-width`](https://docs.rs/unicode-width) crate.
+    ///
+    /// With the `unicode-width` feature, this column (rather than
+    /// [`column_chars`][IndentWriter::column_chars]) is also used to align
+    /// output: when [expanding tabs][IndentWriter::set_expand_tabs],
+    /// [aligning continuation lines][IndentWriter::set_continuation_align],
+    /// and so on.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    /// write!(writer, "😀 😀").unwrap();
+    ///
+    /// assert_eq!(writer.column_chars(), 5);
+    /// assert_eq!(writer.column_width(), 7);
+    /// ```
+    #[cfg(feature = "unicode-width")]
+    #[inline]
+    pub fn column_width(&self) -> usize {
+        self.writer.column_width
+    }
+
+    /// Check if the writer is at the start of a line; that is, if the next
+    /// byte written will begin a new line, so that it will be preceded by an
+    /// indent (if it's not a newline). This is false if the writer is in the
+    /// middle of a line, including at the start of a writer created with
+    /// [`new_skip_initial`][IndentWriter::new_skip_initial].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("    ", Vec::new());
+    /// assert!(writer.at_line_start());
+    ///
+    /// write!(writer, "Partial").unwrap();
+    /// assert!(!writer.at_line_start());
+    ///
+    /// write!(writer, " line\n").unwrap();
+    /// assert!(writer.at_line_start());
+    /// ```
+    #[inline]
+    pub fn at_line_start(&self) -> bool {
+        match self.state {
+            NeedIndent => self.filter_buffer.is_empty(),
+            WritingIndent(_) => true,
+            MidLine => false,
+        }
+    }
+
+    /// Get the number of bytes of the indent which are still to be written,
+    /// if the indent for the current line has been partially written (for
+    /// instance, because the wrapped writer returned an error). Otherwise,
+    /// this is 0. The rest of the indent is written by the next write or
+    /// flush.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut buffer = [0; 8];
+    /// let mut writer = IndentWriter::new("    ", &mut buffer[..]);
+    ///
+    /// writer.write_all(b"a\nb\n").unwrap_err();
+    /// assert_eq!(writer.pending_indent_len(), 2);
+    /// ```
+    #[inline]
+    pub fn pending_indent_len(&self) -> usize {
+        match self.state {
+            WritingIndent(written) => self.required_indent.len() - written,
+            NeedIndent | MidLine => 0,
+        }
+    }
+
+    /// Get the position of the writer within the current line. This is
+    /// mostly useful for tests and diagnostics; see also
+    /// [`at_line_start`][IndentWriter::at_line_start].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::{IndentWriter, LinePhase};
+    ///
+    /// let mut writer = IndentWriter::new("    ", Vec::new());
+    /// assert_eq!(writer.line_phase(), LinePhase::NeedIndent);
+    ///
+    /// write!(writer, "Partial").unwrap();
+    /// assert_eq!(writer.line_phase(), LinePhase::MidLine);
+    ///
+    /// write!(writer, " line\n").unwrap();
+    /// assert_eq!(writer.line_phase(), LinePhase::NeedIndent);
+    /// ```
+    #[inline]
+    pub fn line_phase(&self) -> LinePhase {
+        match self.state {
+            // Part of the line is buffered for the line filter
+            NeedIndent if !self.filter_buffer.is_empty() => LinePhase::MidLine,
+            MidLine => LinePhase::MidLine,
+            NeedIndent => LinePhase::NeedIndent,
+            WritingIndent(_) => LinePhase::WritingIndent,
+        }
+    }
+
+    /// Get the string being used as an indent for each line, or `None` if
+    /// it isn't valid UTF-8 (see [`new_bytes`][IndentWriter::new_bytes]).
+    #[inline]
+    pub fn indent(&self) -> Option<&str> {
+        std::str::from_utf8(self.indent.as_bytes()).ok()
+    }
+
+    /// Get the bytes being used as an indent for each line.
+    #[inline]
+    pub fn indent_bytes(&self) -> &[u8] {
+        self.indent.as_bytes()
+    }
+
+    /// Get the complete indent which will be written at the start of the
+    /// next line; that is, the indent repeated for the current level,
+    /// including any guides or first line prefix. If the indent for the
+    /// current line is partway through being written, this is the indent for
+    /// the current line, even if the level has since changed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::with_indent_level("  ", 2, Vec::new());
+    /// assert_eq!(writer.current_indent(), b"    ");
+    ///
+    /// writer.set_guide(Some("| "));
+    /// assert_eq!(writer.current_indent(), b"|   ");
+    /// ```
+    #[inline]
+    pub fn current_indent(&self) -> &[u8] {
+        &self.required_indent
+    }
+
+    /// Get the complete indent which will be written at the start of the
+    /// next line, as a string, or `None` if it isn't valid UTF-8. See
+    /// [`current_indent`][IndentWriter::current_indent].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("\t", Vec::new());
+    /// writer.inc();
+    /// assert_eq!(writer.current_indent_str(), Some("\t\t"));
+    /// ```
+    #[inline]
+    pub fn current_indent_str(&self) -> Option<&str> {
+        std::str::from_utf8(&self.required_indent).ok()
+    }
+
+    /// Prefix each indented line with its line number, right-aligned, and a
+    /// separator, before the indent. Pass `None` to stop numbering lines.
+    ///
+    /// Lines are numbered from `config.start`, starting with the next line
+    /// to begin. Every line counts towards the line number, but as with the
+    /// indent, empty lines don't get a line number unless
+    /// [`set_indent_empty_lines`][IndentWriter::set_indent_empty_lines] is
+    /// enabled. Numbers wider than `config.min_width` are written in full,
+    /// which pushes those lines to the right. While line numbers are
+    /// enabled, the line number is included in
+    /// [`current_indent`][IndentWriter::current_indent].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::{IndentWriter, LineNumberConfig};
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    /// writer.set_line_numbers(Some(LineNumberConfig {
+    ///     start: 9,
+    ///     min_width: 2,
+    ///     separator: " | ",
+    /// }));
+    ///
+    /// write!(writer, "fn main() {{\n\n").unwrap();
+    /// writer.inc();
+    /// write!(writer, "run();\n").unwrap();
+    ///
+    /// assert_eq!(
+    ///     String::from_utf8(writer.into_inner()).unwrap(),
+    ///     " 9 |   fn main() {\n\n11 |     run();\n"
+    /// );
+    /// ```
+    pub fn set_line_numbers(&mut self, config: Option<LineNumberConfig<'i>>) {
+        // If we're partway through a line, numbering starts on the next one.
+        self.first_numbered_line = match self.state {
+            NeedIndent => self.writer.lines_written,
+            MidLine | WritingIndent(_) => self.writer.lines_written + 1,
+        };
+
+        self.line_numbers = config;
+        self.rebuild_indent();
+    }
+
+    /// Use `bytes` as the complete indent for each line, in place of the
+    /// indent repeated for each level. This is useful when the prefix is
+    /// computed, rather than a fixed string. As with
+    /// [`new_bytes`][IndentWriter::new_bytes], the bytes need not be valid
+    /// UTF-8. Guides are not applied to the raw indent, but a first line
+    /// prefix still takes precedence over it.
+    ///
+    /// The raw indent lasts until it's cleared with
+    /// [`clear_raw_indent`][IndentWriter::clear_raw_indent], or until the
+    /// indent level is changed (by [`inc`][IndentWriter::inc],
+    /// [`dec`][IndentWriter::dec], [`restore`][IndentWriter::restore], etc.),
+    /// at which point the indent is once again computed from the level.
+    ///
+    /// If a line is currently being written, it is unaffected; the new indent
+    /// takes effect at the start of the next line.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    ///
+    /// for thread in 1..=2 {
+    ///     writer.set_raw_indent(format!("[thread-{}] ", thread).into_bytes());
+    ///     writeln!(writer, "Started").unwrap();
+    /// }
+    ///
+    /// writer.clear_raw_indent();
+    /// writeln!(writer, "Done").unwrap();
+    ///
+    /// assert_eq!(
+    ///     writer.get_ref(),
+    ///     b"[thread-1] Started\n[thread-2] Started\n  Done\n"
+    /// );
+    /// ```
+    pub fn set_raw_indent(&mut self, bytes: Vec<u8>) {
+        self.raw_indent = Some(bytes);
+        self.rebuild_indent();
+    }
+
+    /// Remove an indent set with
+    /// [`set_raw_indent`][IndentWriter::set_raw_indent], so that the indent
+    /// is once again computed from the indent level.
+    pub fn clear_raw_indent(&mut self) {
+        if self.raw_indent.take().is_some() {
+            self.rebuild_indent();
+        }
+    }
+
+    /// Get the indent set with
+    /// [`set_raw_indent`][IndentWriter::set_raw_indent], if any.
+    #[inline]
+    pub fn raw_indent(&self) -> Option<&[u8]> {
+        self.raw_indent.as_deref()
+    }
+
+    /// Replace the string being used as an indent for each line. The indent
+    /// level is unchanged.
+    ///
+    /// If a line is currently being written, it is unaffected; the new indent
+    /// takes effect at the start of the next line. This is true even if the
+    /// old indent was only partially written (for instance, because the
+    /// wrapped writer returned an error): the remainder of the old indent will
+    /// be written before the line continues.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::with_indent_level("\t", 2, Vec::new());
+    ///
+    /// writeln!(writer, "Line 1").unwrap();
+    /// writer.set_indent("  ");
+    /// writeln!(writer, "Line 2").unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), b"\t\tLine 1\n    Line 2\n");
+    /// ```
+    pub fn set_indent(&mut self, indent: &'i str) {
+        self.indent = Indent::Str(indent);
+        self.levels = None;
+        self.rebuild_indent();
+    }
+
+    /// Set a guide string, which is used in place of the indent for every
+    /// level except the last (deepest) one. This is useful for drawing tree
+    /// structures, where each level is marked by a guide, and the indent acts
+    /// as a connector to the content. Passing `None` removes the guide, so that
+    /// the indent is used for every level.
+    ///
+    /// Like [`set_indent`][IndentWriter::set_indent], this takes effect at the
+    /// start of the next line.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::with_indent_level("├── ", 0, Vec::new());
+    /// writer.set_guide(Some("│   "));
+    ///
+    /// writeln!(writer, "root").unwrap();
+    /// writer.inc();
+    /// writeln!(writer, "a").unwrap();
+    /// writer.inc();
+    /// writeln!(writer, "b").unwrap();
+    /// writer.dec();
+    /// writeln!(writer, "c").unwrap();
+    ///
+    /// assert_eq!(
+    ///     String::from_utf8(writer.into_inner()).unwrap(),
+    ///     "root\n├── a\n│   ├── b\n├── c\n"
+    /// );
+    /// ```
+    pub fn set_guide(&mut self, guide: Option<&'i str>) {
+        self.guide = guide;
+        self.rebuild_indent();
+    }
+
+    /// Set a prefix to be written in place of the indent on the next indented
+    /// line. After it has been written once, the writer reverts to normal
+    /// indentation. Passing `None` removes a prefix which hasn't been written
+    /// yet.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    ///
+    /// for item in &["Item 1", "Item 2\ncontinued"] {
+    ///     writer.set_first_line_prefix(Some("- "));
+    ///     writeln!(writer, "{}", item).unwrap();
+    /// }
+    ///
+    /// assert_eq!(writer.get_ref(), b"- Item 1\n- Item 2\n  continued\n");
+    /// ```
+    pub fn set_first_line_prefix(&mut self, prefix: Option<&'i str>) {
+        self.first_line_prefix = prefix;
+        self.rebuild_indent();
+    }
+
+    /// Enable or disable aligned continuation lines. When this is enabled,
+    /// writing a [first line prefix][IndentWriter::set_first_line_prefix]
+    /// sets a [raw indent][IndentWriter::set_raw_indent] of spaces, one per
+    /// `char` of the prefix, so that the lines which follow are aligned with
+    /// the text after the prefix. As with any raw indent, it stays in effect
+    /// until the level changes, or until it's replaced (for instance, by the
+    /// next prefix).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    /// writer.set_align_continuation(true);
+    ///
+    /// writer.set_first_line_prefix(Some("name: "));
+    /// writeln!(writer, "first\nlast").unwrap();
+    /// writer.set_first_line_prefix(Some("address: "));
+    /// writeln!(writer, "1 Main St\nSpringfield").unwrap();
+    ///
+    /// assert_eq!(
+    ///     String::from_utf8(writer.into_inner()).unwrap(),
+    ///     "name: first\n      last\naddress: 1 Main St\n         Springfield\n"
+    /// );
+    /// ```
+    #[inline]
+    pub fn set_align_continuation(&mut self, align_continuation: bool) {
+        self.align_continuation = align_continuation;
+    }
+
+    /// Enable or disable aligning each formatted argument with the column it
+    /// starts at. When this is enabled, each piece of output written with
+    /// [`write!`] (through either [`io::Write::write_fmt`] or
+    /// [`fmt::Write`]) is written with
+    /// [`write_aligned`][IndentWriter::write_aligned], so the continuation
+    /// lines of a multi-line value are aligned under its first line
+    /// (although string literals may be combined with the surrounding text
+    /// into a single piece). Other writes, including
+    /// [`write_all`][io::Write::write_all], are unaffected, so that they
+    /// behave the same as repeated calls to [`write`][io::Write::write].
+    ///
+    /// Only the individual pieces are aligned, so a value
+    /// which is written with several calls (such as a [`Display`][fmt::Display]
+    /// implementation which uses `write!` itself) will be aligned with the
+    /// start of whichever piece contains each newline. Unlike
+    /// [`set_align_continuation`][IndentWriter::set_align_continuation],
+    /// this doesn't require a first line prefix.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    /// writer.set_continuation_align(true);
+    ///
+    /// let value = "first\nsecond";
+    /// writeln!(writer, "value: {}", value).unwrap();
+    /// writeln!(writer, "next").unwrap();
+    ///
+    /// assert_eq!(
+    ///     String::from_utf8(writer.into_inner()).unwrap(),
+    ///     "  value: first\n         second\n  next\n"
+    /// );
+    /// ```
+    #[inline]
+    pub fn set_continuation_align(&mut self, continuation_align: bool) {
+        self.continuation_align = continuation_align;
+    }
+
+    /// Get the current indent level; that is, the number of times the indent
+    /// is repeated at the start of each line.
+    #[inline]
+    pub fn indent_level(&self) -> u16 {
+        self.indent_level
+    }
+
+    /// Increase the indent level by one.
+    #[inline]
+    pub fn inc(&mut self) {
+        self.set_indent_level(self.indent_level.saturating_add(1));
+    }
+
+    /// Decrease the indent level by one. Has no effect if the level is
+    /// already at the [base level][IndentWriter::set_base_level].
+    #[inline]
+    pub fn dec(&mut self) {
+        self.set_indent_level(self.indent_level.saturating_sub(1));
+    }
+
+    /// Increase the indent level by one, or return an error if it's already
+    /// at the [maximum level][IndentWriter::set_max_level], rather than
+    /// leaving it unchanged as [`inc`][IndentWriter::inc] does. If no
+    /// maximum is set, this only fails at [`u16::MAX`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    /// writer.set_max_level(Some(2));
+    ///
+    /// assert!(writer.try_inc().is_ok());
+    ///
+    /// let err = writer.try_inc().unwrap_err();
+    /// assert_eq!(err.max_level(), 2);
+    /// assert_eq!(writer.indent_level(), 2);
+    /// ```
+    pub fn try_inc(&mut self) -> Result<(), IndentDepthError> {
+        let max_level = self.max_level.unwrap_or(u16::MAX);
+
+        if self.indent_level >= max_level {
+            return Err(IndentDepthError { max_level });
+        }
+
+        self.inc();
+        Ok(())
+    }
+
+    /// Reserve space for at least `levels` more indent levels, so that
+    /// increasing the level that far won't need to allocate. This doesn't
+    /// change the indent level, or the output.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("    ", Vec::new());
+    /// writer.reserve_levels(10);
+    /// writer.inc_by(10);
+    ///
+    /// assert_eq!(writer.current_indent().len(), 44);
+    /// ```
+    #[inline]
+    pub fn reserve_levels(&mut self, levels: u16) {
+        self.required_indent
+            .reserve(self.indent.as_bytes().len() * usize::from(levels));
+    }
+
+    /// Increase the indent level by `n`.
+    #[inline]
+    pub fn inc_by(&mut self, n: u16) {
+        self.set_indent_level(self.indent_level.saturating_add(n));
+    }
+
+    /// Decrease the indent level by `n`. If this would take the level below
+    /// the [base level][IndentWriter::set_base_level], the level is set to
+    /// the base level.
+    #[inline]
+    pub fn dec_by(&mut self, n: u16) {
+        self.set_indent_level(self.indent_level.saturating_sub(n));
+    }
+
+    /// Reset the indent level to the
+    /// [base level][IndentWriter::set_base_level]. By default this is 0, so
+    /// that no indent is inserted.
+    #[inline]
+    pub fn reset(&mut self) {
+        self.set_indent_level(self.base_level);
+    }
+
+    /// Assume that the output is at the start of a line, so that the next
+    /// non-empty line written is indented. This is useful after writing
+    /// directly to the wrapped writer with [`get_mut`][IndentWriter::get_mut],
+    /// which the [`IndentWriter`] doesn't see. Any partially written indent is
+    /// abandoned, and the [column][IndentWriter::column] is reset to 0.
+    ///
+    /// The [`IndentWriter`] trusts the caller here: if the output is actually
+    /// in the middle of a line, the indent will be inserted there. Call
+    /// [`flush`][io::Write::flush] before writing to the wrapped writer, so
+    /// that any output the [`IndentWriter`] is still holding is written first.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    /// write!(writer, "Header: ").unwrap();
+    /// writer.get_mut().extend_from_slice(b"raw\n");
+    ///
+    /// writer.reset_line();
+    /// writeln!(writer, "Body").unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), b"  Header: raw\n  Body\n");
+    /// ```
+    pub fn reset_line(&mut self) {
+        self.writer.column = 0;
+        self.writer.column_chars = 0;
+        #[cfg(feature = "unicode-width")]
+        self.writer.reset_width();
+        self.unchecked_cr = false;
+        self.state = NeedIndent;
+    }
+
+    /// [Reset][IndentWriter::reset] the indent level, and
+    /// [reset the line][IndentWriter::reset_line], so that the next non-empty
+    /// line written is indented at the base level. The same caveats as
+    /// [`reset_line`][IndentWriter::reset_line] apply.
+    #[inline]
+    pub fn reset_all(&mut self) {
+        self.reset();
+        self.reset_line();
+    }
+
+    /// Set the indent level directly. This is equivalent to calling
+    /// [`reset`][IndentWriter::reset] followed by `level` calls to
+    /// [`inc`][IndentWriter::inc], but builds the new indent in a single pass.
+    ///
+    /// If a line is currently being written, it is unaffected; the new level
+    /// takes effect at the start of the next line.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    ///
+    /// writeln!(writer, "Level 1").unwrap();
+    /// writer.set_indent_level(3);
+    /// writeln!(writer, "Level 3").unwrap();
+    /// writer.set_indent_level(0);
+    /// writeln!(writer, "Level 0").unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), b"  Level 1\n      Level 3\nLevel 0\n");
+    /// ```
+    pub fn set_indent_level(&mut self, level: u16) {
+        let level = match self.max_level {
+            Some(max_level) => level.min(max_level),
+            None => level,
+        };
+        let level = level.max(self.base_level);
+
+        let old_level = self.indent_level;
+        self.indent_level = level;
+
+        // If `required_indent` is currently just the indent repeated
+        // `old_level` times, it can be resized in place. Changing the level
+        // discards any raw indent.
+        if self.raw_indent.take().is_some()
+            || self.line_numbers.is_some()
+            || self.first_line_prefix.is_some()
+            || self.guide.is_some()
+            || self.levels.is_some()
+            || self.tab_stops.is_some()
+            || self.comment_prefix.is_some()
+            || self.rebuild_pending
+        {
+            self.rebuild_indent();
+        } else if let WritingIndent(_) = self.state {
+            self.rebuild_indent();
+        } else if level > old_level {
+            self.required_indent.extend(
+                iter::repeat(self.indent.as_bytes())
+                    .take((level - old_level).into())
+                    .flatten(),
+            );
+        } else {
+            self.required_indent
+                .truncate(self.indent.as_bytes().len() * usize::from(level));
+        }
+    }
+
+    /// Set the indent level, as with
+    /// [`set_indent_level`][IndentWriter::set_indent_level], returning the
+    /// previous level so that it can be restored later.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    ///
+    /// let old_level = writer.replace_indent_level(3);
+    /// writeln!(writer, "Level 3").unwrap();
+    /// writer.set_indent_level(old_level);
+    /// writeln!(writer, "Level 1").unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), b"      Level 3\n  Level 1\n");
+    /// ```
+    #[inline]
+    pub fn replace_indent_level(&mut self, level: u16) -> u16 {
+        let old_level = self.indent_level;
+        self.set_indent_level(level);
+        old_level
+    }
+
+    /// Set the maximum indent level. While a maximum is set, attempts to set
+    /// the indent level any higher (via [`inc`][IndentWriter::inc],
+    /// [`set_indent_level`][IndentWriter::set_indent_level], etc.) set it to
+    /// the maximum instead. If the current level is above the new maximum, it
+    /// is reduced to the maximum.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    /// writer.set_max_level(Some(2));
+    ///
+    /// writer.inc_by(10);
+    /// assert_eq!(writer.indent_level(), 2);
+    /// writeln!(writer, "Level 2").unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), b"    Level 2\n");
+    /// ```
+    pub fn set_max_level(&mut self, max_level: Option<u16>) {
+        self.max_level = max_level;
+
+        if max_level.is_some_and(|max_level| self.indent_level > max_level) {
+            self.set_indent_level(self.indent_level);
+        }
+    }
+
+    /// Get the maximum indent level, if any. See
+    /// [`set_max_level`][IndentWriter::set_max_level].
+    #[inline]
+    pub fn max_level(&self) -> Option<u16> {
+        self.max_level
+    }
+
+    /// Set the base indent level. The indent level never goes below the
+    /// base: [`reset`][IndentWriter::reset] returns to it rather than to 0,
+    /// and [`dec`][IndentWriter::dec] and similar methods stop at it. If the
+    /// current level is below the new base, it is raised to the base.
+    ///
+    /// The base takes precedence over the
+    /// [maximum level][IndentWriter::set_max_level] if the two conflict.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    /// writer.set_base_level(2);
+    /// assert_eq!(writer.indent_level(), 2);
+    ///
+    /// writer.inc();
+    /// writeln!(writer, "Level 3").unwrap();
+    /// writer.reset();
+    /// writeln!(writer, "Level 2").unwrap();
+    /// writer.dec();
+    /// writeln!(writer, "Still level 2").unwrap();
+    ///
+    /// assert_eq!(
+    ///     writer.get_ref(),
+    ///     b"      Level 3\n    Level 2\n    Still level 2\n"
+    /// );
+    /// ```
+    pub fn set_base_level(&mut self, base_level: u16) {
+        self.base_level = base_level;
+
+        if self.indent_level < base_level {
+            self.set_indent_level(base_level);
+        }
+    }
+
+    /// Get the base indent level. See
+    /// [`set_base_level`][IndentWriter::set_base_level].
+    #[inline]
+    pub fn base_level(&self) -> u16 {
+        self.base_level
+    }
+
+    /// Enable or disable hanging indent mode. In this mode, the first line of
+    /// each paragraph is left unindented, and only the subsequent lines of the
+    /// paragraph are indented. A paragraph begins at the first line written,
+    /// and after each empty line.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("    ", Vec::new());
+    /// writer.set_hanging_indent(true);
+    ///
+    /// write!(writer, "Para 1\ncontinued\n\nPara 2\ncontinued\n").unwrap();
+    ///
+    /// assert_eq!(
+    ///     writer.get_ref(),
+    ///     b"Para 1\n    continued\n\nPara 2\n    continued\n"
+    /// );
+    /// ```
+    #[inline]
+    pub fn set_hanging_indent(&mut self, hanging_indent: bool) {
+        self.hanging_indent = hanging_indent;
+    }
+
+    /// Enable or disable preserving a shebang or byte order mark. When this
+    /// is enabled, the first line of the output is left unindented if it
+    /// starts with a `#!` shebang (such as `#!/bin/sh`) or a UTF-8 BOM, so
+    /// that a whole script or file can be indented without breaking it.
+    ///
+    /// This only affects the first line, so it has no effect if anything has
+    /// already been written. Until enough of the first line has been written
+    /// to tell if it starts with a shebang or BOM, it's held back, and written
+    /// by the next write or flush.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    /// writer.set_preserve_shebang(true);
+    ///
+    /// write!(writer, "#!/bin/sh\necho hello\n").unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), b"#!/bin/sh\n  echo hello\n");
+    /// ```
+    #[inline]
+    pub fn set_preserve_shebang(&mut self, preserve_shebang: bool) {
+        self.shebang_check = preserve_shebang
+            && matches!(self.state, NeedIndent)
+            && self.writer.bytes_written == 0
+            && self.writer.lines_written == 0;
+    }
+
+    /// Enable or disable indentation of empty lines. By default, only
+    /// non-empty lines are indented; when this is enabled, every line is
+    /// indented, including empty ones.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("> ", Vec::new());
+    /// writer.set_indent_empty_lines(true);
+    ///
+    /// write!(writer, "Line 1\n\nLine 2\n").unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), b"> Line 1\n> \n> Line 2\n");
+    /// ```
+    #[inline]
+    pub fn set_indent_empty_lines(&mut self, indent_empty_lines: bool) {
+        self.indent_empty_lines = indent_empty_lines;
+    }
+
+    /// Set a comment prefix, such as `"// "` or `"# "`, which is written
+    /// after the indent on each non-empty line, so that the output is a
+    /// block of comments. This is useful for writing headers in generated
+    /// code. Passing `None` (the default) removes the prefix.
+    ///
+    /// By default, empty lines are left empty, as usual; see
+    /// [`set_comment_blank_lines`][IndentWriter::set_comment_blank_lines]
+    /// to comment them too. Lines broken by
+    /// [`set_wrap_at`][IndentWriter::set_wrap_at] are commented like any
+    /// other, and the prefix counts towards their width.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("    ", Vec::new());
+    /// writer.set_comment_prefix(Some("// ".to_owned()));
+    ///
+    /// write!(writer, "Generated code\n\nDo not edit\n").unwrap();
+    ///
+    /// assert_eq!(
+    ///     writer.get_ref(),
+    ///     b"    // Generated code\n\n    // Do not edit\n"
+    /// );
+    /// ```
+    pub fn set_comment_prefix(&mut self, comment_prefix: Option<String>) {
+        self.comment_prefix = comment_prefix;
+        self.rebuild_indent();
+    }
+
+    /// Get the comment prefix set with
+    /// [`set_comment_prefix`][IndentWriter::set_comment_prefix], if any.
+    #[inline]
+    pub fn comment_prefix(&self) -> Option<&str> {
+        self.comment_prefix.as_deref()
+    }
+
+    /// Enable or disable commenting of empty lines, when a
+    /// [comment prefix][IndentWriter::set_comment_prefix] is set. When this
+    /// is enabled, each empty line is written with the indent and the
+    /// comment prefix, without any trailing whitespace from the prefix, so
+    /// that a comment block with paragraphs stays in one piece. This is
+    /// disabled by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("    ", Vec::new());
+    /// writer.set_comment_prefix(Some("// ".to_owned()));
+    /// writer.set_comment_blank_lines(true);
+    ///
+    /// write!(writer, "Generated code\n\nDo not edit\n").unwrap();
+    ///
+    /// assert_eq!(
+    ///     writer.get_ref(),
+    ///     b"    // Generated code\n    //\n    // Do not edit\n"
+    /// );
+    /// ```
+    #[inline]
+    pub fn set_comment_blank_lines(&mut self, comment_blank_lines: bool) {
+        self.comment_blank_lines = comment_blank_lines;
+    }
+
+    /// Limit runs of consecutive empty lines to at most `max` lines; any
+    /// further empty lines are discarded. Passing `None` (the default) allows
+    /// any number of empty lines. Leading empty lines count as a run as well,
+    /// so a `max` of 0 removes empty lines entirely.
+    ///
+    /// A line containing only whitespace is only treated as empty if it's
+    /// removed by [`set_trim_trailing_whitespace`][IndentWriter::set_trim_trailing_whitespace]
+    /// or [`set_reindent`][IndentWriter::set_reindent].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    /// writer.set_collapse_blank_lines(Some(1));
+    ///
+    /// write!(writer, "Line 1\n\n\n\nLine 2\n").unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), b"  Line 1\n\n  Line 2\n");
+    /// ```
+    #[inline]
+    pub fn set_collapse_blank_lines(&mut self, max: Option<usize>) {
+        self.max_blank_lines = max;
+    }
+
+    /// Enable or disable trimming of trailing whitespace. When this is
+    /// enabled, spaces and tabs at the end of each line are discarded, and
+    /// lines consisting only of whitespace are treated as empty lines. In
+    /// CRLF-aware mode (see [`set_crlf_aware`][IndentWriter::set_crlf_aware]),
+    /// a `\r` that isn't part of a `\r\n` is also considered whitespace.
+    ///
+    /// Whitespace is held by the writer until it's known whether it's
+    /// followed by more content on the same line, so whitespace at the very
+    /// end of the output is never written, even if the writer is flushed. If
+    /// trimming is disabled while whitespace is being held, it takes effect
+    /// once that whitespace has been resolved.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("    ", Vec::new());
+    /// writer.set_trim_trailing_whitespace(true);
+    ///
+    /// write!(writer, "Line 1  \n  \n  Line\t2\t\n").unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), b"    Line 1\n\n      Line\t2\n");
+    /// ```
+    #[inline]
+    pub fn set_trim_trailing_whitespace(&mut self, trim_trailing_whitespace: bool) {
+        self.trim_trailing_whitespace = trim_trailing_whitespace;
+    }
+
+    /// Enable or disable treating lines which contain only spaces and tabs as
+    /// empty lines, so that they aren't indented. Unlike with
+    /// [`set_trim_trailing_whitespace`][IndentWriter::set_trim_trailing_whitespace]
+    /// (which also treats such lines as empty), the whitespace is still
+    /// written. This has no effect if
+    /// [`set_indent_empty_lines`][IndentWriter::set_indent_empty_lines] is
+    /// enabled.
+    ///
+    /// As with trimming, whitespace at the start of a line is held by the
+    /// writer until it's known whether the line is empty.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("    ", Vec::new());
+    /// writer.set_treat_whitespace_only_as_blank(true);
+    ///
+    /// write!(writer, "Line 1\n  \n  Line 2 \n").unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), b"    Line 1\n  \n      Line 2 \n");
+    /// ```
+    #[inline]
+    pub fn set_treat_whitespace_only_as_blank(&mut self, whitespace_only_blank: bool) {
+        self.whitespace_only_blank = whitespace_only_blank;
+    }
+
+    /// Enable or disable re-indentation. When this is enabled, any spaces and
+    /// tabs at the start of each line are discarded before the indent is
+    /// written, so that the content's own indentation is replaced by the
+    /// writer's. Lines consisting only of whitespace become empty lines.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("    ", Vec::new());
+    /// writer.set_reindent(true);
+    ///
+    /// write!(writer, "Line 1\n\t  Line 2\n  \n Line 3\n").unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), b"    Line 1\n    Line 2\n\n    Line 3\n");
+    /// ```
+    #[inline]
+    pub fn set_reindent(&mut self, reindent: bool) {
+        self.reindent = reindent;
+    }
+
+    /// Set a filter which decides which lines are indented. The filter is
+    /// called with the content of each non-empty line (without its line
+    /// ending), and the line is only indented if it returns true; otherwise,
+    /// the line is written without an indent. Empty lines aren't passed to
+    /// the filter.
+    ///
+    /// To make this possible, the start of each line is buffered until the
+    /// whole line has been written, or until the writer is
+    /// [flushed][io::Write::flush]. If the writer is flushed partway through
+    /// a line, the filter is called with the part of the line written so
+    /// far. Buffered content isn't counted by
+    /// [`bytes_written`][IndentWriter::bytes_written] until it's been
+    /// written out, and is lost if the writer is dropped without being
+    /// flushed.
+    ///
+    /// The filter isn't cloned along with the writer: a
+    /// [clone][Clone::clone] of the writer has no filter, and indents every
+    /// line, including any line that was buffered when it was cloned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("    ", Vec::new());
+    /// writer.set_line_filter(|line: &[u8]| !line.starts_with(b"#"));
+    ///
+    /// write!(writer, "# Heading\nBody\n\n# Heading\nBody\n").unwrap();
+    /// writer.flush().unwrap();
+    ///
+    /// assert_eq!(
+    ///     writer.get_ref(),
+    ///     b"# Heading\n    Body\n\n# Heading\n    Body\n"
+    /// );
+    /// ```
+    pub fn set_line_filter<F>(&mut self, filter: F)
+    where
+        F: FnMut(&[u8]) -> bool + 'i,
+    {
+        self.line_filter = LineFilter(Some(Box::new(filter)));
+    }
+
+    /// Remove a filter set with
+    /// [`set_line_filter`][IndentWriter::set_line_filter], so that every
+    /// non-empty line is indented again. If part of a line has been
+    /// buffered, that line is still indented according to the old filter.
+    pub fn clear_line_filter(&mut self) {
+        if !self.filter_buffer.is_empty() && self.filter_result.is_none() {
+            self.filter_line();
+        }
+
+        self.line_filter = LineFilter(None);
+    }
+
+    /// Set a callback which is called each time a newline is written to the
+    /// wrapped writer, with the new number of
+    /// [lines written][IndentWriter::lines_written]. This is useful for
+    /// reporting progress or collecting statistics, without wrapping the
+    /// wrapped writer.
+    ///
+    /// The callback is called exactly once for each newline, when it's
+    /// actually written, so a newline which is held by the writer (for
+    /// example, by a [line filter][IndentWriter::set_line_filter]) isn't
+    /// reported until it's written out. Newlines written directly to the
+    /// wrapped writer via [`get_mut`][IndentWriter::get_mut] aren't
+    /// reported.
+    ///
+    /// The callback isn't cloned along with the writer: a
+    /// [clone][Clone::clone] of the writer has no callback.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut lines = Vec::new();
+    ///
+    /// let mut writer = IndentWriter::new("    ", Vec::new());
+    /// writer.set_on_newline(|line| lines.push(line));
+    ///
+    /// write!(writer, "Line 1\n\nLine 2\nLine 3").unwrap();
+    /// drop(writer);
+    ///
+    /// assert_eq!(lines, [1, 2, 3]);
+    /// ```
+    pub fn set_on_newline<F>(&mut self, on_newline: F)
+    where
+        F: FnMut(u64) + 'i,
+    {
+        self.writer.on_newline = OnNewline(Some(Box::new(on_newline)));
+    }
+
+    /// Remove a callback set with
+    /// [`set_on_newline`][IndentWriter::set_on_newline].
+    #[inline]
+    pub fn clear_on_newline(&mut self) {
+        self.writer.on_newline = OnNewline(None);
+    }
+
+    /// Enable or disable flushing after each line. When this is enabled, the
+    /// wrapped writer is [flushed][io::Write::flush] each time a newline is
+    /// written to it, so that each line appears as soon as it's complete.
+    /// This is useful for interactive output, at the cost of throughput.
+    /// This is disabled by default.
+    ///
+    /// The flush happens straight after the newline, before the indent for
+    /// the next line is written. If it fails, the write of the newline still
+    /// succeeds; the flush is tried again before anything else is written,
+    /// and any error is reported then.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    /// writer.set_flush_on_newline(true);
+    ///
+    /// writeln!(writer, "Progress: 50%").unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), b"  Progress: 50%\n");
+    /// ```
+    #[inline]
+    pub fn set_flush_on_newline(&mut self, flush_on_newline: bool) {
+        self.writer.flush_on_newline = flush_on_newline;
+    }
+
+    /// Enable or disable strict checking for lone `\r`s. When this is
+    /// enabled, writing a `\r` which isn't immediately followed by a `\n` is
+    /// an error of kind [`InvalidData`][io::ErrorKind::InvalidData]. This
+    /// catches accidental classic Mac OS line endings, which would otherwise
+    /// be written without any indentation.
+    ///
+    /// Nothing is written for the offending `\r`, unless it's the only byte
+    /// of a write; in that case, it can't be known until the next write
+    /// whether it's followed by a `\n`, so it's written and the error is
+    /// reported by the next write instead.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::io::{ErrorKind, Write};
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("    ", Vec::new());
+    /// writer.set_reject_lone_cr(true);
+    ///
+    /// writer.write_all(b"Line 1\r\n").unwrap();
+    ///
+    /// let err = writer.write_all(b"Line 2\rLine 3\n").unwrap_err();
+    /// assert_eq!(err.kind(), ErrorKind::InvalidData);
+    /// assert_eq!(writer.get_ref(), b"    Line 1\r\n    Line 2");
+    /// ```
+    #[inline]
+    pub fn set_reject_lone_cr(&mut self, reject_lone_cr: bool) {
+        self.reject_lone_cr = reject_lone_cr;
+        self.unchecked_cr = false;
+    }
+
+    /// Enable or disable annotation of errors with the line being written.
+    /// When this is enabled, any error returned by a write or flush is
+    /// wrapped in a [`LineError`], which records the value of
+    /// [`lines_written`][IndentWriter::lines_written] when the error
+    /// occurred. The [kind][io::Error::kind] of the error is unchanged, and
+    /// the original error can be retrieved from the [`LineError`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::io::{self, Write};
+    /// use indent_write::io::{IndentWriter, LineError};
+    ///
+    /// // A writer which fails once it's been given 12 bytes
+    /// # struct Disk(Vec<u8>);
+    /// #
+    /// # impl Write for Disk {
+    /// #     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    /// #         match self.0.len() {
+    /// #             0..12 => self.0.write(buf),
+    /// #             _ => Err(io::Error::new(io::ErrorKind::StorageFull, "disk full")),
+    /// #         }
+    /// #     }
+    /// #
+    /// #     fn flush(&mut self) -> io::Result<()> {
+    /// #         Ok(())
+    /// #     }
+    /// # }
+    /// let disk = Disk(Vec::new());
+    ///
+    /// let mut writer = IndentWriter::new("    ", disk);
+    /// writer.set_annotate_errors(true);
+    ///
+    /// let err = writer.write_all(b"Line 1\nLine 2\nLine 3\n").unwrap_err();
+    /// assert_eq!(err.kind(), io::ErrorKind::StorageFull);
+    ///
+    /// let err = err.get_ref().unwrap().downcast_ref::<LineError>().unwrap();
+    /// assert_eq!(err.lines_written(), 1);
+    /// assert_eq!(err.to_string(), "error writing line 2: disk full");
+    /// ```
+    #[inline]
+    pub fn set_annotate_errors(&mut self, annotate_errors: bool) {
+        self.annotate_errors = annotate_errors;
+    }
+
+    // Wrap `error` in a `LineError`, if requested.
+    fn annotate_error(&self, error: io::Error) -> io::Error {
+        match self.annotate_errors {
+            true => LineError {
+                lines_written: self.lines_written(),
+                error,
+            }
+            .into(),
+            false => error,
+        }
+    }
+
+    /// Enable or disable CRLF awareness. By default, only `\n` is considered
+    /// when detecting empty lines, so a line consisting only of `\r\n` is
+    /// indented. When CRLF awareness is enabled, a `\r` immediately preceding
+    /// a `\n` is treated as part of the newline, so such lines are left
+    /// unindented.
+    ///
+    /// In this mode, a `\r` at the very end of a write can't be classified
+    /// until the next write, so it is held until then. If the writer is
+    /// flushed while holding a `\r`, it is written out as the end of an empty
+    /// line.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("\t", Vec::new());
+    /// writer.set_crlf_aware(true);
+    ///
+    /// write!(writer, "Line 1\r\n\r\nLine 2\r\n").unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), b"\tLine 1\r\n\r\n\tLine 2\r\n");
+    /// ```
+    #[inline]
+    pub fn set_crlf_aware(&mut self, crlf_aware: bool) {
+        self.crlf_aware = crlf_aware;
+    }
+
+    /// Set the line ending sequence to write. By default, newlines are written
+    /// exactly as they appear in the input. If a line ending is set, each
+    /// `\n` or `\r\n` in the input is translated to that line ending; a `\r`
+    /// which isn't part of a `\r\n` is not considered to be a newline, and is
+    /// written unchanged. This also enables CRLF awareness (see
+    /// [`set_crlf_aware`][IndentWriter::set_crlf_aware]), since `\r\n` is
+    /// recognized as a newline.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::{IndentWriter, LineEnding};
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    /// writer.set_line_ending(Some(LineEnding::CrLf));
+    ///
+    /// write!(writer, "Line 1\n\r\nLine 2\r\n").unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), b"  Line 1\r\n\r\n  Line 2\r\n");
+    /// ```
+    #[inline]
+    pub fn set_line_ending(&mut self, line_ending: Option<LineEnding>) {
+        self.writer.line_ending = line_ending;
+    }
+
+    /// Set the tab width used to expand tabs. By default, tabs are written
+    /// unchanged. If a width is set, each `\t` in the content (but not in the
+    /// indent) is replaced with enough spaces to reach the next column that
+    /// is a multiple of `width`. Columns are counted in `char`s, including
+    /// the indent; see [`column_chars`][IndentWriter::column_chars]. With the
+    /// `unicode-width` feature, they're counted by display width instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` is `Some(0)`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    /// writer.set_expand_tabs(Some(4));
+    ///
+    /// write!(writer, "a\tb\nabcd\te").unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), b"  a b\n  abcd  e");
+    /// ```
+    #[inline]
+    pub fn set_expand_tabs(&mut self, width: Option<usize>) {
+        assert!(width != Some(0), "tab width must be non-zero");
+        self.writer.tab_width = width;
+    }
+
+    /// Set the width at which to wrap long lines. By default, lines are
+    /// written at whatever length they are. If a width is set, then whenever
+    /// the next word would take a line past `width` columns (including the
+    /// indent), a newline is written before it instead, so that it starts the
+    /// next line, indented as usual; the whitespace at the break is discarded.
+    /// A word which is too long to fit on a line of its own is broken between
+    /// characters. Columns are counted as for
+    /// [`set_expand_tabs`][IndentWriter::set_expand_tabs].
+    ///
+    /// Words are separated by spaces and tabs. Each word is held back until
+    /// the whitespace or newline after it is written, so that it can be moved
+    /// to the next line if necessary. [Flushing][io::Write::flush] writes the
+    /// held word, so a word shouldn't be split across a flush.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` is `Some(0)`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    /// writer.set_wrap_at(Some(12));
+    ///
+    /// writeln!(writer, "The quick brown fox jumps").unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), b"  The quick\n  brown fox\n  jumps\n");
+    /// ```
+    #[inline]
+    pub fn set_wrap_at(&mut self, width: Option<usize>) {
+        assert!(width != Some(0), "wrap width must be non-zero");
+        self.wrap_width = width;
+    }
+
+    /// Set a width to right-align lines to. By default, each line starts
+    /// straight after its indent. If a width is set, each non-empty line is
+    /// padded with spaces after the indent, so that its content ends `width`
+    /// columns after the indent; lines which are already at least `width`
+    /// columns long aren't padded. This is useful for columns of numbers, or
+    /// other tables. Columns are counted as for
+    /// [`set_expand_tabs`][IndentWriter::set_expand_tabs], but tabs in the
+    /// content aren't expanded before the line is measured.
+    ///
+    /// To know how much padding each line needs, the line is buffered until
+    /// it's complete, as with
+    /// [`set_line_filter`][IndentWriter::set_line_filter]. If the writer is
+    /// [flushed][io::Write::flush] partway through a line, the line is
+    /// aligned according to the part of it written so far. A line which the
+    /// line filter leaves unindented isn't padded either.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("| ", Vec::new());
+    /// writer.set_right_align(Some(6));
+    ///
+    /// write!(writer, "1.5\n\n-20.25\n300\n").unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), b"|    1.5\n\n| -20.25\n|    300\n");
+    /// ```
+    pub fn set_right_align(&mut self, width: Option<usize>) {
+        // A buffered line is aligned according to the old width
+        if !self.filter_buffer.is_empty() && self.filter_result.is_none() {
+            self.filter_line();
+        }
+
+        self.right_align = width;
+    }
+
+    /// Enable or disable recognition of ANSI escape sequences, such as the
+    /// SGR sequences (`"\x1b[...m"`) used to color terminal output. When this
+    /// is enabled, escape sequences take up no space in
+    /// [`column_chars`][IndentWriter::column_chars], and so don't affect
+    /// where [expanded tabs][IndentWriter::set_expand_tabs] stop. They're
+    /// still written unchanged, and still counted by
+    /// [`column`][IndentWriter::column], which counts bytes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    /// writer.set_ansi_aware(true);
+    /// writer.set_expand_tabs(Some(4));
+    ///
+    /// write!(writer, "\x1b[31mred\x1b[0m\tplain").unwrap();
+    ///
+    /// assert_eq!(writer.column_chars(), 13);
+    /// assert_eq!(writer.get_ref(), b"  \x1b[31mred\x1b[0m   plain");
+    /// ```
+    #[inline]
+    pub fn set_ansi_aware(&mut self, ansi_aware: bool) {
+        self.writer.ansi_aware = ansi_aware;
+    }
+
+    /// Indent with tab stops, rather than the indent string. If a width is
+    /// set, each indent level is written as enough spaces to reach the next
+    /// column that is a multiple of `width`, the way a tab would be
+    /// displayed. This accounts for anything written before the indent, such
+    /// as a [line number][IndentWriter::set_line_numbers] of varying width.
+    /// [Guides][IndentWriter::set_guide] are still written as they are, but
+    /// the last level is aligned to the next tab stop after them.
+    ///
+    /// Columns are counted in `char`s. Like
+    /// [`set_indent`][IndentWriter::set_indent], this takes effect at the
+    /// start of the next line.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` is `Some(0)`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::{IndentWriter, LineNumberConfig};
+    ///
+    /// let mut writer = IndentWriter::new("\t", Vec::new());
+    /// writer.set_align_to_tab_stops(Some(4));
+    /// writer.set_line_numbers(Some(LineNumberConfig {
+    ///     start: 9,
+    ///     min_width: 1,
+    ///     separator: " ",
+    /// }));
+    ///
+    /// writeln!(writer, "Line 9\nLine 10").unwrap();
+    ///
+    /// assert_eq!(
+    ///     String::from_utf8(writer.into_inner()).unwrap(),
+    ///     "9   Line 9\n10  Line 10\n"
+    /// );
+    /// ```
+    pub fn set_align_to_tab_stops(&mut self, width: Option<usize>) {
+        assert!(width != Some(0), "tab width must be non-zero");
+        self.tab_stops = width;
+        self.rebuild_indent();
+    }
+
+    /// Write a newline, ending the current line (or writing an empty line,
+    /// if the writer is already at the start of one). The next content
+    /// written will be indented. The newline is written as the configured
+    /// [line ending][IndentWriter::set_line_ending], if there is one.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::{IndentWriter, LineEnding};
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    /// writer.set_line_ending(Some(LineEnding::CrLf));
+    ///
+    /// write!(writer, "Line 1").unwrap();
+    /// writer.newline().unwrap();
+    /// writer.newline().unwrap();
+    /// write!(writer, "Line 2").unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), b"  Line 1\r\n\r\n  Line 2");
+    /// ```
+    #[inline]
+    pub fn newline(&mut self) -> io::Result<()> {
+        self.write_all(b"\n")
+    }
+
+    /// Write formatted arguments, such as those created by
+    /// [`format_args!`], streaming each formatted piece through the
+    /// indentation as it's produced. Nothing is allocated: the arguments are
+    /// never collected into an intermediate [`String`].
+    ///
+    /// This is the same as [`io::Write::write_fmt`], which is what [`write!`]
+    /// uses, and is provided for code which is handed a [`fmt::Arguments`]
+    /// to write.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    /// let (name, value) = ("x", 1);
+    ///
+    /// writer
+    ///     .write_fmt_indented(format_args!("{}:\n{}\n", name, value))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), b"  x:\n  1\n");
+    /// ```
+    #[inline]
+    pub fn write_fmt_indented(&mut self, args: fmt::Arguments<'_>) -> io::Result<()> {
+        io::Write::write_fmt(self, args)
+    }
+
+    /// Write all of `buf`, aligning it with the column it starts at. If this
+    /// is called partway through a line, any lines which begin during the
+    /// call are indented with spaces up to the column (in `char`s) where the
+    /// call began, rather than with the usual indent. Once the call returns,
+    /// the usual indent is used again. At the start of a line, this is the
+    /// same as [`write_all`][io::Write::write_all].
+    ///
+    /// See also [`set_continuation_align`][IndentWriter::set_continuation_align],
+    /// which does this for each argument written with [`write!`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    ///
+    /// writer.write_all(b"value: ").unwrap();
+    /// writer.write_aligned(b"first\nsecond\n").unwrap();
+    /// writer.write_all(b"next\n").unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), b"  value: first\n         second\n  next\n");
+    /// ```
+    pub fn write_aligned(&mut self, buf: &[u8]) -> io::Result<()> {
+        let column = self.writer.align_column() + self.pending_whitespace.len();
+
+        if !matches!(self.state, MidLine) || column == 0 {
+            return self.write_all(buf);
+        }
+
+        self.continuation = Some(column);
+        self.rebuild_indent();
+
+        let result = self.write_all(buf);
+
+        self.continuation = None;
+        self.rebuild_indent();
+        result
+    }
+
+    // Write a piece of formatted output, aligning it if that's been
+    // requested with `set_continuation_align`.
+    #[inline]
+    fn write_piece(&mut self, buf: &[u8]) -> io::Result<()> {
+        match self.continuation_align {
+            true => self.write_aligned(buf),
+            false => self.write_all(buf),
+        }
+    }
+
+    /// Write `line` as its own indented line, followed by a newline. If the
+    /// writer is currently in the middle of a line, a newline is written
+    /// first to end it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    ///
+    /// write!(writer, "Partial").unwrap();
+    /// writer.write_line("Line 1").unwrap();
+    /// writer.write_line("Line 2").unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), b"  Partial\n  Line 1\n  Line 2\n");
+    /// ```
+    pub fn write_line<S: AsRef<[u8]>>(&mut self, line: S) -> io::Result<()> {
+        if !self.at_line_start() {
+            self.write_all(b"\n")?;
+        }
+
+        self.write_all(line.as_ref())?;
+        self.write_all(b"\n")
+    }
+
+    /// Write `block`, a piece of text which may already be indented, as
+    /// complete lines at the current indent level. The current indent is
+    /// added before each non-empty line of the block, and any indentation
+    /// the block already has is kept after it, even if
+    /// [`set_reindent`][IndentWriter::set_reindent] is enabled. This differs
+    /// from [`write_raw`][IndentWriter::write_raw], which doesn't add an
+    /// indent at all.
+    ///
+    /// If the writer is in the middle of a line, a newline is written first
+    /// to end it, and if `block` doesn't end with a newline, one is added.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let snippet = "if x {\n    y();\n}";
+    ///
+    /// let mut writer = IndentWriter::new("    ", Vec::new());
+    /// writer.set_reindent(true);
+    ///
+    /// writeln!(writer, "  fn main() {{").unwrap();
+    /// writer.inc();
+    /// writer.write_block(snippet).unwrap();
+    /// writer.dec();
+    /// writeln!(writer, "}}").unwrap();
+    ///
+    /// assert_eq!(
+    ///     writer.get_ref(),
+    ///     b"    fn main() {\n        if x {\n            y();\n        }\n    }\n"
+    /// );
+    /// ```
+    pub fn write_block(&mut self, block: &str) -> io::Result<()> {
+        if !self.at_line_start() {
+            self.write_all(b"\n")?;
+        }
+
+        let reindent = mem::replace(&mut self.reindent, false);
+
+        let result = self.write_all(block.as_bytes()).and_then(|()| {
+            match block.is_empty() || block.ends_with('\n') {
+                true => Ok(()),
+                false => self.write_all(b"\n"),
+            }
+        });
+
+        self.reindent = reindent;
+        result
+    }
+
+    /// Write each of `items`, with `sep` written between each pair of items
+    /// (but not after the last one). Everything is written through this
+    /// writer, so if `sep` contains a newline, each item starts on a new,
+    /// indented line.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new_skip_initial("    ", Vec::new());
+    ///
+    /// write!(writer, "[\n").unwrap();
+    /// writer.write_joined(["1", "2", "3"], b",\n").unwrap();
+    /// writer.reset();
+    /// write!(writer, "\n]").unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), b"[\n    1,\n    2,\n    3\n]");
+    /// ```
+    pub fn write_joined<I, S>(&mut self, items: I, sep: &[u8]) -> io::Result<()>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<[u8]>,
+    {
+        for (i, item) in items.into_iter().enumerate() {
+            if i > 0 {
+                self.write_all(sep)?;
+            }
+
+            self.write_all(item.as_ref())?;
+        }
+
+        Ok(())
+    }
+
+    /// Write all of `buf` as with [`write_all`][io::Write::write_all], and
+    /// report how many bytes were written to the wrapped writer as a result.
+    /// Returns the number of bytes of `buf` consumed (which is always
+    /// `buf.len()`), and the total number of bytes emitted to the wrapped
+    /// writer, including any indents, however many writes that took.
+    ///
+    /// The two counts can differ in either direction: indents and translated
+    /// line endings add bytes, while held bytes (such as trailing whitespace
+    /// being trimmed) are consumed now but emitted by a later write. See
+    /// [`bytes_written`][IndentWriter::bytes_written].
+    ///
+    /// Returns an error of kind [`Other`][io::ErrorKind::Other] if the number
+    /// of bytes emitted doesn't fit in a `usize`, in which case `buf` has
+    /// still been written.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("    ", Vec::new());
+    ///
+    /// assert_eq!(writer.write_counted(b"Line 1\n").unwrap(), (7, 11));
+    /// assert_eq!(writer.write_counted(b"\n").unwrap(), (1, 1));
+    /// ```
+    pub fn write_counted(&mut self, buf: &[u8]) -> io::Result<(usize, usize)> {
+        let before = self.bytes_written();
+        self.write_all(buf)?;
+        let emitted = usize::try_from(self.bytes_written() - before)
+            .map_err(|_| io::Error::other("the number of bytes emitted doesn't fit in a usize"))?;
+
+        Ok((buf.len(), emitted))
+    }
+
+    /// Write `buf` directly to the wrapped writer, bypassing indentation
+    /// entirely. No indent is inserted anywhere in `buf`, and its line endings
+    /// and tabs aren't translated, even if that's been requested. Returns the
+    /// number of bytes of `buf` written.
+    ///
+    /// Anything already accepted but not yet written (such as a partially
+    /// written indent, or held whitespace) is written first. Afterwards, the
+    /// writer considers itself to be at the start of a line if `buf` ended
+    /// with a newline, and in the middle of a line otherwise.
+    ///
+    /// Because the contents of `buf` are otherwise ignored, other state can
+    /// get out of step with the actual output. For example, with
+    /// [`set_hanging_indent`][IndentWriter::set_hanging_indent], an empty
+    /// line in `buf` doesn't start a new paragraph, and a `"\r\n"` split
+    /// between `buf` and an ordinary write isn't recognised as a line ending.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("    ", Vec::new());
+    ///
+    /// writeln!(writer, "fn main() {{").unwrap();
+    /// writer.write_raw(b"#[rustfmt::skip]\n").unwrap();
+    /// writeln!(writer, "let x = 1;").unwrap();
+    ///
+    /// assert_eq!(
+    ///     writer.get_ref(),
+    ///     b"    fn main() {\n#[rustfmt::skip]\n    let x = 1;\n"
+    /// );
+    /// ```
+    pub fn write_raw(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_unwritten()?;
+
+        if !self.write_pending_whitespace()? {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+
+        let written = self.writer.write_verbatim(buf)?;
+
+        if let Some(&last) = buf[..written].last() {
+            self.unchecked_cr = false;
+            self.blank_lines = 0;
+            self.state = match last {
+                b'\n' => NeedIndent,
+                _ => MidLine,
+            };
+        }
+
+        Ok(written)
+    }
+
+    /// Write the indent for the current line now, if it hasn't been written
+    /// yet. Normally, the indent is only written once some content for the
+    /// line arrives, so that empty lines aren't indented; this writes it
+    /// even if no content follows. Afterwards, the writer is in the middle
+    /// of the line. The wrapped writer is not flushed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("    ", Vec::new());
+    /// write!(writer, "Line 1\n").unwrap();
+    /// assert_eq!(writer.get_ref(), b"    Line 1\n");
+    ///
+    /// writer.flush_pending_indent().unwrap();
+    /// assert_eq!(writer.get_ref(), b"    Line 1\n    ");
+    /// ```
+    pub fn flush_pending_indent(&mut self) -> io::Result<()> {
+        if let NeedIndent = self.state {
+            // The line filter has to decide now, based on the line so far
+            let filtered = self.buffers_lines() || !self.filter_buffer.is_empty();
+
+            if filtered && self.filter_result.is_none() {
+                self.filter_line();
+            }
+
+            if let NeedIndent = self.state {
+                self.start_line(b"");
+            }
+        }
+
+        self.write_remaining_indent()?;
+
+        match self.filter_result {
+            Some(_) => self.write_filtered(),
+            None => Ok(()),
+        }
+    }
+
+    /// Increase the indent level by one, returning a guard which restores the
+    /// original level when it's dropped. The guard dereferences to this
+    /// [`IndentWriter`], so it can be written to directly.
+    ///
+    /// The guard restores the level that was in effect when it was created,
+    /// so any manual changes to the level made through the guard are undone
+    /// rather than compounded.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::with_indent_level("  ", 0, Vec::new());
+    ///
+    /// writeln!(writer, "<root>").unwrap();
+    /// {
+    ///     let mut writer = writer.indented();
+    ///     writeln!(writer, "<child>").unwrap();
+    ///     writeln!(writer.indented(), "text").unwrap();
+    ///     writeln!(writer, "</child>").unwrap();
+    /// }
+    /// writeln!(writer, "</root>").unwrap();
+    ///
+    /// assert_eq!(
+    ///     writer.get_ref(),
+    ///     b"<root>\n  <child>\n    text\n  </child>\n</root>\n"
+    /// );
+    /// ```
+    pub fn indented(&mut self) -> IndentGuard<'_, 'i, W> {
+        let snapshot = self.save();
+        self.inc();
+
+        IndentGuard {
+            writer: self,
+            snapshot,
+        }
+    }
+
+    /// Set the indent level to `level`, returning a guard which restores the
+    /// original level when it's dropped. This is like
+    /// [`indented`][IndentWriter::indented], but sets an absolute level rather
+    /// than increasing it, which is useful when some content needs a
+    /// particular depth regardless of the current one.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    /// writer.inc_by(2);
+    ///
+    /// writeln!(writer, "Nested").unwrap();
+    /// writeln!(writer.at_level(0), "Top").unwrap();
+    /// writeln!(writer, "Nested").unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), b"      Nested\nTop\n      Nested\n");
+    /// ```
+    pub fn at_level(&mut self, level: u16) -> IndentGuard<'_, 'i, W> {
+        let snapshot = self.save();
+        self.set_indent_level(level);
+
+        IndentGuard {
+            writer: self,
+            snapshot,
+        }
+    }
+
+    /// Take a snapshot of the current indent level, which can later be
+    /// reapplied with [`restore`][IndentWriter::restore]. The snapshot only
+    /// includes the indent level; it doesn't include any other configuration
+    /// or whether the writer is in the middle of a line.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// use indent_write::io::IndentWriter;
+    ///
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    /// let snapshot = writer.save();
+    ///
+    /// writer.inc_by(2);
+    /// writeln!(writer, "Nested").unwrap();
+    ///
+    /// writer.restore(snapshot);
+    /// writeln!(writer, "Top").unwrap();
+    ///
+    /// assert_eq!(writer.get_ref(), b"      Nested\n  Top\n");
+    /// ```
+    #[inline]
+    pub fn save(&self) -> IndentSnapshot {
+        IndentSnapshot {
+            indent_level: self.indent_level,
+        }
+    }
+
+    /// Restore the indent level from a snapshot taken with
+    /// [`save`][IndentWriter::save]. Like
+    /// [`set_indent_level`][IndentWriter::set_indent_level], this takes effect
+    /// at the start of the next line.
+    #[inline]
+    pub fn restore(&mut self, snapshot: IndentSnapshot) {
+        self.set_indent_level(snapshot.indent_level);
+    }
+
+    // Recompute `required_indent` from `indent` and `indent_level` (or from
+    // the first line prefix, if there is one). If we're in the middle of
+    // writing an indent, the rebuild is deferred until it's finished, so that
+    // the current line gets the indent it started with.
+    fn rebuild_indent(&mut self) {
+        if let WritingIndent(_) = self.state {
+            self.rebuild_pending = true;
+        } else {
+            self.required_indent.clear();
+            self.push_gutter();
+
+            match (self.first_line_prefix, &self.raw_indent, self.guide) {
+                (Some(prefix), _, _) => self.required_indent.extend_from_slice(prefix.as_bytes()),
+                (None, _, _) if self.continuation.is_some() => self.push_continuation(),
+                (None, Some(raw_indent), _) => self.required_indent.extend_from_slice(raw_indent),
+                (None, None, _) if self.tab_stops.is_some() => self.push_tab_stops(),
+                (None, None, None) => match self.levels {
+                    Some(levels) => self.required_indent.extend(
+                        levels
+                            .iter()
+                            .cycle()
+                            .take(self.indent_level.into())
+                            .flat_map(|indent| indent.as_bytes()),
+                    ),
+                    None => self.required_indent.extend(
+                        iter::repeat(self.indent.as_bytes())
+                            .take(self.indent_level.into())
+                            .flatten(),
+                    ),
+                },
+                (None, None, Some(guide)) => {
+                    if let Some(guides) = self.indent_level.checked_sub(1) {
+                        self.required_indent
+                            .extend(iter::repeat(guide.as_bytes()).take(guides.into()).flatten());
+
+                        match self.levels {
+                            Some([]) => {}
+                            Some(levels) => self.required_indent.extend_from_slice(
+                                levels[usize::from(guides) % levels.len()].as_bytes(),
+                            ),
+                            None => self
+                                .required_indent
+                                .extend_from_slice(self.indent.as_bytes()),
+                        }
+                    }
+                }
+            }
+
+            if let Some(ref prefix) = self.comment_prefix {
+                self.required_indent.extend_from_slice(prefix.as_bytes());
+            }
+
+            self.rebuild_pending = false;
+        }
+    }
+
+    // Add spaces to `required_indent` up to the column at which the current
+    // `write_aligned` began, following whatever's already there.
+    fn push_continuation(&mut self) {
+        let Some(column) = self.continuation else {
+            return;
+        };
+
+        let gutter = display_width(&String::from_utf8_lossy(&self.required_indent));
+
+        self.required_indent
+            .extend(iter::repeat(b' ').take(column.saturating_sub(gutter)));
+    }
+
+    // Add spaces to `required_indent` to reach the next tab stop for each
+    // level (see `set_align_to_tab_stops`), following whatever's already
+    // there.
+    fn push_tab_stops(&mut self) {
+        let Some(width) = self.tab_stops else { return };
+
+        let mut column = display_width(&String::from_utf8_lossy(&self.required_indent));
+
+        for level in 1..=self.indent_level {
+            match self.guide {
+                Some(guide) if level < self.indent_level => {
+                    self.required_indent.extend_from_slice(guide.as_bytes());
+                    column += display_width(guide);
+                }
+                _ => {
+                    let spaces = width - column % width;
+                    self.required_indent.extend(iter::repeat(b' ').take(spaces));
+                    column += spaces;
+                }
+            }
+        }
+    }
+
+    // Add the line number gutter (if any) for the next line to
+    // `required_indent`.
+    fn push_gutter(&mut self) {
+        if let Some(config) = self.line_numbers {
+            let line = self
+                .writer
+                .lines_written
+                .saturating_sub(self.first_numbered_line);
+
+            write!(
+                self.required_indent,
+                "{:>width$}{}",
+                config.start.saturating_add(line),
+                config.separator,
+                width = config.min_width
+            )
+            .expect("writing to a Vec can't fail");
+        }
+    }
+
+    // True if "\r\n" should be treated as a newline.
+    #[inline]
+    fn crlf(&self) -> bool {
+        self.crlf_aware || self.writer.line_ending.is_some()
+    }
+
+    // Find the start of the next line in `buf` that needs an indent.
+    fn next_line_start(&self, buf: &[u8]) -> Option<usize> {
+        if self.indents_empty_lines() {
+            return if buf.is_empty() { None } else { Some(0) };
+        }
+
+        if !self.crlf() {
+            return buf.iter().position(|&b| b != b'\n');
+        }
+
+        let mut i = 0;
+
+        loop {
+            match &buf[i..] {
+                [] => break None,
+                [b'\n', ..] => i += 1,
+                [b'\r', b'\n', ..] => i += 2,
+                _ => break Some(i),
+            }
+        }
+    }
+
+    // True if whitespace is currently being held back from the end of lines.
+    #[inline]
+    fn trimming(&self) -> bool {
+        self.trim_trailing_whitespace || !self.pending_whitespace.is_empty()
+    }
+
+    // True if whitespace at the start of a line should be held back until
+    // we know if the line is empty.
+    #[inline]
+    fn holding_blank_whitespace(&self) -> bool {
+        self.trimming() || (self.whitespace_only_blank && !self.indents_empty_lines())
+    }
+
+    // True if empty lines are indented, either because of
+    // `indent_empty_lines` or because they're commented.
+    #[inline]
+    fn indents_empty_lines(&self) -> bool {
+        self.indent_empty_lines || (self.comment_blank_lines && self.comment_prefix.is_some())
+    }
+
+    #[inline]
+    fn is_whitespace(&self, b: u8) -> bool {
+        b == b' ' || b == b'\t' || (self.crlf() && b == b'\r')
+    }
+
+    // Hold the run of whitespace at the start of `buf`, returning its length.
+    fn hold_whitespace(&mut self, buf: &[u8]) -> usize {
+        let len = buf
+            .iter()
+            .position(|&b| !self.is_whitespace(b))
+            .unwrap_or(buf.len());
+
+        self.pending_whitespace.extend_from_slice(&buf[..len]);
+        len
+    }
+
+    // The held whitespace turned out to be at the end of a line, so discard
+    // it, except for a '\r' which is part of a "\r\n".
+    fn discard_whitespace(&mut self) {
+        let keep_cr = self.crlf() && self.pending_whitespace.last() == Some(&b'\r');
+        self.pending_whitespace.clear();
+
+        if keep_cr {
+            self.pending_whitespace.push(b'\r');
+        }
+    }
+
+    // Write out all of the held whitespace. Returns false if the wrapped
+    // writer reached EoF.
+    fn write_pending_whitespace(&mut self) -> io::Result<bool> {
+        while !self.pending_whitespace.is_empty() {
+            match self.writer.write(&self.pending_whitespace)? {
+                0 => return Ok(false),
+                n => drop(self.pending_whitespace.drain(..n)),
+            }
+        }
+
+        Ok(true)
+    }
+
+    // The length of the existing indentation at the start of `line` which
+    // should be discarded; this is always 0 unless we're re-indenting.
+    fn old_indent_len(&self, line: &[u8]) -> usize {
+        match self.reindent {
+            false => 0,
+            true => line
+                .iter()
+                .position(|&b| b != b' ' && b != b'\t')
+                .unwrap_or(line.len()),
+        }
+    }
+
+    // We're at the start of `line`, which needs an indent. In hanging indent
+    // mode, the indent is skipped if this is the first line of a paragraph.
+    fn start_line(&mut self, line: &[u8]) {
+        // An empty line which is only being indented because of
+        // `indent_empty_lines` (or `comment_blank_lines`) still separates
+        // paragraphs. Its comment prefix isn't followed by any content, so
+        // any trailing whitespace in the prefix is left off.
+        if self.indents_empty_lines() && matches!(line, [b'\n', ..] | [b'\r', b'\n', ..]) {
+            self.paragraph_start = true;
+            self.blank_lines = self.blank_lines.saturating_add(1);
+            self.begin_indent();
+
+            if let Some(ref prefix) = self.comment_prefix {
+                let trailing = prefix.len() - prefix.trim_end().len();
+                let len = self.required_indent.len().saturating_sub(trailing);
+                self.required_indent.truncate(len);
+                self.rebuild_pending = true;
+            }
+
+            return;
+        }
+
+        self.blank_lines = 0;
+
+        match (
+            self.hanging_indent && self.paragraph_start,
+            self.line_numbers,
+        ) {
+            (true, None) => self.state = MidLine,
+
+            // The line still needs its line number, but not the indent
+            (true, Some(_)) => {
+                self.required_indent.clear();
+                self.push_gutter();
+                self.state = WritingIndent(0);
+                self.rebuild_pending = true;
+            }
+
+            (false, _) => self.begin_indent(),
+        }
+
+        self.paragraph_start = false;
+    }
+
+    // Start writing the indent. If it's the first line prefix, the prefix is
+    // used up, so the normal indent is restored once it's been written.
+    #[inline]
+    fn begin_indent(&mut self) {
+        // The line number has changed since the last line.
+        if self.line_numbers.is_some() {
+            self.rebuild_indent();
+        }
+
+        self.state = WritingIndent(0);
+
+        if self.align_padding > 0 {
+            let padding = mem::take(&mut self.align_padding);
+            self.required_indent
+                .extend(iter::repeat(b' ').take(padding));
+            self.rebuild_pending = true;
+        }
+
+        if let Some(prefix) = self.first_line_prefix.take() {
+            if self.align_continuation {
+                self.raw_indent = Some(vec![b' '; display_width(prefix)]);
+            }
+
+            self.rebuild_pending = true;
+        }
+    }
+
+    // Empty lines (`written`, while we need an indent) have been written; the
+    // next non-empty line begins a paragraph.
+    #[inline]
+    fn note_empty_lines(&mut self, written: &[u8]) {
+        if written.contains(&b'\n') {
+            self.paragraph_start = true;
+        }
+    }
+
+    // If we're in the middle of writing an indent, write the rest of it.
+    fn write_remaining_indent(&mut self) -> io::Result<()> {
+        while let WritingIndent(written) = self.state {
+            let indent = &self.required_indent[written..];
+
+            match self.writer.write_verbatim(indent)? {
+                // We wrote the entire indent
+                len if len >= indent.len() => self.finish_indent(),
+
+                // EoF; return an error
+                0 => return Err(io::ErrorKind::WriteZero.into()),
+
+                // Partial write, continue writing.
+                len => self.state = WritingIndent(written + len),
+            }
+        }
+
+        Ok(())
+    }
+
+    // The entire indent has been written; resume forwarding the line.
+    #[inline]
+    fn finish_indent(&mut self) {
+        self.state = MidLine;
+
+        if self.rebuild_pending {
+            self.rebuild_indent();
+        }
+    }
+
+    // Call the line filter with the buffered line, and prepare to write it
+    // out with or without an indent.
+    fn filter_line(&mut self) {
+        let line = match self.filter_buffer.strip_suffix(b"\r") {
+            Some(line) if self.crlf() => line,
+            _ => &self.filter_buffer,
+        };
+
+        let indent = match self.line_filter.0 {
+            Some(ref mut filter) => filter(line),
+            None => true,
+        };
+
+        if !indent {
+            self.state = MidLine;
+            self.paragraph_start = false;
+            self.blank_lines = 0;
+        } else if let Some(width) = self.right_align {
+            let line_width = display_width(&String::from_utf8_lossy(line));
+            self.align_padding = width.saturating_sub(line_width);
+        }
+
+        self.filter_result = Some(indent);
+    }
+
+    // True if the start of each line is buffered until it's complete.
+    #[inline]
+    fn buffers_lines(&self) -> bool {
+        self.line_filter.0.is_some() || self.right_align.is_some()
+    }
+
+    // Write out the buffered line, once the filter has been called.
+    fn write_filtered(&mut self) -> io::Result<()> {
+        while !self.filter_buffer.is_empty() {
+            let buffer = mem::take(&mut self.filter_buffer);
+            let result = self.write_checked(&buffer);
+            self.filter_buffer = buffer;
+
+            match result? {
+                0 => return Err(io::ErrorKind::WriteZero.into()),
+                n => drop(self.filter_buffer.drain(..n)),
+            }
+        }
+
+        self.filter_result = None;
+        Ok(())
+    }
+
+    // Write `buf` with a line filter. At the start of a non-empty line, the
+    // line is buffered until it's complete, then filtered.
+    fn write_with_filter(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.filter_result.is_some() {
+            self.write_filtered()?;
+        }
+
+        if !matches!(self.state, NeedIndent) {
+            return self.write_checked(buf);
+        }
+
+        let (line, complete) = match buf.iter().position(|&b| b == b'\n') {
+            Some(len) => (&buf[..len], true),
+            None => (buf, false),
+        };
+
+        // Empty lines aren't filtered. Write just the one line, since the
+        // next one needs to be filtered.
+        if complete && self.filter_buffer.is_empty() && self.is_empty_line(line) {
+            return self.write_checked(&buf[..=line.len()]);
+        }
+
+        self.filter_buffer.extend_from_slice(line);
+
+        if complete {
+            self.filter_line();
+
+            // Once any of the line has been accepted, an error writing it out
+            // is reported by the next write instead.
+            match self.write_filtered() {
+                Ok(()) if line.is_empty() => return self.write_checked(&buf[..1]),
+                Err(err) if line.is_empty() => return Err(err),
+                _ => {}
+            }
+        }
+
+        Ok(line.len())
+    }
+
+    // The length of the empty line at the start of `buf` (including its
+    // newline), or 0 if it doesn't start with an empty line.
+    #[inline]
+    fn empty_line_len(&self, buf: &[u8]) -> usize {
+        match buf {
+            [b'\n', ..] => 1,
+            [b'\r', b'\n', ..] if self.crlf() => 2,
+            _ => 0,
+        }
+    }
+
+    // True if the current run of empty lines is as long as it's allowed to
+    // be, so that any more are discarded.
+    #[inline]
+    fn blank_lines_full(&self) -> bool {
+        self.max_blank_lines
+            .is_some_and(|max| self.blank_lines >= max)
+    }
+
+    // True if `line` (without its '\n') is empty, including "\r" from a
+    // "\r\n" line if those are recognised.
+    #[inline]
+    fn is_empty_line(&self, line: &[u8]) -> bool {
+        line.is_empty() || (self.crlf() && line == b"\r")
+    }
+
+    // Write `buf`, once any held start of the output has been written.
+    fn write_unheld(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let result = match self.buffers_lines() || !self.filter_buffer.is_empty() {
+            true => self.write_with_filter(buf),
+            false => self.write_checked(buf),
+        };
+
+        result.map_err(|err| self.annotate_error(err))
+    }
+
+    // Check if the output starts with a shebang or BOM, given that it
+    // continues with `buf`. If it can't be known yet, `buf` is held, and the
+    // number of bytes held is returned. Otherwise, if it does, the first line
+    // is left unindented.
+    fn check_shebang(&mut self, buf: &[u8]) -> Option<usize> {
+        const MARKERS: [&[u8]; 2] = [b"#!", b"\xEF\xBB\xBF"];
+
+        let len = buf.len().min(3 - self.shebang_held.len());
+        let start = [&self.shebang_held[..], &buf[..len]].concat();
+
+        if MARKERS.iter().any(|marker| start.starts_with(marker)) {
+            self.shebang_check = false;
+            self.state = MidLine;
+            None
+        } else if MARKERS.iter().any(|marker| marker.starts_with(&start)) {
+            self.shebang_held.extend_from_slice(buf);
+            Some(buf.len())
+        } else {
+            self.shebang_check = false;
+            None
+        }
+    }
+
+    // Write out the held start of the output.
+    fn write_shebang_held(&mut self) -> io::Result<()> {
+        while !self.shebang_held.is_empty() {
+            let held = mem::take(&mut self.shebang_held);
+            let result = self.write_unheld(&held);
+            self.shebang_held = held;
+
+            match result? {
+                0 => return Err(io::ErrorKind::WriteZero.into()),
+                n => drop(self.shebang_held.drain(..n)),
+            }
+        }
+
+        Ok(())
+    }
+
+    // Write `buf`, wrapping lines at `width`. Each word is held until it's
+    // complete, then written on the current line if it fits, or else on the
+    // next.
+    fn write_wrapped(&mut self, buf: &[u8], width: usize) -> io::Result<usize> {
+        for (i, &b) in buf.iter().enumerate() {
+            let result = match b {
+                b'\n' => self
+                    .write_held_word(width)
+                    .and_then(|()| self.write_all_unheld(b"\n")),
+
+                b' ' | b'\t' | b'\r' => match self.wrap_held.last() {
+                    Some(&last) if !is_wrap_space(last) => self.write_held_word(width),
+                    _ => Ok(()),
+                },
+
+                _ => Ok(()),
+            };
+
+            match result {
+                Ok(()) if b == b'\n' => {}
+                Ok(()) => self.wrap_held.push(b),
+                Err(_) if i > 0 => return Ok(i),
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(buf.len())
+    }
+
+    // Write out the held word, and the whitespace before it, first starting
+    // a new line if it doesn't fit on the current one.
+    fn write_held_word(&mut self, width: usize) -> io::Result<()> {
+        while !self.wrap_held.is_empty() {
+            // The column the word would start at, and how many columns
+            // it needs
+            let at_line_start = !matches!(self.state, MidLine);
+            let column = match at_line_start {
+                true => display_width(&String::from_utf8_lossy(&self.required_indent)),
+                false => self.writer.align_column() + self.pending_whitespace.len(),
+            };
+            let held_width = display_width(&String::from_utf8_lossy(&self.wrap_held));
+            let space = self
+                .wrap_held
+                .iter()
+                .position(|&b| !is_wrap_space(b))
+                .unwrap_or(self.wrap_held.len());
+
+            if column + held_width <= width || space == self.wrap_held.len() {
+                return self.write_wrap_held(self.wrap_held.len());
+            }
+
+            // Move the word to the next line
+            if !at_line_start {
+                self.wrap_held.drain(..space);
+                self.write_all_unheld(b"\n")?;
+                continue;
+            }
+
+            // The word doesn't fit on a line of its own, so write as many
+            // characters as will fit (but at least one), then start a new
+            // line for the rest.
+            let mut len = 0;
+            let mut used = column;
+
+            for (start, end) in char_bounds(&self.wrap_held) {
+                let char_width =
+                    std::str::from_utf8(&self.wrap_held[start..end]).map_or(1, display_width);
+
+                if len > 0 && used + char_width > width {
+                    break;
+                }
+
+                used += char_width;
+                len = end;
+            }
+
+            self.write_wrap_held(len.max(1))?;
+
+            if !self.wrap_held.is_empty() {
+                self.write_all_unheld(b"\n")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Write out the first `len` bytes of the held word.
+    fn write_wrap_held(&mut self, mut len: usize) -> io::Result<()> {
+        while len > 0 {
+            let held = mem::take(&mut self.wrap_held);
+            let result = self.write_unheld(&held[..len]);
+            self.wrap_held = held;
+
+            match result? {
+                0 => return Err(io::ErrorKind::WriteZero.into()),
+                n => {
+                    self.wrap_held.drain(..n);
+                    len -= n;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Write all of `buf`, bypassing wrapping.
+    fn write_all_unheld(&mut self, mut buf: &[u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.write_unheld(buf)? {
+                0 => return Err(io::ErrorKind::WriteZero.into()),
+                n => buf = &buf[n..],
+            }
+        }
+
+        Ok(())
+    }
+
+    // Write out everything that's been accepted but not yet written to the
+    // wrapped writer.
+    fn write_unwritten(&mut self) -> io::Result<()> {
+        // If the start of the output is held, it can't be a shebang or BOM
+        // after all, or it would have been recognised by now.
+        self.shebang_check = false;
+        self.write_shebang_held()?;
+
+        // Write the held word, even though it may not be complete.
+        if let Some(width) = self.wrap_width {
+            self.write_held_word(width)?;
+        }
+
+        // If part of a line is buffered for the line filter, filter it now
+        if !self.filter_buffer.is_empty() {
+            if self.filter_result.is_none() {
+                self.filter_line();
+            }
+
+            self.write_filtered()?;
+        }
+
+        // If we're currently in the middle of writing an indent, flush it
+        self.write_remaining_indent()?;
+
+        // If we're holding a '\r', there's no way to know yet what follows
+        // it, so treat it as the end of a line.
+        while self.held_cr {
+            match self.writer.write(b"\r")? {
+                0 => return Err(io::ErrorKind::WriteZero.into()),
+                _ => self.held_cr = false,
+            }
+        }
+
+        self.writer.write_pending()
+    }
+}
+
+/// Builder for an [`IndentWriter`] with non-default options.
+///
+/// Each method corresponds to one of the setters on [`IndentWriter`]; see
+/// those for details of each option.
+///
+/// # Example
+///
+/// ```
+/// # use std::io::Write;
+/// use indent_write::io::{IndentWriterBuilder, LineEnding};
+///
+/// let mut writer = IndentWriterBuilder::new("  ")
+///     .indent_level(2)
+///     .trim_trailing_whitespace(true)
+///     .line_ending(Some(LineEnding::CrLf))
+///     .build(Vec::new());
+///
+/// write!(writer, "Line 1  \n\nLine 2\n").unwrap();
+///
+/// assert_eq!(writer.get_ref(), b"    Line 1\r\n\r\n    Line 2\r\n");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[must_use = "Builders do nothing unless built"]
+pub struct IndentWriterBuilder<'i> {
+    indent: &'i str,
+    indent_level: u16,
+    max_level: Option<u16>,
+    base_level: u16,
+    skip_initial: bool,
+    guide: Option<&'i str>,
+    first_line_prefix: Option<&'i str>,
+    align_continuation: bool,
+    continuation_align: bool,
+    hanging_indent: bool,
+    preserve_shebang: bool,
+    indent_empty_lines: bool,
+    comment_prefix: Option<&'i str>,
+    comment_blank_lines: bool,
+    collapse_blank_lines: Option<usize>,
+    trim_trailing_whitespace: bool,
+    treat_whitespace_only_as_blank: bool,
+    reindent: bool,
+    reject_lone_cr: bool,
+    annotate_errors: bool,
+    crlf_aware: bool,
+    line_ending: Option<LineEnding>,
+    expand_tabs: Option<usize>,
+    wrap_at: Option<usize>,
+    right_align: Option<usize>,
+    flush_on_newline: bool,
+    ansi_aware: bool,
+    align_to_tab_stops: Option<usize>,
+    line_numbers: Option<LineNumberConfig<'i>>,
+}
+
+impl<'i> IndentWriterBuilder<'i> {
+    /// Create a new builder using the given indent, with all other options
+    /// set to their defaults.
+    #[inline]
+    pub fn new(indent: &'i str) -> Self {
+        Self {
+            indent,
+            indent_level: 1,
+            max_level: None,
+            base_level: 0,
+            skip_initial: false,
+            guide: None,
+            first_line_prefix: None,
+            align_continuation: false,
+            continuation_align: false,
+            hanging_indent: false,
+            preserve_shebang: false,
+            indent_empty_lines: false,
+            comment_prefix: None,
+            comment_blank_lines: false,
+            collapse_blank_lines: None,
+            trim_trailing_whitespace: false,
+            treat_whitespace_only_as_blank: false,
+            reindent: false,
+            reject_lone_cr: false,
+            annotate_errors: false,
+            crlf_aware: false,
+            line_ending: None,
+            expand_tabs: None,
+            wrap_at: None,
+            right_align: None,
+            flush_on_newline: false,
+            ansi_aware: false,
+            align_to_tab_stops: None,
+            line_numbers: None,
+        }
+    }
+
+    /// Set the initial indent level. See [`IndentWriter::with_indent_level`].
+    #[inline]
+    pub fn indent_level(self, indent_level: u16) -> Self {
+        Self {
+            indent_level,
+            ..self
+        }
+    }
+
+    /// See [`IndentWriter::set_max_level`].
+    #[inline]
+    pub fn max_level(self, max_level: Option<u16>) -> Self {
+        Self { max_level, ..self }
+    }
+
+    /// See [`IndentWriter::set_base_level`].
+    #[inline]
+    pub fn base_level(self, base_level: u16) -> Self {
+        Self { base_level, ..self }
+    }
+
+    /// Omit the indent on the first line. See
+    /// [`IndentWriter::new_skip_initial`].
+    #[inline]
+    pub fn skip_initial(self, skip_initial: bool) -> Self {
+        Self {
+            skip_initial,
+            ..self
+        }
+    }
+
+    /// See [`IndentWriter::set_guide`].
+    #[inline]
+    pub fn guide(self, guide: Option<&'i str>) -> Self {
+        Self { guide, ..self }
+    }
+
+    /// See [`IndentWriter::set_first_line_prefix`].
+    #[inline]
+    pub fn first_line_prefix(self, first_line_prefix: Option<&'i str>) -> Self {
+        Self {
+            first_line_prefix,
+            ..self
+        }
+    }
+
+    /// See [`IndentWriter::set_align_continuation`].
+    #[inline]
+    pub fn align_continuation(self, align_continuation: bool) -> Self {
+        Self {
+            align_continuation,
+            ..self
+        }
+    }
+
+    /// See [`IndentWriter::set_continuation_align`].
+    #[inline]
+    pub fn continuation_align(self, continuation_align: bool) -> Self {
+        Self {
+            continuation_align,
+            ..self
+        }
+    }
+
+    /// See [`IndentWriter::set_hanging_indent`].
+    #[inline]
+    pub fn hanging_indent(self, hanging_indent: bool) -> Self {
+        Self {
+            hanging_indent,
+            ..self
+        }
+    }
+
+    /// See [`IndentWriter::set_preserve_shebang`].
+    #[inline]
+    pub fn preserve_shebang(self, preserve_shebang: bool) -> Self {
+        Self {
+            preserve_shebang,
+            ..self
+        }
+    }
+
+    /// See [`IndentWriter::set_indent_empty_lines`].
+    #[inline]
+    pub fn indent_empty_lines(self, indent_empty_lines: bool) -> Self {
+        Self {
+            indent_empty_lines,
+            ..self
+        }
+    }
+
+    /// See [`IndentWriter::set_comment_prefix`].
+    #[inline]
+    pub fn comment_prefix(self, comment_prefix: Option<&'i str>) -> Self {
+        Self {
+            comment_prefix,
+            ..self
+        }
+    }
+
+    /// See [`IndentWriter::set_comment_blank_lines`].
+    #[inline]
+    pub fn comment_blank_lines(self, comment_blank_lines: bool) -> Self {
+        Self {
+            comment_blank_lines,
+            ..self
+        }
+    }
+
+    /// See [`IndentWriter::set_collapse_blank_lines`].
+    #[inline]
+    pub fn collapse_blank_lines(self, collapse_blank_lines: Option<usize>) -> Self {
+        Self {
+            collapse_blank_lines,
+            ..self
+        }
+    }
+
+    /// See [`IndentWriter::set_trim_trailing_whitespace`].
+    #[inline]
+    pub fn trim_trailing_whitespace(self, trim_trailing_whitespace: bool) -> Self {
+        Self {
+            trim_trailing_whitespace,
+            ..self
+        }
+    }
+
+    /// See [`IndentWriter::set_treat_whitespace_only_as_blank`].
+    #[inline]
+    pub fn treat_whitespace_only_as_blank(self, treat_whitespace_only_as_blank: bool) -> Self {
+        Self {
+            treat_whitespace_only_as_blank,
+            ..self
+        }
+    }
+
+    /// See [`IndentWriter::set_reindent`].
+    #[inline]
+    pub fn reindent(self, reindent: bool) -> Self {
+        Self { reindent, ..self }
+    }
+
+    /// See [`IndentWriter::set_reject_lone_cr`].
+    #[inline]
+    pub fn reject_lone_cr(self, reject_lone_cr: bool) -> Self {
+        Self {
+            reject_lone_cr,
+            ..self
+        }
+    }
+
+    /// See [`IndentWriter::set_annotate_errors`].
+    #[inline]
+    pub fn annotate_errors(self, annotate_errors: bool) -> Self {
+        Self {
+            annotate_errors,
+            ..self
+        }
+    }
+
+    /// See [`IndentWriter::set_crlf_aware`].
+    #[inline]
+    pub fn crlf_aware(self, crlf_aware: bool) -> Self {
+        Self { crlf_aware, ..self }
+    }
+
+    /// See [`IndentWriter::set_line_ending`].
+    #[inline]
+    pub fn line_ending(self, line_ending: Option<LineEnding>) -> Self {
+        Self {
+            line_ending,
+            ..self
+        }
+    }
+
+    /// See [`IndentWriter::set_line_numbers`].
+    #[inline]
+    pub fn line_numbers(self, line_numbers: Option<LineNumberConfig<'i>>) -> Self {
+        Self {
+            line_numbers,
+            ..self
+        }
+    }
+
+    /// See [`IndentWriter::set_expand_tabs`].
+    #[inline]
+    pub fn expand_tabs(self, expand_tabs: Option<usize>) -> Self {
+        Self {
+            expand_tabs,
+            ..self
+        }
+    }
+
+    /// See [`IndentWriter::set_wrap_at`].
+    #[inline]
+    pub fn wrap_at(self, wrap_at: Option<usize>) -> Self {
+        Self { wrap_at, ..self }
+    }
+
+    /// See [`IndentWriter::set_right_align`].
+    #[inline]
+    pub fn right_align(self, right_align: Option<usize>) -> Self {
+        Self {
+            right_align,
+            ..self
+        }
+    }
+
+    /// See [`IndentWriter::set_flush_on_newline`].
+    #[inline]
+    pub fn flush_on_newline(self, flush_on_newline: bool) -> Self {
+        Self {
+            flush_on_newline,
+            ..self
+        }
+    }
+
+    /// See [`IndentWriter::set_ansi_aware`].
+    #[inline]
+    pub fn ansi_aware(self, ansi_aware: bool) -> Self {
+        Self { ansi_aware, ..self }
+    }
+
+    /// See [`IndentWriter::set_align_to_tab_stops`].
+    #[inline]
+    pub fn align_to_tab_stops(self, align_to_tab_stops: Option<usize>) -> Self {
+        Self {
+            align_to_tab_stops,
+            ..self
+        }
+    }
+
+    /// Create an [`IndentWriter`] wrapping `writer`, with the options
+    /// configured in this builder.
+    pub fn build<W: io::Write>(self, writer: W) -> IndentWriter<'i, W> {
+        let state = match self.skip_initial {
+            true => MidLine,
+            false => NeedIndent,
+        };
+
+        let mut writer =
+            IndentWriter::with_state(Indent::Str(self.indent), self.indent_level, writer, state);
+
+        writer.set_max_level(self.max_level);
+        writer.set_base_level(self.base_level);
+        writer.set_guide(self.guide);
+        writer.set_first_line_prefix(self.first_line_prefix);
+        writer.set_align_continuation(self.align_continuation);
+        writer.set_continuation_align(self.continuation_align);
+        writer.set_hanging_indent(self.hanging_indent);
+        writer.set_preserve_shebang(self.preserve_shebang);
+        writer.set_indent_empty_lines(self.indent_empty_lines);
+        writer.set_comment_prefix(self.comment_prefix.map(str::to_owned));
+        writer.set_comment_blank_lines(self.comment_blank_lines);
+        writer.set_collapse_blank_lines(self.collapse_blank_lines);
+        writer.set_trim_trailing_whitespace(self.trim_trailing_whitespace);
+        writer.set_treat_whitespace_only_as_blank(self.treat_whitespace_only_as_blank);
+        writer.set_reindent(self.reindent);
+        writer.set_reject_lone_cr(self.reject_lone_cr);
+        writer.set_annotate_errors(self.annotate_errors);
+        writer.set_crlf_aware(self.crlf_aware);
+        writer.set_line_ending(self.line_ending);
+        writer.set_expand_tabs(self.expand_tabs);
+        writer.set_wrap_at(self.wrap_at);
+        writer.set_right_align(self.right_align);
+        writer.set_flush_on_newline(self.flush_on_newline);
+        writer.set_ansi_aware(self.ansi_aware);
+        writer.set_align_to_tab_stops(self.align_to_tab_stops);
+        writer.set_line_numbers(self.line_numbers);
+
+        writer
+    }
+}
+
+/// Copy everything from `src` into `dst`, indenting it, until `src` reaches
+/// EoF. Returns the number of bytes copied from `src`.
+///
+/// This is useful for indenting a stream, such as the standard input. The
+/// reader's buffer is written directly, without any additional copying or
+/// splitting into lines; `dst` isn't flushed.
+///
+/// # Example
+///
+/// ```
+/// use indent_write::io::{indent_reader, IndentWriter};
+///
+/// let input: &[u8] = b"Line 1\nLine 2\n\nLine 3\n";
+/// let mut writer = IndentWriter::new("  ", Vec::new());
+///
+/// assert_eq!(indent_reader(input, &mut writer).unwrap(), 22);
+/// assert_eq!(writer.get_ref(), b"  Line 1\n  Line 2\n\n  Line 3\n");
+/// ```
+pub fn indent_reader<R: io::BufRead, W: io::Write>(
+    mut src: R,
+    dst: &mut IndentWriter<'_, W>,
+) -> io::Result<u64> {
+    let mut copied = 0;
+
+    loop {
+        let buf = match src.fill_buf() {
+            Ok([]) => break Ok(copied),
+            Ok(buf) => buf,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => break Err(err),
+        };
+
+        let len = buf.len();
+        dst.write_all(buf)?;
+        src.consume(len);
+        copied += len as u64;
+    }
+}
+
+/// Writer which writes everything to two writers, so that output can be
+/// indented once and sent to both; for instance, to a file and to a log.
+///
+/// Each write is made to the first writer, then whatever it accepted is
+/// written in full to the second, so that both receive identical content. If
+/// the second writer returns an error partway through, the part it hasn't
+/// taken is kept, and the write still succeeds (so that retrying it doesn't
+/// send the same bytes to the first writer again). The kept part is written to
+/// the second writer at the start of the next write or flush, or by
+/// [`into_inner`][Tee::into_inner], which return the error if the second
+/// writer fails again.
+///
+/// # Example
+///
+/// ```
+/// # use std::io::Write;
+/// use indent_write::io::{TeeIndentWriter, Tee};
+///
+/// let mut writer = TeeIndentWriter::new("  ", Tee::new(Vec::new(), Vec::new()));
+/// write!(writer, "Line 1\nLine 2\n").unwrap();
+///
+/// let (file, log) = writer.into_inner().into_inner().unwrap();
+/// assert_eq!(file, b"  Line 1\n  Line 2\n");
+/// assert_eq!(file, log);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Tee<A, B> {
+    first: A,
+    second: B,
+
+    // Bytes which the first writer has accepted, but the second hasn't yet.
+    behind: Vec<u8>,
+}
+
+/// An [`IndentWriter`] which writes to two writers. See [`Tee`].
+pub type TeeIndentWriter<'i, A, B> = IndentWriter<'i, Tee<A, B>>;
+
+impl<A, B> Tee<A, B> {
+    /// Create a new [`Tee`] which writes to both `first` and `second`.
+    #[inline]
+    pub fn new(first: A, second: B) -> Self {
+        Self {
+            first,
+            second,
+            behind: Vec::new(),
+        }
+    }
+
+    /// Get references to both of the wrapped writers.
+    #[inline]
+    pub fn get_ref(&self) -> (&A, &B) {
+        (&self.first, &self.second)
+    }
+
+    /// Get mutable references to both of the wrapped writers. Writing to
+    /// either of them directly means that they'll no longer receive
+    /// identical content.
+    #[inline]
+    pub fn get_mut(&mut self) -> (&mut A, &mut B) {
+        (&mut self.first, &mut self.second)
+    }
+}
+
+impl<A, B: io::Write> Tee<A, B> {
+    /// Extract both writers from the [`Tee`]. If the second writer has yet
+    /// to take something after an error, it's written first, and if that
+    /// fails, the error is returned.
+    pub fn into_inner(mut self) -> io::Result<(A, B)> {
+        self.catch_up()?;
+        Ok((self.first, self.second))
+    }
+
+    // Write everything the second writer is behind by. On error, whatever
+    // it didn't take is kept for next time.
+    fn catch_up(&mut self) -> io::Result<()> {
+        while !self.behind.is_empty() {
+            match self.second.write(&self.behind) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    self.behind.drain(..n);
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
+    }
 }
 
-impl<'i, W: io::Write> IndentWriter<'i, W> {
-    /// Create a new [`IndentWriter`].
-    pub fn new(indent: &'i str, writer: W) -> Self {
-        Self {
-            writer,
-            indent,
-            state: NeedIndent,
-        }
+impl<A: io::Write, B: io::Write> io::Write for Tee<A, B> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.catch_up()?;
+
+        let written = self.first.write(buf)?;
+        self.behind.extend_from_slice(&buf[..written]);
+
+        // The first writer has taken `written` bytes, so they have to be
+        // reported as written, or a retry would send them to it twice. Any
+        // error from the second writer is returned by the next call instead.
+        let _ = self.catch_up();
+        Ok(written)
     }
 
-    /// Create a new [`IndentWriter`] which will not add an indent to the first
-    /// written line.
+    fn flush(&mut self) -> io::Result<()> {
+        self.catch_up()?;
+        self.first.flush()?;
+        self.second.flush()
+    }
+}
+
+impl IndentWriter<'_, Vec<u8>> {
+    /// [Finish][IndentWriter::finish] writing, and return an iterator over
+    /// the lines of the output, without their line endings. This is mostly
+    /// useful for checking the output in tests.
+    ///
+    /// Returns an error of kind [`InvalidData`][io::ErrorKind::InvalidData]
+    /// if the output isn't valid UTF-8.
     ///
     /// # Example
     ///
@@ -70,131 +4435,723 @@ impl<'i, W: io::Write> IndentWriter<'i, W> {
     /// # use std::io::Write;
     /// use indent_write::io::IndentWriter;
     ///
-    /// let mut buffer = Vec::new();
-    /// let mut writer = IndentWriter::new_skip_initial("    ", &mut buffer);
-    ///
-    /// writeln!(writer, "Line 1").unwrap();
-    /// writeln!(writer, "Line 2").unwrap();
-    /// writeln!(writer, "Line 3").unwrap();
+    /// let mut writer = IndentWriter::new("  ", Vec::new());
+    /// write!(writer, "Line 1\n\nLine 2").unwrap();
     ///
-    /// assert_eq!(buffer, b"Line 1\n    Line 2\n    Line 3\n")
+    /// let lines: Vec<String> = writer.lines().unwrap().collect();
+    /// assert_eq!(lines, ["  Line 1", "", "  Line 2"]);
     /// ```
+    pub fn lines(self) -> io::Result<OutputLines> {
+        let output = String::from_utf8(self.finish()?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        Ok(OutputLines {
+            lines: output
+                .lines()
+                .map(String::from)
+                .collect::<Vec<_>>()
+                .into_iter(),
+        })
+    }
+}
+
+/// Iterator over the lines written to an [`IndentWriter`], created by
+/// [`IndentWriter::lines`].
+#[derive(Debug, Clone)]
+pub struct OutputLines {
+    lines: std::vec::IntoIter<String>,
+}
+
+impl Iterator for OutputLines {
+    type Item = String;
+
     #[inline]
-    pub fn new_skip_initial(indent: &'i str, writer: W) -> Self {
-        Self {
-            writer,
-            indent,
-            state: MidLine,
-        }
+    fn next(&mut self) -> Option<String> {
+        self.lines.next()
     }
 
-    /// Extract the writer from the [`IndentWriter`], discarding any in-progress
-    /// indent state.
     #[inline]
-    pub fn into_inner(self) -> W {
-        self.writer
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.lines.size_hint()
+    }
+}
+
+/// An error from an [`IndentWriter`], annotated with the line being written
+/// when it occurred. See [`IndentWriter::set_annotate_errors`].
+///
+/// This is returned as the inner error of an [`io::Error`] with the same
+/// [kind][io::Error::kind] as the original error; use
+/// [`io::Error::get_ref`] and [`downcast_ref`][std::error::Error#method.downcast_ref]
+/// to retrieve it.
+#[derive(Debug)]
+pub struct LineError {
+    lines_written: u64,
+    error: io::Error,
+}
+
+impl LineError {
+    /// Get the number of complete lines which had been written when the error
+    /// occurred (see [`IndentWriter::lines_written`]). This is also the
+    /// zero-based index of the line being written.
+    #[inline]
+    pub fn lines_written(&self) -> u64 {
+        self.lines_written
     }
 
-    /// Get a reference to the wrapped writer
+    /// Get a reference to the original error.
     #[inline]
-    pub fn get_ref(&self) -> &W {
-        &self.writer
+    pub fn error(&self) -> &io::Error {
+        &self.error
     }
 
-    /// Get the string being used as an indent for each line
+    /// Extract the original error.
     #[inline]
-    pub fn indent(&self) -> &'i str {
-        self.indent
+    pub fn into_error(self) -> io::Error {
+        self.error
     }
 }
 
-impl<'i, W: io::Write> io::Write for IndentWriter<'i, W> {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "error writing line {}: {}",
+            self.lines_written.saturating_add(1),
+            self.error
+        )
+    }
+}
+
+impl std::error::Error for LineError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl From<LineError> for io::Error {
+    #[inline]
+    fn from(error: LineError) -> Self {
+        io::Error::new(error.error.kind(), error)
+    }
+}
+
+/// The error returned by [`IndentWriter::try_inc`] when the indent level is
+/// already at its maximum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IndentDepthError {
+    max_level: u16,
+}
+
+impl IndentDepthError {
+    /// Get the maximum indent level which would have been exceeded.
+    #[inline]
+    pub fn max_level(&self) -> u16 {
+        self.max_level
+    }
+}
+
+impl fmt::Display for IndentDepthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "indent level can't exceed the maximum of {}",
+            self.max_level
+        )
+    }
+}
+
+impl std::error::Error for IndentDepthError {}
+
+/// A snapshot of the indent level of an [`IndentWriter`].
+///
+/// Created with [`IndentWriter::save`]; see its documentation for an example.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IndentSnapshot {
+    indent_level: u16,
+}
+
+impl IndentSnapshot {
+    /// Get the indent level captured by this snapshot.
+    #[inline]
+    pub fn indent_level(&self) -> u16 {
+        self.indent_level
+    }
+}
+
+/// Guard which restores the indent level of an [`IndentWriter`] when dropped.
+///
+/// Created with [`IndentWriter::indented`] or [`IndentWriter::at_level`]; see
+/// their documentation for examples.
+#[derive(Debug)]
+pub struct IndentGuard<'a, 'i, W: io::Write> {
+    writer: &'a mut IndentWriter<'i, W>,
+    snapshot: IndentSnapshot,
+}
+
+impl<'i, W: io::Write> Deref for IndentGuard<'_, 'i, W> {
+    type Target = IndentWriter<'i, W>;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.writer
+    }
+}
+
+impl<W: io::Write> DerefMut for IndentGuard<'_, '_, W> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.writer
+    }
+}
+
+impl<W: io::Write> Drop for IndentGuard<'_, '_, W> {
+    fn drop(&mut self) {
+        self.writer.restore(self.snapshot);
+    }
+}
+
+/// Run a block with the indent level of an [`IndentWriter`] increased by one,
+/// restoring the original level afterwards.
+///
+/// `indented!(writer => { ... })` expands to:
+///
+/// ```ignore
+/// {
+///     let mut writer = writer.indented();
+///     { ... }
+/// }
+/// ```
+///
+/// That is, `writer` is shadowed within the block by an [`IndentGuard`],
+/// which restores the level when it's dropped. The level is therefore
+/// restored however the block is left, including by `return` or `?`. The
+/// value of the block is the value of the macro.
+///
+/// # Example
+///
+/// ```
+/// # use std::io::{self, Write};
+/// use indent_write::indented;
+/// use indent_write::io::IndentWriter;
+///
+/// # fn main() -> io::Result<()> {
+/// let mut writer = IndentWriter::with_indent_level("  ", 0, Vec::new());
+///
+/// writeln!(writer, "<root>")?;
+/// indented!(writer => {
+///     writeln!(writer, "<child>")?;
+///     indented!(writer => {
+///         writeln!(writer, "text")?;
+///     });
+///     writeln!(writer, "</child>")?;
+/// });
+/// writeln!(writer, "</root>")?;
+///
+/// assert_eq!(
+///     writer.get_ref(),
+///     b"<root>\n  <child>\n    text\n  </child>\n</root>\n"
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[macro_export]
+macro_rules! indented {
+    ($writer:ident => $body:block) => {{
+        #[allow(unused_mut)]
+        let mut $writer = $writer.indented();
+        $body
+    }};
+}
+
+impl<W: io::Write> IndentWriter<'_, W> {
+    // Write `buf`, checking for lone '\r's if requested.
+    fn write_checked(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.reject_lone_cr {
+            return self.write_indented(buf);
+        }
+
+        let buf = self.check_lone_cr(buf)?;
+        let written = self.write_indented(buf)?;
+
+        if let Some(&last) = buf[..written].last() {
+            self.unchecked_cr = last == b'\r';
+        }
+
+        Ok(written)
+    }
+
+    // Write `buf`, inserting indents as needed. Returns the number of bytes
+    // of `buf` consumed.
+    fn write_indented(&mut self, buf: &[u8]) -> io::Result<usize> {
         loop {
             match self.state {
+                // We were holding a '\r' (see `set_crlf_aware`), which turned
+                // out to be the start of a non-empty line. The indent has been
+                // written, so write the '\r' before continuing the line.
+                IndentState::MidLine if self.held_cr => match self.writer.write(b"\r")? {
+                    0 => break Ok(0),
+                    _ => self.held_cr = false,
+                },
+
+                // We're currently writing a line, and trimming trailing
+                // whitespace. Whitespace is held until we know if it's
+                // followed by more content or by the end of the line.
+                IndentState::MidLine if self.trimming() => match buf.first() {
+                    None => break self.writer.write(buf),
+
+                    Some(&b) if self.is_whitespace(b) => break Ok(self.hold_whitespace(buf)),
+
+                    // There's held whitespace, and it's followed by either
+                    // the end of the line (in which case it's discarded) or
+                    // more content. Either way, write what remains, then
+                    // continue looping.
+                    Some(&b) if !self.pending_whitespace.is_empty() => {
+                        if b == b'\n' {
+                            self.discard_whitespace();
+                        }
+
+                        if !self.write_pending_whitespace()? {
+                            break Ok(0);
+                        }
+                    }
+
+                    // Write up to the next whitespace. If this is the end of
+                    // the line, write the newline and request an indent.
+                    Some(_) => match buf
+                        .iter()
+                        .position(|&b| b == b'\n' || self.is_whitespace(b))
+                    {
+                        None => break self.writer.write(buf),
+                        Some(0) => {
+                            break self.writer.write(&buf[..1]).inspect(|&n| {
+                                if n > 0 {
+                                    self.state = NeedIndent;
+                                }
+                            })
+                        }
+                        Some(len) => break self.writer.write(&buf[..len]),
+                    },
+                },
+
                 // We're currently writing a line. Scan for the end of the line.
                 IndentState::MidLine => match buf.iter().position(|&b| b == b'\n') {
                     // No newlines in the input buffer, so write the entire thing.
                     None => break self.writer.write(buf),
 
-                    // We are at a newline presently. Request an indent be
-                    // written at the front of the next non-empty line, then
-                    // continue looping (since we haven't yet attempted to
-                    // write user data).
-                    Some(0) => self.state = NeedIndent,
-
                     // There's an upcoming newline. Write out the remainder of
                     // this line, plus its newline. If the entire line was
                     // written, request an indent on the subsequent call to
                     // write.
                     Some(len) => {
                         break self.writer.write(&buf[..len + 1]).inspect(|&n| {
-                            if n >= len {
+                            if n > len {
                                 self.state = NeedIndent;
                             }
                         })
                     }
                 },
 
+                // We need an indent, and we're holding a '\r'. If it's
+                // followed by a '\n', it was part of an empty line, so write
+                // it out; otherwise, it's the start of a non-empty line.
+                IndentState::NeedIndent if self.held_cr => match buf.first() {
+                    None => break Ok(0),
+                    Some(b'\n') if self.blank_lines_full() => self.held_cr = false,
+                    Some(b'\n') => match self.writer.write(b"\r")? {
+                        0 => break Ok(0),
+                        _ => self.held_cr = false,
+                    },
+                    Some(_) => self.start_line(buf),
+                },
+
+                // We need an indent, and we're holding whitespace from the
+                // start of a line which has turned out to be empty. Discard
+                // the whitespace (unless it's only held to find out if the
+                // line is blank, and the line isn't being collapsed), then
+                // continue with the empty line.
+                IndentState::NeedIndent
+                    if !self.pending_whitespace.is_empty()
+                        && !self.indents_empty_lines()
+                        && buf.first() == Some(&b'\n') =>
+                {
+                    if self.trim_trailing_whitespace || self.blank_lines_full() {
+                        self.discard_whitespace();
+                    }
+
+                    if !self.write_pending_whitespace()? {
+                        break Ok(0);
+                    }
+                }
+
+                // We need an indent, we're limiting runs of empty lines, and
+                // this is an empty line. If the run is already long enough,
+                // discard it; otherwise, write just this line, so that it can
+                // be counted.
+                IndentState::NeedIndent
+                    if self.max_blank_lines.is_some() && self.empty_line_len(buf) > 0 =>
+                {
+                    let len = self.empty_line_len(buf);
+
+                    if self.blank_lines_full() {
+                        break Ok(len);
+                    } else if self.indents_empty_lines() {
+                        self.start_line(buf);
+                    } else {
+                        break self.writer.write(&buf[..len]).inspect(|&n| {
+                            self.note_empty_lines(&buf[..n]);
+
+                            if n >= len {
+                                self.blank_lines += 1;
+                            }
+                        });
+                    }
+                }
+
                 // We need an indent. Scan for the next non-empty line.
-                IndentState::NeedIndent => match buf.iter().position(|&b| b != b'\n') {
+                IndentState::NeedIndent => match self.next_line_start(buf) {
                     // No non-empty lines in the input buffer, so write the entire thing
-                    None => break self.writer.write(buf),
+                    None => {
+                        break self
+                            .writer
+                            .write(buf)
+                            .inspect(|&n| self.note_empty_lines(&buf[..n]))
+                    }
+
+                    // We're re-indenting, and this line begins with its own
+                    // indentation. Discard it, then continue on the next
+                    // call, since the line may turn out to be empty.
+                    Some(0) if self.old_indent_len(buf) > 0 => break Ok(self.old_indent_len(buf)),
+
+                    // We're trimming trailing whitespace (or treating
+                    // whitespace-only lines as blank), and this line begins
+                    // with whitespace. Hold it (without an indent) until we
+                    // know if there's anything else on the line.
+                    Some(0) if self.holding_blank_whitespace() && self.is_whitespace(buf[0]) => {
+                        break Ok(self.hold_whitespace(buf))
+                    }
+
+                    // The input is a lone '\r', which might be part of a
+                    // "\r\n" we can't see yet. Hold it until the next write.
+                    Some(0) if self.crlf() && !self.indents_empty_lines() && buf == b"\r" => {
+                        self.held_cr = true;
+                        break Ok(1);
+                    }
 
                     // We are at the beginning of a non-empty line presently.
                     // Begin inserting an indent now, then continue looping
                     // (since we haven't yet attempted to write user data)
-                    Some(0) => self.state = WritingIndent(self.indent.as_bytes()),
+                    Some(0) => self.start_line(buf),
 
                     // There's an upcoming non-empty line. Write out the
                     // remainder of the empty lines. If all the empty lines
                     // were written, force an indent on the subsequent call to
-                    // write.
+                    // write (unless the "line" is a lone trailing '\r', or
+                    // begins with indentation to be discarded, which will be
+                    // dealt with on the subsequent call).
                     Some(len) => {
+                        let deferred = (self.crlf() && &buf[len..] == b"\r")
+                            || self.old_indent_len(&buf[len..]) > 0;
+
                         break self.writer.write(&buf[..len]).inspect(|&n| {
-                            if n >= len {
-                                self.state = IndentState::WritingIndent(self.indent.as_bytes())
+                            self.note_empty_lines(&buf[..n]);
+
+                            if n >= len && !deferred {
+                                self.start_line(&buf[len..])
                             }
-                        })
+                        });
                     }
                 },
 
                 // We are writing an indent unconditionally. If we're in this
                 // state, the input buffer is known to be the start of a non-
                 // empty line.
-                IndentState::WritingIndent(indent) => match self.writer.write(indent)? {
-                    // We successfully wrote the entire indent. Continue with
-                    // writing the input buffer.
-                    n if n >= indent.len() => self.state = MidLine,
+                IndentState::WritingIndent(written) => {
+                    let indent = &self.required_indent[written..];
 
-                    // Eof; stop work immediately
-                    0 => break Ok(0),
+                    match self.writer.write_verbatim(indent)? {
+                        // We successfully wrote the entire indent. Continue
+                        // with writing the input buffer.
+                        n if n >= indent.len() => self.finish_indent(),
 
-                    // Only a part of the indent was written. Continue
-                    // trying to write the rest of it, but update our state
-                    // to keep it consistent in case the next write is an
-                    // error
-                    n => self.state = WritingIndent(&indent[n..]),
-                },
+                        // Eof; stop work immediately
+                        0 => break Ok(0),
+
+                        // Only a part of the indent was written. Continue
+                        // trying to write the rest of it, but update our state
+                        // to keep it consistent in case the next write is an
+                        // error
+                        n => self.state = WritingIndent(written + n),
+                    }
+                }
             }
         }
     }
 
-    fn flush(&mut self) -> io::Result<()> {
-        // If we're currently in the middle of writing an indent, flush it
-        while let WritingIndent(ref mut indent) = self.state {
-            match self.writer.write(*indent)? {
-                // We wrote the entire indent. Proceed with the flush
-                len if len >= indent.len() => self.state = MidLine,
+    // In strict mode, get the part of `buf` which can be written without
+    // writing a lone '\r'. If the '\r' is at the start of `buf`, this is an
+    // error; otherwise, the error will be reported by the next write.
+    fn check_lone_cr<'b>(&self, buf: &'b [u8]) -> io::Result<&'b [u8]> {
+        let lone_cr = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "found a '\\r' which isn't followed by a '\\n'",
+            )
+        };
 
-                // EoF; return an error
-                0 => return Err(io::ErrorKind::WriteZero.into()),
+        if self.unchecked_cr && buf.first().is_some_and(|&b| b != b'\n') {
+            return Err(lone_cr());
+        }
 
-                // Partial write, continue writing.
-                len => *indent = &indent[len..],
+        let position = buf
+            .iter()
+            .enumerate()
+            .position(|(i, &b)| b == b'\r' && buf.get(i + 1) != Some(&b'\n'));
+
+        match position {
+            None => Ok(buf),
+
+            // A '\r' on its own might be followed by a '\n' in the next
+            // write, so it's checked then.
+            Some(0) if buf.len() == 1 => Ok(buf),
+
+            Some(0) => Err(lone_cr()),
+            Some(len) => Ok(&buf[..len]),
+        }
+    }
+}
+
+impl<W: io::Write> io::Write for IndentWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.shebang_check {
+            if let Some(held) = self.check_shebang(buf) {
+                return Ok(held);
             }
         }
 
-        self.writer.flush()
+        if !self.shebang_held.is_empty() {
+            self.write_shebang_held()?;
+        }
+
+        match self.wrap_width {
+            Some(width) => self.write_wrapped(buf, width),
+            None => self.write_unheld(buf),
+        }
+    }
+
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> io::Result<()> {
+        let mut adapter = FmtAdapter {
+            writer: self,
+            error: Ok(()),
+        };
+
+        match fmt::write(&mut adapter, args) {
+            Ok(()) => Ok(()),
+            Err(_) => adapter.error.and(Err(io::Error::other("formatter error"))),
+        }
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        // In the middle of a line, whole buffers up to the one containing
+        // the next newline can be forwarded as-is. Anything else goes
+        // through the state machine one buffer at a time.
+        let checked = self.reject_lone_cr;
+        let held = self.held_cr
+            || self.trimming()
+            || !self.filter_buffer.is_empty()
+            || !self.shebang_held.is_empty()
+            || self.wrap_width.is_some();
+
+        if let (MidLine, false, false) = (self.state, held, checked) {
+            let plain = bufs
+                .iter()
+                .position(|buf| buf.contains(&b'\n'))
+                .unwrap_or(bufs.len());
+
+            let plain = &bufs[..plain];
+
+            if plain.iter().any(|buf| !buf.is_empty()) {
+                return self
+                    .writer
+                    .write_vectored(plain)
+                    .map_err(|err| self.annotate_error(err));
+            }
+        }
+
+        self.write(first_nonempty(bufs))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_unwritten()
+            .and_then(|()| self.writer.flush())
+            .map_err(|err| self.annotate_error(err))
+    }
+}
+
+/// Seeks the wrapped writer. Any partially written indent or other pending
+/// output is written first, so that it ends up at the old position.
+///
+/// Because the position in the output has changed, the writer can no longer
+/// know if it's at the start of a line. Seeking (other than with
+/// [`stream_position`][io::Seek::stream_position]) assumes it is, so the next
+/// non-empty line written is indented.
+///
+/// # Example
+///
+/// ```
+/// use std::io::{Cursor, Seek, SeekFrom, Write};
+/// use indent_write::io::IndentWriter;
+///
+/// let mut writer = IndentWriter::new("  ", Cursor::new(Vec::new()));
+/// writeln!(writer, "Count: 0").unwrap();
+/// writeln!(writer, "Item").unwrap();
+///
+/// // Go back and patch the header
+/// writer.seek(SeekFrom::Start(0)).unwrap();
+/// write!(writer, "Count: 1").unwrap();
+///
+/// assert_eq!(writer.get_ref().get_ref(), b"  Count: 1\n  Item\n");
+/// ```
+impl<W: io::Write + io::Seek> io::Seek for IndentWriter<'_, W> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        self.write_unwritten()?;
+        let pos = self.writer.writer.seek(pos)?;
+
+        self.writer.column = 0;
+        self.writer.column_chars = 0;
+        #[cfg(feature = "unicode-width")]
+        self.writer.reset_width();
+        self.unchecked_cr = false;
+        self.blank_lines = 0;
+        self.state = NeedIndent;
+
+        Ok(pos)
+    }
+
+    fn stream_position(&mut self) -> io::Result<u64> {
+        self.write_unwritten()?;
+        self.writer.writer.stream_position()
+    }
+}
+
+/// Writes formatted text through the [`IndentWriter`], so that it can be used
+/// with APIs that expect a [`fmt::Write`]. Any I/O error is reported as a
+/// [`fmt::Error`].
+///
+/// # Example
+///
+/// ```
+/// use std::fmt;
+/// use indent_write::io::IndentWriter;
+///
+/// fn emit(mut dest: impl fmt::Write) -> fmt::Result {
+///     writeln!(dest, "Line 1\nLine 2")
+/// }
+///
+/// let mut writer = IndentWriter::new("    ", Vec::new());
+/// emit(&mut writer).unwrap();
+///
+/// assert_eq!(writer.get_ref(), b"    Line 1\n    Line 2\n");
+/// ```
+impl<W: io::Write> fmt::Write for IndentWriter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_piece(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+// Adapter for `io::Write::write_fmt`, which writes each piece with
+// `write_piece`, and keeps the underlying error.
+struct FmtAdapter<'a, 'i, W> {
+    writer: &'a mut IndentWriter<'i, W>,
+    error: io::Result<()>,
+}
+
+impl<W: io::Write> fmt::Write for FmtAdapter<'_, '_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.writer.write_piece(s.as_bytes()).map_err(|err| {
+            self.error = Err(err);
+            fmt::Error
+        })
     }
 }
+
+/// Writers which know the total width of the indentation added to each line
+/// written through them.
+///
+/// This is implemented for [`IndentWriter`] when the wrapped writer also
+/// implements it, so that the indentation of a chain of nested writers can be
+/// found from the outermost one; and for common writers which don't indent
+/// anything, which end the chain.
+///
+/// # Example
+///
+/// ```
+/// use indent_write::io::{EffectiveIndent, IndentWriter};
+///
+/// let mut outer = IndentWriter::new("\t", Vec::new());
+/// outer.inc();
+///
+/// let inner = IndentWriter::new("  ", &mut outer);
+/// assert_eq!(inner.effective_indent_len(), 4);
+/// ```
+pub trait EffectiveIndent {
+    /// Get the total length, in bytes, of the indentation which will be
+    /// added to the next line written through this writer, including any
+    /// added by the writers it wraps.
+    fn effective_indent_len(&self) -> usize;
+}
+
+impl<W: EffectiveIndent> EffectiveIndent for IndentWriter<'_, W> {
+    /// This is the length of this writer's
+    /// [`current_indent`][IndentWriter::current_indent], plus the effective
+    /// indent of the wrapped writer.
+    #[inline]
+    fn effective_indent_len(&self) -> usize {
+        self.required_indent.len() + self.writer.writer.effective_indent_len()
+    }
+}
+
+impl<W: EffectiveIndent + ?Sized> EffectiveIndent for &mut W {
+    #[inline]
+    fn effective_indent_len(&self) -> usize {
+        (**self).effective_indent_len()
+    }
+}
+
+impl<W: EffectiveIndent + ?Sized> EffectiveIndent for Box<W> {
+    #[inline]
+    fn effective_indent_len(&self) -> usize {
+        (**self).effective_indent_len()
+    }
+}
+
+impl<W: io::Write + EffectiveIndent> EffectiveIndent for io::BufWriter<W> {
+    #[inline]
+    fn effective_indent_len(&self) -> usize {
+        self.get_ref().effective_indent_len()
+    }
+}
+
+// Writers which don't indent anything end a chain of nested writers.
+macro_rules! no_effective_indent {
+    ($($type:ty,)*) => {$(
+        impl EffectiveIndent for $type {
+            #[inline]
+            fn effective_indent_len(&self) -> usize {
+                0
+            }
+        }
+    )*};
+}
+
+no_effective_indent! {
+    Vec<u8>,
+    io::Cursor<Vec<u8>>,
+    io::Cursor<&mut Vec<u8>>,
+    io::Cursor<&mut [u8]>,
+    io::Sink,
+    io::Stdout,
+    io::StdoutLock<'_>,
+    io::Stderr,
+    io::StderrLock<'_>,
+    std::fs::File,
+}
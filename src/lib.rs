@@ -7,35 +7,34 @@
 //!
 //! See [`fmt::IndentWriter`], [`io::IndentWriter`], and
 //! [`indentable::Indentable`] for examples.
+//!
+//! With the `embedded-io` feature enabled, `embedded_io::IndentWriter`
+//! provides the same adapter for `embedded_io::Write`, for `no_std` targets.
+//!
+//! With the `unicode-width` feature enabled, `io::IndentWriter` counts columns
+//! by display width, so that wide characters such as emoji are aligned
+//! correctly in a terminal.
+//!
+//! With the `serde_json` feature enabled, `serde_json::to_writer` writes
+//! pretty-printed JSON through an `io::IndentWriter`, using its indentation.
+//!
+//! With the `tokio` feature enabled, `tokio::IndentWriter` provides the same
+//! adapter for `tokio::io::AsyncWrite`, for async code.
 
 pub mod fmt;
 pub mod indentable;
 
-#[cfg(feature = "std")]
-pub mod io;
-
-trait Inspect<T> {
-    fn inspect(self, func: impl FnOnce(&T)) -> Self;
-}
+#[cfg(feature = "embedded-io")]
+pub mod embedded_io;
 
-impl<T> Inspect<T> for Option<T> {
-    #[inline]
-    fn inspect(self, func: impl FnOnce(&T)) -> Self {
-        if let Some(ref value) = self {
-            func(value)
-        }
+#[cfg(any(feature = "embedded-io", feature = "tokio"))]
+mod indenter;
 
-        self
-    }
-}
+#[cfg(feature = "std")]
+pub mod io;
 
-impl<T, E> Inspect<T> for Result<T, E> {
-    #[inline]
-    fn inspect(self, func: impl FnOnce(&T)) -> Self {
-        if let Ok(ref value) = self {
-            func(value)
-        }
+#[cfg(feature = "serde_json")]
+pub mod serde_json;
 
-        self
-    }
-}
+#[cfg(feature = "tokio")]
+pub mod tokio;
@@ -0,0 +1,222 @@
+use std::cell::Cell;
+use std::io;
+
+use ::serde::Serialize;
+use ::serde_json::ser::{Formatter, Serializer};
+
+use crate::io::IndentWriter;
+
+/// Serialize `value` as pretty-printed JSON to an [`IndentWriter`]. Each level
+/// of nesting in the JSON is indented by one more indent level of `writer`,
+/// so the output uses the writer's indent (and any other options, such as
+/// guides or line numbers) rather than a fixed indent. The JSON starts at the
+/// writer's current position and indent level, and the level is restored
+/// afterwards.
+///
+/// If the writer has a [raw indent][IndentWriter::set_raw_indent], each
+/// level of nesting adds the writer's indent to the raw indent instead, and
+/// the raw indent is restored afterwards.
+///
+/// With an indent of two spaces at level 0, the output is the same as
+/// `serde_json::to_writer_pretty`.
+///
+/// # Example
+///
+/// ```
+/// # use std::io::Write;
+/// use indent_write::io::IndentWriter;
+///
+/// let value = serde_json::json!({ "name": "x", "tags": ["a", "b"] });
+///
+/// let mut writer = IndentWriter::new("> ", Vec::new());
+/// write!(writer, "value = ").unwrap();
+/// indent_write::serde_json::to_writer(&mut writer, &value).unwrap();
+///
+/// assert_eq!(
+///     std::str::from_utf8(writer.get_ref()).unwrap(),
+///     r#"> value = {
+/// > > "name": "x",
+/// > > "tags": [
+/// > > > "a",
+/// > > > "b"
+/// > > ]
+/// > }"#
+/// );
+/// ```
+pub fn to_writer<W, T>(writer: &mut IndentWriter<'_, W>, value: &T) -> ::serde_json::Result<()>
+where
+    W: io::Write,
+    T: ?Sized + Serialize,
+{
+    let base = writer.indent_level();
+    let raw = writer.raw_indent().map(<[u8]>::to_vec);
+    let depth = Cell::new(0);
+
+    let mut serializer = Serializer::with_formatter(
+        LevelWriter {
+            writer: &mut *writer,
+            base,
+            raw: raw.clone(),
+            depth: &depth,
+            applied: 0,
+        },
+        JsonFormatter {
+            depth: &depth,
+            has_value: false,
+        },
+    );
+
+    let result = value.serialize(&mut serializer);
+
+    match raw {
+        Some(raw) => writer.set_raw_indent(raw),
+        None => writer.set_indent_level(base),
+    }
+
+    result
+}
+
+// Writer which sets the indent of an `IndentWriter` to follow the nesting
+// depth of the JSON before each write: by setting the indent level, or, if
+// the writer had a raw indent, by extending the raw indent (since changing
+// the level would discard it).
+struct LevelWriter<'a, 'i, W> {
+    writer: &'a mut IndentWriter<'i, W>,
+    base: u16,
+    raw: Option<Vec<u8>>,
+    depth: &'a Cell<u16>,
+
+    // The depth the writer's indent currently reflects.
+    applied: u16,
+}
+
+impl<W: io::Write> io::Write for LevelWriter<'_, '_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let depth = self.depth.get();
+
+        if depth != self.applied {
+            self.applied = depth;
+
+            match self.raw {
+                Some(ref raw) => {
+                    let mut indent = raw.clone();
+
+                    for _ in 0..depth {
+                        indent.extend_from_slice(self.writer.indent_bytes());
+                    }
+
+                    self.writer.set_raw_indent(indent);
+                }
+                None => self
+                    .writer
+                    .set_indent_level(self.base.saturating_add(depth)),
+            }
+        }
+
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+// Formatter which lays out JSON like `serde_json::ser::PrettyFormatter`, but
+// without writing any indentation. Instead, it tracks the nesting depth, so
+// that `LevelWriter` can set the indent level.
+struct JsonFormatter<'a> {
+    depth: &'a Cell<u16>,
+    has_value: bool,
+}
+
+impl JsonFormatter<'_> {
+    // Write the opening bracket of an array or object, increasing the depth
+    // for its contents.
+    fn begin<W: ?Sized + io::Write>(&mut self, writer: &mut W, bracket: &[u8]) -> io::Result<()> {
+        self.has_value = false;
+        writer.write_all(bracket)?;
+        self.depth.set(self.depth.get().saturating_add(1));
+        Ok(())
+    }
+
+    // Write the closing bracket of an array or object on its own line, if
+    // it has any contents.
+    fn end<W: ?Sized + io::Write>(&mut self, writer: &mut W, bracket: &[u8]) -> io::Result<()> {
+        self.depth.set(self.depth.get().saturating_sub(1));
+
+        if self.has_value {
+            writer.write_all(b"\n")?;
+        }
+
+        writer.write_all(bracket)
+    }
+
+    // Start a new line for the next value in an array or object.
+    fn begin_value<W: ?Sized + io::Write>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        match first {
+            true => writer.write_all(b"\n"),
+            false => writer.write_all(b",\n"),
+        }
+    }
+}
+
+impl Formatter for JsonFormatter<'_> {
+    #[inline]
+    fn begin_array<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.begin(writer, b"[")
+    }
+
+    #[inline]
+    fn end_array<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.end(writer, b"]")
+    }
+
+    #[inline]
+    fn begin_array_value<W: ?Sized + io::Write>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        self.begin_value(writer, first)
+    }
+
+    #[inline]
+    fn end_array_value<W: ?Sized + io::Write>(&mut self, _writer: &mut W) -> io::Result<()> {
+        self.has_value = true;
+        Ok(())
+    }
+
+    #[inline]
+    fn begin_object<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.begin(writer, b"{")
+    }
+
+    #[inline]
+    fn end_object<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.end(writer, b"}")
+    }
+
+    #[inline]
+    fn begin_object_key<W: ?Sized + io::Write>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        self.begin_value(writer, first)
+    }
+
+    #[inline]
+    fn begin_object_value<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(b": ")
+    }
+
+    #[inline]
+    fn end_object_value<W: ?Sized + io::Write>(&mut self, _writer: &mut W) -> io::Result<()> {
+        self.has_value = true;
+        Ok(())
+    }
+}
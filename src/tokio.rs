@@ -0,0 +1,210 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use ::tokio::io::AsyncWrite;
+
+use crate::indenter::{LineIndenter, Next};
+
+/// Adapter for [`tokio::io::AsyncWrite`] writers to indent each
+/// line
+///
+/// An `IndentWriter` adapts an [`AsyncWrite`] object to insert an indent
+/// before each non-empty line. Specifically, this means it will insert an
+/// indent between each newline when followed by a non-newline. It works the
+/// same way as `io::IndentWriter`, but without blocking: if the wrapped writer
+/// isn't ready partway through an indent, the rest of the indent is written
+/// the next time the writer is polled.
+///
+/// These writers can be nested to provide increasing levels of indentation.
+///
+/// The wrapped writer must be [`Unpin`]; a writer which isn't can be wrapped
+/// with [`Box::pin`] first.
+///
+/// # Example
+///
+/// ```
+/// use tokio::io::AsyncWriteExt;
+/// use indent_write::tokio::IndentWriter;
+///
+/// # tokio_test::block_on(async {
+/// let mut indented = IndentWriter::new("\t", Vec::new());
+///
+/// // Lines will be indented
+/// indented.write_all(b"Line 1\nLine 2\n").await.unwrap();
+///
+/// // Empty lines will not be indented
+/// indented.write_all(b"\n\nLine 3\n\n").await.unwrap();
+///
+/// assert_eq!(indented.get_ref(), b"\tLine 1\n\tLine 2\n\n\n\tLine 3\n\n");
+/// # });
+/// ```
+///
+/// # Indent levels
+///
+/// Each line is prefixed with `indent` repeated once per indent level. A new
+/// writer starts at level 1; the level can be adjusted at any time with
+/// [`inc`][IndentWriter::inc], [`dec`][IndentWriter::dec],
+/// [`reset`][IndentWriter::reset], and
+/// [`set_indent_level`][IndentWriter::set_indent_level]. Changes to the level
+/// take effect at the start of the next line.
+#[derive(Debug, Clone)]
+pub struct IndentWriter<'i, W> {
+    writer: W,
+    indenter: LineIndenter<'i>,
+}
+
+impl<'i, W: AsyncWrite + Unpin> IndentWriter<'i, W> {
+    /// Create a new [`IndentWriter`].
+    #[inline]
+    pub fn new(indent: &'i str, writer: W) -> Self {
+        Self {
+            writer,
+            indenter: LineIndenter::new(indent, false),
+        }
+    }
+
+    /// Create a new [`IndentWriter`] which will not add an indent to the first
+    /// written line.
+    #[inline]
+    pub fn new_skip_initial(indent: &'i str, writer: W) -> Self {
+        Self {
+            writer,
+            indenter: LineIndenter::new(indent, true),
+        }
+    }
+
+    /// Extract the writer from the `IndentWriter`, discarding any in-progress
+    /// indent state.
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Get a reference to the wrapped writer
+    #[inline]
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Get a mutable reference to the wrapped writer. Writing to it directly
+    /// bypasses the indentation, and may interleave with a partially written
+    /// indent.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Get the string being used as an indent for each line
+    #[inline]
+    pub fn indent(&self) -> &'i str {
+        self.indenter.indent()
+    }
+
+    /// Get the current indent level; that is, the number of times the indent
+    /// is repeated at the start of each line.
+    #[inline]
+    pub fn indent_level(&self) -> u16 {
+        self.indenter.indent_level()
+    }
+
+    /// Increase the indent level by one.
+    #[inline]
+    pub fn inc(&mut self) {
+        self.set_indent_level(self.indent_level().saturating_add(1));
+    }
+
+    /// Decrease the indent level by one. Has no effect if the level is
+    /// already 0.
+    #[inline]
+    pub fn dec(&mut self) {
+        self.set_indent_level(self.indent_level().saturating_sub(1));
+    }
+
+    /// Reset the indent level to 0, so that no indent is inserted.
+    #[inline]
+    pub fn reset(&mut self) {
+        self.set_indent_level(0);
+    }
+
+    /// Set the indent level directly.
+    ///
+    /// If a line is currently being written, it is unaffected; the new level
+    /// takes effect at the start of the next line.
+    #[inline]
+    pub fn set_indent_level(&mut self, level: u16) {
+        self.indenter.set_indent_level(level);
+    }
+
+    // Write a piece of the current line (or of a run of empty lines) to the
+    // wrapped writer.
+    #[inline]
+    fn poll_write_inner(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.writer).poll_write(cx, buf)
+    }
+
+    // Write out the rest of a partially written indent.
+    fn poll_write_indent(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while let Some(part) = self.indenter.unwritten_indent() {
+            match ready!(self.poll_write_inner(cx, part))? {
+                0 => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                n => self.indenter.indent_written(n),
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for IndentWriter<'_, W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        loop {
+            match this.indenter.next(buf) {
+                // Write a piece of the current line (or of a run of empty
+                // lines), and let the indenter know how much was written, so
+                // that it knows whether an indent is due.
+                Next::Input(len) => {
+                    let n = ready!(this.poll_write_inner(cx, &buf[..len]))?;
+                    this.indenter.input_written(buf, len, n);
+                    break Poll::Ready(Ok(n));
+                }
+
+                // We are writing an indent unconditionally, before the start
+                // of a non-empty line. If the writer isn't ready, the progress
+                // through the indent is kept, so that it resumes at the next
+                // poll.
+                Next::Indent(part) => match ready!(this.poll_write_inner(cx, part))? {
+                    // The writer broke its contract by accepting nothing
+                    // without an error; stop work immediately
+                    0 => break Poll::Ready(Ok(0)),
+
+                    // Some or all of the indent was written. Update our state
+                    // to keep it consistent in case the next write is an
+                    // error
+                    n => this.indenter.indent_written(n),
+                },
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        // If we're currently in the middle of writing an indent, flush it
+        ready!(this.poll_write_indent(cx))?;
+        Pin::new(&mut this.writer).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        ready!(this.poll_write_indent(cx))?;
+        Pin::new(&mut this.writer).poll_shutdown(cx)
+    }
+}
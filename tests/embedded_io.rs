@@ -0,0 +1,193 @@
+#![cfg(feature = "embedded-io")]
+// These tests deliberately route literals through format strings, to exercise
+// the writers the way real formatting code does.
+#![allow(clippy::write_literal, clippy::write_with_newline)]
+
+use std::str::from_utf8;
+
+use embedded_io::{Error as _, ErrorKind, ErrorType, Write};
+use indent_write::embedded_io::{Error, IndentWriter};
+
+// This is an in-memory sink, like a UART with a fixed size buffer. It accepts
+// at most `chunk` bytes per write, and fails with `WriteZero` once the buffer
+// is full. While `stalled` is set, it misbehaves by accepting nothing, without
+// an error.
+#[derive(Debug)]
+struct Mock {
+    buffer: [u8; 256],
+    len: usize,
+    capacity: usize,
+    chunk: usize,
+    stalled: bool,
+}
+
+impl Mock {
+    fn new(chunk: usize) -> Self {
+        Mock {
+            buffer: [0; 256],
+            len: 0,
+            capacity: 256,
+            chunk,
+            stalled: false,
+        }
+    }
+
+    fn contents(&self) -> &str {
+        from_utf8(&self.buffer[..self.len]).unwrap()
+    }
+}
+
+impl ErrorType for Mock {
+    type Error = ErrorKind;
+}
+
+impl Write for Mock {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, ErrorKind> {
+        if buf.is_empty() || self.stalled {
+            return Ok(0);
+        }
+
+        let n = buf.len().min(self.chunk).min(self.capacity - self.len);
+
+        if n == 0 {
+            return Err(ErrorKind::WriteZero);
+        }
+
+        self.buffer[self.len..self.len + n].copy_from_slice(&buf[..n]);
+        self.len += n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<(), ErrorKind> {
+        Ok(())
+    }
+}
+
+const CONTENT: &str = "\
+Line 1
+Line 2
+
+Line 3
+\n\n\
+Line 4
+";
+
+#[test]
+fn basic_test() {
+    for &chunk in &[1, 2, 3, 256] {
+        let mut writer = IndentWriter::new("\t", Mock::new(chunk));
+        write!(writer, "{}", CONTENT).unwrap();
+        assert_eq!(
+            writer.get_ref().contents(),
+            "\tLine 1\n\tLine 2\n\n\tLine 3\n\n\n\tLine 4\n",
+            "chunk size {}",
+            chunk
+        );
+    }
+}
+
+#[test]
+fn test_skip_initial() {
+    let mut writer = IndentWriter::new_skip_initial("  ", Mock::new(256));
+    write!(writer, "{}", CONTENT).unwrap();
+    assert_eq!(
+        writer.get_ref().contents(),
+        "Line 1\n  Line 2\n\n  Line 3\n\n\n  Line 4\n"
+    );
+}
+
+#[test]
+fn test_indent_level() {
+    let mut writer = IndentWriter::new("->", Mock::new(3));
+    write!(writer, "Level 1\n").unwrap();
+    writer.inc();
+    writer.inc();
+    write!(writer, "Level 3\nLevel 3 ").unwrap();
+
+    // Changes take effect on the next line
+    writer.dec();
+    assert_eq!(writer.indent_level(), 2);
+    write!(writer, "continued\n").unwrap();
+
+    writer.reset();
+    writer.dec();
+    write!(writer, "Level 0\n").unwrap();
+
+    assert_eq!(
+        writer.get_ref().contents(),
+        "->Level 1\n->->->Level 3\n->->->Level 3 continued\nLevel 0\n"
+    );
+}
+
+// An empty line written on its own, including straight after a level change,
+// is never indented, and the line after it always is
+#[test]
+fn test_lone_newline_write() {
+    let mut writer = IndentWriter::new("  ", Mock::new(1));
+    writer.write_all(b"a").unwrap();
+    writer.write_all(b"\n").unwrap();
+    writer.inc();
+    writer.write_all(b"\n").unwrap();
+    writer.write_all(b"x").unwrap();
+    writer.write_all(b"\n").unwrap();
+    writer.dec();
+    writer.write_all(b"\n").unwrap();
+    writer.write_all(b"y").unwrap();
+    assert_eq!(writer.get_ref().contents(), "  a\n\n    x\n\n  y");
+}
+
+#[test]
+fn test_empty_indent() {
+    let mut writer = IndentWriter::new("", Mock::new(1));
+    writer.inc();
+    write!(writer, "{}", CONTENT).unwrap();
+    assert_eq!(writer.get_ref().contents(), CONTENT);
+}
+
+#[test]
+fn test_error_mid_indent() {
+    let mut mock = Mock::new(2);
+    mock.capacity = 3;
+
+    // The indent is only partially written before the error
+    let mut writer = IndentWriter::new("1234", mock);
+    writer.inc();
+    assert_eq!(
+        writer.write_all(b"Line\n"),
+        Err(Error::Inner(ErrorKind::WriteZero))
+    );
+    assert_eq!(writer.get_ref().contents(), "123");
+
+    // Level changes don't affect the indent being written
+    writer.reset();
+
+    // Make room, then write the rest of the indent with flush
+    writer.get_mut().capacity = 256;
+    writer.flush().unwrap();
+    assert_eq!(writer.get_ref().contents(), "12341234");
+
+    writer.write_all(b"Line\nLine\n").unwrap();
+    assert_eq!(writer.get_ref().contents(), "12341234Line\nLine\n");
+}
+
+#[test]
+fn test_flush_stalled_mid_indent() {
+    let mut mock = Mock::new(2);
+    mock.capacity = 3;
+
+    let mut writer = IndentWriter::new("1234", mock);
+    assert!(writer.write_all(b"Line\n").is_err());
+    assert_eq!(writer.get_ref().contents(), "123");
+
+    // A writer which accepts nothing is an error, rather than a panic
+    writer.get_mut().capacity = 256;
+    writer.get_mut().stalled = true;
+    assert_eq!(writer.flush(), Err(Error::WriteZero));
+    assert_eq!(writer.flush().unwrap_err().kind(), ErrorKind::WriteZero);
+
+    // The indent is still finished once the writer recovers
+    writer.get_mut().stalled = false;
+    writer.flush().unwrap();
+    writer.write_all(b"Line\n").unwrap();
+    assert_eq!(writer.get_ref().contents(), "1234Line\n");
+}
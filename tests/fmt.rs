@@ -1,3 +1,12 @@
+// These tests deliberately route literals through format strings, to exercise
+// the writers the way real formatting code does.
+#![allow(
+    clippy::redundant_static_lifetimes,
+    clippy::write_literal,
+    clippy::write_with_newline,
+    clippy::writeln_empty_string
+)]
+
 use std::fmt::{self, Write};
 
 use indent_write::fmt::IndentWriter;
@@ -136,3 +145,293 @@ fn test_partial_writes_combined() {
     }
     assert_eq!(dest, "    Hello, World\n    😀 😀 😀\n    😀 😀 😀\n");
 }
+
+#[cfg(feature = "std")]
+#[test]
+fn test_indent_lines() {
+    use indent_write::fmt::indent_lines;
+
+    assert_eq!(indent_lines("", "\t"), "");
+    assert_eq!(indent_lines("Line 1", "\t"), "\tLine 1");
+    assert_eq!(indent_lines("Line 1\n", "\t"), "\tLine 1\n");
+    assert_eq!(
+        indent_lines("Line 1\n\n\nLine 2\n\n", "\t"),
+        "\tLine 1\n\n\n\tLine 2\n\n"
+    );
+    assert_eq!(
+        indent_lines("\n😀 😀 😀\n😀 😀 😀", "    "),
+        "\n    😀 😀 😀\n    😀 😀 😀"
+    );
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_indent_lines_iter() {
+    use std::borrow::Cow;
+
+    use indent_write::fmt::{indent_lines, indent_lines_iter};
+
+    for input in [
+        "",
+        "\n",
+        "Line 1",
+        "\n\nLine 1\n\n\nLine 2\n",
+        "😀\n😀 😀\n",
+    ] {
+        let lines: Vec<_> = indent_lines_iter(input, "\t").collect();
+        assert_eq!(lines.concat(), indent_lines(input, "\t"), "{:?}", input);
+
+        for line in lines {
+            assert_eq!(line.starts_with('\t'), line != "\n");
+        }
+    }
+
+    // Nothing needs to be allocated if there's no indent
+    assert!(indent_lines_iter("a\nb\n", "").all(|line| matches!(line, Cow::Borrowed(_))));
+}
+
+// `indent` and `indent_lines` should treat empty lines the same way as the io
+// writer
+#[cfg(feature = "std")]
+#[test]
+fn test_indent_lines_matches_io() {
+    use std::io::Write as _;
+
+    use indent_write::fmt::{indent, indent_lines};
+
+    for input in [
+        "",
+        "\n",
+        "Line 1",
+        "\n\nLine 1\n\n\nLine 2\n  \n",
+        "a\r\n\r\nb",
+    ] {
+        let mut io_writer = indent_write::io::IndentWriter::new("> ", Vec::new());
+        io_writer.write_all(input.as_bytes()).unwrap();
+
+        assert_eq!(
+            indent_lines(input, "> ").as_bytes(),
+            io_writer.get_ref(),
+            "input {:?}",
+            input
+        );
+        assert_eq!(indent(input, "> "), indent_lines(input, "> "));
+    }
+}
+
+// `indent_debug` should indent each line of the debug output, passing the
+// alternate flag through to select the pretty-printed form
+#[test]
+fn test_indent_debug() {
+    use indent_write::indentable::IndentableDebug;
+
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    struct Node {
+        name: &'static str,
+        children: Vec<Node>,
+    }
+
+    let tree = Node {
+        name: "root",
+        children: vec![Node {
+            name: "leaf",
+            children: vec![],
+        }],
+    };
+
+    let expected: String = format!("{:#?}", tree)
+        .lines()
+        .map(|line| format!("    {}", line))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    assert_eq!(format!("{:#}", tree.indent_debug("    ")), expected);
+    assert_eq!(
+        tree.indent_debug("    ").to_string(),
+        format!("    {:?}", tree)
+    );
+
+    // Unsized types work through the reference
+    assert_eq!(
+        format!("{:#}", [1, 2][..].indent_debug("> ")),
+        "> [\n>     1,\n>     2,\n> ]"
+    );
+}
+
+// `display` should indent every line of a nested `Display`, even when it's
+// written a piece at a time
+#[test]
+fn test_display() {
+    struct Node {
+        name: &'static str,
+        children: Vec<Node>,
+    }
+
+    impl fmt::Display for Node {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.name)?;
+
+            let mut writer = IndentWriter::new("  ", f);
+            self.children.iter().try_for_each(|child| {
+                writer.write_char('\n')?;
+                writer.display(child)
+            })
+        }
+    }
+
+    let tree = Node {
+        name: "root",
+        children: vec![
+            Node {
+                name: "a",
+                children: vec![Node {
+                    name: "b",
+                    children: vec![],
+                }],
+            },
+            Node {
+                name: "c",
+                children: vec![],
+            },
+        ],
+    };
+
+    let mut dest = String::new();
+    {
+        let mut writer = IndentWriter::new("| ", OneByteAtATime(&mut dest));
+        writer.display(&tree).unwrap();
+        writer.display("\n").unwrap();
+    }
+    assert_eq!(dest, "| root\n|   a\n|     b\n|   c\n");
+}
+
+#[test]
+fn test_indent_level() {
+    let mut dest = String::new();
+    {
+        let mut writer = IndentWriter::new("\t", OneByteAtATime(&mut dest));
+        assert_eq!(writer.indent_level(), 1);
+
+        writeln!(writer, "{}", "Level 1").unwrap();
+        writer.inc();
+        writer.inc();
+        assert_eq!(writer.indent_level(), 3);
+        write!(writer, "Level 3\n\nLevel 3 ").unwrap();
+
+        // Changes take effect on the next line
+        writer.dec();
+        assert_eq!(writer.indent_level(), 2);
+        writeln!(writer, "continued").unwrap();
+        writeln!(writer, "Level 2").unwrap();
+
+        writer.reset();
+        writer.dec();
+        assert_eq!(writer.indent_level(), 0);
+        writeln!(writer, "Level 0").unwrap();
+    }
+    assert_eq!(
+        dest,
+        "\tLevel 1\n\t\t\tLevel 3\n\n\t\t\tLevel 3 continued\n\t\tLevel 2\nLevel 0\n"
+    );
+}
+
+// An empty line written on its own, including straight after a level change,
+// is never indented, and the line after it always is
+#[test]
+fn test_lone_newline_write() {
+    let mut writer = IndentWriter::new("  ", String::new());
+    writer.write_str("a").unwrap();
+    writer.write_str("\n").unwrap();
+    writer.inc();
+    writer.write_str("\n").unwrap();
+    writer.write_str("x").unwrap();
+    writer.write_str("\n").unwrap();
+    writer.dec();
+    writer.write_str("\n").unwrap();
+    writer.write_str("y").unwrap();
+    assert_eq!(writer.get_ref(), "  a\n\n    x\n\n  y");
+}
+
+#[test]
+fn test_empty_indent() {
+    let mut dest = String::new();
+    {
+        let mut writer = IndentWriter::new("", OneByteAtATime(&mut dest));
+        writer.dec();
+        writer.dec();
+        assert_eq!(writer.indent_level(), 0);
+        write!(writer, "Line 1\n\n").unwrap();
+
+        writer.inc();
+        writer.inc();
+        write!(writer, "Line 2\n").unwrap();
+    }
+    assert_eq!(dest, "Line 1\n\nLine 2\n");
+}
+
+#[test]
+fn test_indent_empty_lines() {
+    let mut dest = String::new();
+    {
+        let mut writer = IndentWriter::new("> ", OneByteAtATime(&mut dest));
+        writer.set_indent_empty_lines(true);
+        write!(writer, "\nLine 1\n\n\nLine 2\n").unwrap();
+
+        writer.set_indent_empty_lines(false);
+        write!(writer, "\nLine 3\n").unwrap();
+    }
+    assert_eq!(dest, "> \n> Line 1\n> \n> \n> Line 2\n\n> Line 3\n");
+}
+
+// The fmt and io writers should produce identical output for the same input
+#[cfg(feature = "std")]
+#[test]
+fn test_indent_empty_lines_matches_io() {
+    use std::io::Write as _;
+
+    const INPUT: &str = "\nLine 1\n\nLine 2\n  \n\n";
+
+    for chunk in [1, 3, INPUT.len()] {
+        let mut fmt_writer = IndentWriter::new("\t", String::new());
+        fmt_writer.set_indent_empty_lines(true);
+
+        let mut io_writer = indent_write::io::IndentWriter::new("\t", Vec::new());
+        io_writer.set_indent_empty_lines(true);
+
+        for part in INPUT.as_bytes().chunks(chunk) {
+            let part = std::str::from_utf8(part).unwrap();
+            fmt_writer.write_str(part).unwrap();
+            io_writer.write_all(part.as_bytes()).unwrap();
+        }
+
+        assert_eq!(fmt_writer.get_ref().as_bytes(), io_writer.get_ref());
+        assert_eq!(
+            fmt_writer.get_ref(),
+            "\t\n\tLine 1\n\t\n\tLine 2\n\t  \n\t\n"
+        );
+    }
+}
+
+// `Indentable` is implemented for every `Display` type, including borrowed
+// strings, owned strings, and user-defined types
+#[test]
+fn test_indentable_blanket_impl() {
+    use indent_write::indentable::Indentable;
+
+    struct Pair(u8, u8);
+
+    impl fmt::Display for Pair {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}\n{}", self.0, self.1)
+        }
+    }
+
+    assert_eq!("a\n\nb".indented("  ").to_string(), "  a\n\n  b");
+    assert_eq!(String::from("a\nb").indented("  ").to_string(), "  a\n  b");
+    assert_eq!(Pair(1, 2).indented("> ").to_string(), "> 1\n> 2");
+
+    // References to `Display` types are `Display` too
+    let pair = Pair(3, 4);
+    assert_eq!((&pair).indented("- ").to_string(), "- 3\n- 4");
+}
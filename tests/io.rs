@@ -1,9 +1,18 @@
 #![cfg(feature = "std")]
+// These tests deliberately route literals through format strings, to exercise
+// the writers the way real formatting code does.
+#![allow(
+    clippy::redundant_static_lifetimes,
+    clippy::write_literal,
+    clippy::write_with_newline,
+    clippy::writeln_empty_string
+)]
 
-use std::io::{self, Write};
+use std::io::{self, Cursor, IoSlice, Seek, SeekFrom, Write};
 use std::str::from_utf8;
 
-use indent_write::io::IndentWriter;
+use indent_write::indented;
+use indent_write::io::{IndentWriter, LinePhase};
 
 // This is a wrapper for io::Write that only writes one byte at a time, to test
 // the invariants of IndentableWrite
@@ -23,6 +32,57 @@ impl<W: Write> Write for OneByteAtATime<W> {
     }
 }
 
+// This is a wrapper for io::Write that fails once its budget of bytes is used
+// up, to test that state is kept consistent across errors. The budget can be
+// refilled to resume writing.
+#[derive(Debug, Clone)]
+struct FailAfter<W> {
+    writer: W,
+    budget: usize,
+}
+
+impl<W: Write> Write for FailAfter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.budget {
+            0 => Err(io::Error::other("budget exhausted")),
+            budget => {
+                let n = self.writer.write(&buf[..buf.len().min(budget)])?;
+                self.budget -= n;
+                Ok(n)
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<W: Seek> Seek for FailAfter<W> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.writer.seek(pos)
+    }
+}
+
+// This is an in-memory writer which records how much had been written each
+// time it was flushed.
+#[derive(Debug, Clone, Default)]
+struct FlushLog {
+    written: Vec<u8>,
+    flushed_at: Vec<usize>,
+}
+
+impl Write for FlushLog {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.written.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flushed_at.push(self.written.len());
+        Ok(())
+    }
+}
+
 const CONTENT: &'static [&'static str] = &["\t😀 😀 😀", "\t\t😀 😀 😀", "\t😀 😀 😀"];
 
 // Using a function to wrap a writer, run a standard test and check against expected
@@ -153,3 +213,2813 @@ fn test_partial_writes_combined() {
         Ok("    Hello, World\n    😀 😀 😀\n    😀 😀 😀\n")
     );
 }
+
+#[test]
+fn test_set_indent_level() {
+    let mut direct = IndentWriter::new("  ", Vec::new());
+    let mut stepped = IndentWriter::new("  ", Vec::new());
+
+    for level in [3, 0, 2, 5, 1] {
+        direct.set_indent_level(level);
+
+        stepped.reset();
+        for _ in 0..level {
+            stepped.inc();
+        }
+
+        writeln!(direct, "Level {}\n\nDone", level).unwrap();
+        writeln!(stepped, "Level {}\n\nDone", level).unwrap();
+    }
+
+    assert_eq!(direct.get_ref(), stepped.get_ref());
+    assert_eq!(
+        from_utf8(direct.get_ref()),
+        Ok("      Level 3\n\n      Done\n\
+            Level 0\n\nDone\n    \
+            Level 2\n\n    Done\n          \
+            Level 5\n\n          Done\n  \
+            Level 1\n\n  Done\n")
+    );
+}
+
+#[test]
+fn test_set_indent_level_mid_line() {
+    let mut dest = Vec::new();
+    {
+        let writer = OneByteAtATime(&mut dest);
+        let mut writer = IndentWriter::new("\t", writer);
+        write!(writer, "Hello, ").unwrap();
+        writer.set_indent_level(2);
+        write!(writer, "World\n{}\n", "😀 😀 😀").unwrap();
+        writer.dec();
+        writer.dec();
+        writer.dec();
+        write!(writer, "Goodbye\n").unwrap();
+    }
+    assert_eq!(
+        from_utf8(&dest),
+        Ok("\tHello, World\n\t\t😀 😀 😀\nGoodbye\n")
+    );
+}
+
+#[test]
+fn test_indent_level() {
+    let mut writer = IndentWriter::new("\t", Vec::new());
+    assert_eq!(writer.indent_level(), 1);
+
+    writer.inc();
+    writer.inc();
+    assert_eq!(writer.indent_level(), 3);
+
+    writer.dec();
+    assert_eq!(writer.indent_level(), 2);
+
+    writer.inc();
+    writer.dec();
+    writer.dec();
+    writer.dec();
+    assert_eq!(writer.indent_level(), 0);
+
+    // dec saturates at 0
+    writer.dec();
+    assert_eq!(writer.indent_level(), 0);
+
+    writer.inc();
+    assert_eq!(writer.indent_level(), 1);
+
+    writer.set_indent_level(7);
+    assert_eq!(writer.indent_level(), 7);
+
+    writer.reset();
+    assert_eq!(writer.indent_level(), 0);
+}
+
+#[test]
+fn test_with_indent_level() {
+    let mut direct = IndentWriter::with_indent_level("\t", 3, Vec::new());
+    let mut stepped = IndentWriter::new("\t", Vec::new());
+    stepped.inc();
+    stepped.inc();
+
+    assert_eq!(direct.indent_level(), 3);
+
+    for line in CONTENT {
+        writeln!(direct, "{}", line).unwrap();
+        writeln!(stepped, "{}", line).unwrap();
+    }
+
+    assert_eq!(direct.get_ref(), stepped.get_ref());
+
+    let mut unindented = IndentWriter::with_indent_level("\t", 0, Vec::new());
+    writeln!(unindented, "Line 1\n\nLine 2").unwrap();
+    assert_eq!(from_utf8(unindented.get_ref()), Ok("Line 1\n\nLine 2\n"));
+    assert_eq!(unindented.indent_level(), 0);
+
+    // Level 1 is the same as `new`
+    let mut level_one = IndentWriter::with_indent_level("\t", 1, Vec::new());
+    let mut new = IndentWriter::new("\t", Vec::new());
+    assert_eq!(level_one.indent_level(), new.indent_level());
+
+    writeln!(level_one, "Line 1\n\nLine 2").unwrap();
+    writeln!(new, "Line 1\n\nLine 2").unwrap();
+    assert_eq!(from_utf8(new.get_ref()), Ok("\tLine 1\n\n\tLine 2\n"));
+    assert_eq!(level_one.get_ref(), new.get_ref());
+}
+
+#[test]
+fn test_indent_guard() {
+    fn write_item(writer: &mut IndentWriter<Vec<u8>>, depth: u32) -> io::Result<()> {
+        writeln!(writer, "item {}", depth)?;
+        let mut writer = writer.indented();
+
+        if depth == 2 {
+            // Decrementing through the guard shouldn't compound with the
+            // guard's own restore
+            writer.dec();
+            writer.dec();
+            return Err(io::ErrorKind::Other.into());
+        }
+
+        write_item(&mut writer, depth + 1)
+    }
+
+    let mut writer = IndentWriter::new("\t", Vec::new());
+    assert!(write_item(&mut writer, 0).is_err());
+    assert_eq!(writer.indent_level(), 1);
+
+    writeln!(writer, "done").unwrap();
+    assert_eq!(
+        from_utf8(writer.get_ref()),
+        Ok("\titem 0\n\t\titem 1\n\t\t\titem 2\n\tdone\n")
+    );
+}
+
+#[test]
+fn test_at_level() {
+    let mut writer = IndentWriter::new("-", Vec::new());
+    writer.inc();
+    {
+        let mut outer = writer.at_level(5);
+        writeln!(outer, "a").unwrap();
+        {
+            let mut inner = outer.at_level(0);
+            writeln!(inner, "b").unwrap();
+
+            // Changes through the guard are undone too
+            inner.inc_by(3);
+            writeln!(inner, "c").unwrap();
+        }
+        assert_eq!(outer.indent_level(), 5);
+        writeln!(outer, "d").unwrap();
+    }
+    assert_eq!(writer.indent_level(), 2);
+    writeln!(writer, "e").unwrap();
+
+    assert_eq!(
+        from_utf8(writer.get_ref()),
+        Ok("-----a\nb\n---c\n-----d\n--e\n")
+    );
+}
+
+#[test]
+fn test_indented_macro() {
+    struct Node {
+        name: &'static str,
+        children: Vec<Node>,
+    }
+
+    fn write_node(writer: &mut IndentWriter<Vec<u8>>, node: &Node) -> io::Result<()> {
+        if node.children.is_empty() {
+            return writeln!(writer, "<{}/>", node.name);
+        }
+
+        writeln!(writer, "<{}>", node.name)?;
+        indented!(writer => {
+            for child in &node.children {
+                if child.name == "bad" {
+                    return Err(io::ErrorKind::InvalidData.into());
+                }
+
+                write_node(&mut writer, child)?;
+            }
+        });
+        writeln!(writer, "</{}>", node.name)
+    }
+
+    let tree = Node {
+        name: "root",
+        children: vec![
+            Node {
+                name: "a",
+                children: vec![Node {
+                    name: "b",
+                    children: vec![],
+                }],
+            },
+            Node {
+                name: "c",
+                children: vec![],
+            },
+        ],
+    };
+
+    let mut writer = IndentWriter::with_indent_level("  ", 0, Vec::new());
+    write_node(&mut writer, &tree).unwrap();
+    assert_eq!(
+        from_utf8(writer.get_ref()),
+        Ok("<root>\n  <a>\n    <b/>\n  </a>\n  <c/>\n</root>\n")
+    );
+
+    // The level is restored on early return from within the block
+    let tree = Node {
+        name: "root",
+        children: vec![Node {
+            name: "a",
+            children: vec![Node {
+                name: "bad",
+                children: vec![],
+            }],
+        }],
+    };
+
+    let mut writer = IndentWriter::with_indent_level("  ", 0, Vec::new());
+    assert!(write_node(&mut writer, &tree).is_err());
+    assert_eq!(writer.indent_level(), 0);
+}
+
+#[test]
+fn test_crlf_aware() {
+    let mut writer = IndentWriter::new("\t", Vec::new());
+    write!(writer, "a\r\n\r\nb\r\n").unwrap();
+    assert_eq!(from_utf8(writer.get_ref()), Ok("\ta\r\n\t\r\n\tb\r\n"));
+
+    let mut writer = IndentWriter::new("\t", Vec::new());
+    writer.set_crlf_aware(true);
+    write!(writer, "a\r\n\r\nb\r\n").unwrap();
+    write!(writer, "\rc\n\r\r\n").unwrap();
+    assert_eq!(
+        from_utf8(writer.get_ref()),
+        Ok("\ta\r\n\r\n\tb\r\n\t\rc\n\t\r\r\n")
+    );
+}
+
+#[test]
+fn test_partial_crlf_aware() {
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriter::new("\t", OneByteAtATime(&mut dest));
+        writer.set_crlf_aware(true);
+        let mut writer = OneByteAtATime(writer);
+        write!(writer, "a\r\n\r\nb\r\n").unwrap();
+        write!(writer, "\rc\n\r\r\n").unwrap();
+        write!(writer, "d\r").unwrap();
+        writer.flush().unwrap();
+    }
+    assert_eq!(
+        from_utf8(&dest),
+        Ok("\ta\r\n\r\n\tb\r\n\t\rc\n\t\r\r\n\td\r")
+    );
+}
+
+#[test]
+fn test_set_indent() {
+    let mut writer = IndentWriter::with_indent_level("\t", 2, Vec::new());
+    writeln!(writer, "Line 1").unwrap();
+    write!(writer, "Line 2 ").unwrap();
+    writer.set_indent("  ");
+    writeln!(writer, "continued").unwrap();
+    writer.dec();
+    writeln!(writer, "Line 3").unwrap();
+
+    assert_eq!(
+        from_utf8(writer.get_ref()),
+        Ok("\t\tLine 1\n\t\tLine 2 continued\n  Line 3\n")
+    );
+}
+
+#[test]
+fn test_set_indent_mid_indent() {
+    let mut writer = IndentWriter::new(
+        "----",
+        FailAfter {
+            writer: Vec::new(),
+            budget: 2,
+        },
+    );
+
+    // The indent is only partially written before the error
+    assert!(writer.write_all(b"Line 1\n").is_err());
+    writer.set_indent("++");
+    writer.inc();
+    assert_eq!(writer.indent_level(), 2);
+
+    // The remainder of the original indent is written first
+    writer.get_mut().budget = usize::MAX;
+    writer.write_all(b"Line 1\nLine 2\n").unwrap();
+
+    assert_eq!(
+        from_utf8(&writer.get_ref().writer),
+        Ok("----Line 1\n++++Line 2\n")
+    );
+}
+
+#[test]
+fn test_indent_empty_lines() {
+    let mut writer = IndentWriter::new("\t", Vec::new());
+    writer.set_indent_empty_lines(true);
+    write!(writer, "a\n\n\nb\n").unwrap();
+    assert_eq!(from_utf8(writer.get_ref()), Ok("\ta\n\t\n\t\n\tb\n"));
+
+    writer.set_crlf_aware(true);
+    write!(writer, "\r\nc\r\n").unwrap();
+    assert_eq!(
+        from_utf8(writer.get_ref()),
+        Ok("\ta\n\t\n\t\n\tb\n\t\r\n\tc\r\n")
+    );
+}
+
+#[test]
+fn test_partial_indent_empty_lines() {
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriter::new("  ", OneByteAtATime(&mut dest));
+        writer.set_indent_empty_lines(true);
+        let mut writer = OneByteAtATime(writer);
+        write!(writer, "a\n\n\nb\n").unwrap();
+    }
+    assert_eq!(from_utf8(&dest), Ok("  a\n  \n  \n  b\n"));
+}
+
+#[test]
+fn test_comment_prefix() {
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriter::new("    ", OneByteAtATime(&mut dest));
+        writer.set_comment_prefix(Some("// ".to_owned()));
+        assert_eq!(writer.comment_prefix(), Some("// "));
+
+        write!(writer, "{}\n{}\n{}\n", "Line 1", "Line 2", "Line 3").unwrap();
+
+        // Empty lines can be commented too, without trailing whitespace
+        write!(writer, "{}", "\n").unwrap();
+        writer.set_comment_blank_lines(true);
+        writer.inc();
+        write!(writer, "{}\n\n{}\n", "Para 1", "Para 2").unwrap();
+
+        writer.set_comment_prefix(None);
+        writer.dec();
+        write!(writer, "{}\n\n", "Code").unwrap();
+    }
+
+    assert_eq!(
+        from_utf8(&dest),
+        Ok(concat!(
+            "    // Line 1\n",
+            "    // Line 2\n",
+            "    // Line 3\n",
+            "\n",
+            "        // Para 1\n",
+            "        //\n",
+            "        // Para 2\n",
+            "    Code\n",
+            "\n",
+        ))
+    );
+}
+
+#[test]
+fn test_comment_prefix_wrap_at() {
+    use indent_write::io::IndentWriterBuilder;
+
+    let mut writer = IndentWriterBuilder::new("  ")
+        .comment_prefix(Some("# "))
+        .wrap_at(Some(16))
+        .build(Vec::new());
+
+    writeln!(writer, "The quick brown fox jumps").unwrap();
+
+    assert_eq!(
+        from_utf8(writer.get_ref()),
+        Ok("  # The quick\n  # brown fox\n  # jumps\n")
+    );
+}
+
+const WHITESPACE_CONTENT: &str = "a  \n \t \n\t b \t c\t\n  \n";
+
+#[test]
+fn test_trim_trailing_whitespace() {
+    let mut writer = IndentWriter::new("  ", Vec::new());
+    writer.set_trim_trailing_whitespace(true);
+    write!(writer, "{}", WHITESPACE_CONTENT).unwrap();
+    write!(writer, "d \r\n \r\n").unwrap();
+    assert_eq!(
+        from_utf8(writer.get_ref()),
+        Ok("  a\n\n  \t b \t c\n\n  d \r\n   \r\n")
+    );
+
+    let mut writer = IndentWriter::new("  ", Vec::new());
+    writer.set_trim_trailing_whitespace(true);
+    writer.set_crlf_aware(true);
+    write!(writer, "a \r\n \r\n\r b \r\r\n").unwrap();
+    assert_eq!(from_utf8(writer.get_ref()), Ok("  a\r\n\r\n  \r b\r\n"));
+}
+
+#[test]
+fn test_partial_trim_trailing_whitespace() {
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriter::new("  ", OneByteAtATime(&mut dest));
+        writer.set_trim_trailing_whitespace(true);
+        let mut writer = OneByteAtATime(writer);
+        write!(writer, "{}", WHITESPACE_CONTENT).unwrap();
+        write!(writer, "trailing  ").unwrap();
+        writer.flush().unwrap();
+    }
+    assert_eq!(from_utf8(&dest), Ok("  a\n\n  \t b \t c\n\n  trailing"));
+}
+
+#[test]
+fn test_bytes_written() {
+    let mut dest = Vec::new();
+    let bytes_written = {
+        let mut writer = IndentWriter::new("\t", OneByteAtATime(&mut dest));
+        writer.set_trim_trailing_whitespace(true);
+        assert_eq!(writer.bytes_written(), 0);
+
+        for line in CONTENT {
+            writeln!(writer, "{}", line).unwrap();
+        }
+        write!(writer, "{}trailing  ", WHITESPACE_CONTENT).unwrap();
+        writer.bytes_written()
+    };
+    assert_eq!(bytes_written, dest.len() as u64);
+
+    let mut writer = IndentWriter::new(
+        "----",
+        FailAfter {
+            writer: Vec::new(),
+            budget: 3,
+        },
+    );
+    assert!(writer.write_all(b"Line 1\n").is_err());
+    assert_eq!(writer.bytes_written(), 3);
+
+    writer.get_mut().budget = 8;
+    assert!(writer.write_all(b"Line 1\nLine 2\n").is_err());
+    assert_eq!(writer.bytes_written(), 11);
+    assert_eq!(writer.bytes_written(), writer.get_ref().writer.len() as u64);
+}
+
+#[test]
+fn test_lines_written() {
+    let mut writer = IndentWriter::new("\t", Vec::new());
+    write!(writer, "Line 1\n\n\nLine 2").unwrap();
+    assert_eq!(writer.lines_written(), 3);
+    write!(writer, " continued\n").unwrap();
+    assert_eq!(writer.lines_written(), 4);
+
+    let mut dest = Vec::new();
+    let lines_written = {
+        let mut writer = OneByteAtATime(IndentWriter::new("\t", OneByteAtATime(&mut dest)));
+        for line in CONTENT {
+            writeln!(writer, "{}", line).unwrap();
+        }
+        write!(writer, "\n\n").unwrap();
+        writer.0.lines_written()
+    };
+    assert_eq!(lines_written, 5);
+    assert_eq!(
+        lines_written,
+        dest.iter().filter(|&&b| b == b'\n').count() as u64
+    );
+}
+
+#[test]
+fn test_column() {
+    let mut dest = Vec::new();
+    let mut writer = IndentWriter::new("\t", OneByteAtATime(&mut dest));
+    assert_eq!((writer.column(), writer.column_chars()), (0, 0));
+
+    write!(writer, "ab").unwrap();
+    assert_eq!((writer.column(), writer.column_chars()), (3, 3));
+
+    write!(writer, " 😀").unwrap();
+    assert_eq!((writer.column(), writer.column_chars()), (8, 5));
+
+    write!(writer, "\n").unwrap();
+    assert_eq!((writer.column(), writer.column_chars()), (0, 0));
+
+    // The indent isn't written until there's content on the line
+    write!(writer, "\n").unwrap();
+    writer.inc();
+    assert_eq!((writer.column(), writer.column_chars()), (0, 0));
+
+    write!(writer, "😀😀\n😀 a").unwrap();
+    assert_eq!((writer.column(), writer.column_chars()), (8, 5));
+}
+
+#[cfg(feature = "unicode-width")]
+#[test]
+fn test_column_width() {
+    let mut dest = Vec::new();
+    let mut writer = IndentWriter::new("\t", OneByteAtATime(&mut dest));
+    assert_eq!(writer.column_width(), 0);
+
+    write!(writer, "ab").unwrap();
+    assert_eq!((writer.column_chars(), writer.column_width()), (3, 3));
+
+    // Each emoji is two columns wide, even when written a byte at a time
+    write!(writer, "{}", "😀").unwrap();
+    assert_eq!((writer.column_chars(), writer.column_width()), (4, 5));
+
+    // Combining characters take up no space
+    write!(writer, " e\u{301}中\n").unwrap();
+    assert_eq!(writer.column_width(), 0);
+    write!(writer, "e\u{301}中").unwrap();
+    assert_eq!((writer.column_chars(), writer.column_width()), (4, 4));
+
+    // Tabs are expanded, and continuation lines aligned, by display width
+    let mut writer = IndentWriter::new("", Vec::new());
+    writer.set_expand_tabs(Some(4));
+    write!(writer, "😀😀\tx").unwrap();
+    assert_eq!(from_utf8(writer.get_ref()), Ok("😀😀    x"));
+
+    let mut writer = IndentWriter::new("", Vec::new());
+    writer.set_continuation_align(true);
+    let (label, body) = ("中文: ", "a\nb");
+    write!(writer, "{}", label).unwrap();
+    writer.write_aligned(body.as_bytes()).unwrap();
+    assert_eq!(from_utf8(writer.get_ref()), Ok("中文: a\n      b"));
+
+    // Including when the line number gutter is wide
+    let mut writer = IndentWriter::new("", Vec::new());
+    writer.set_continuation_align(true);
+    writer.set_line_numbers(Some(indent_write::io::LineNumberConfig {
+        start: 1,
+        min_width: 1,
+        separator: "中 ",
+    }));
+    write!(writer, "x: ").unwrap();
+    writer.write_aligned(body.as_bytes()).unwrap();
+    assert_eq!(from_utf8(writer.get_ref()), Ok("1中 x: a\n2中    b"));
+}
+
+#[test]
+fn test_skip_initial() {
+    let mut writer = IndentWriter::new_skip_initial("\t", Vec::new());
+    write!(writer, "a\nb\n").unwrap();
+    assert_eq!(from_utf8(writer.get_ref()), Ok("a\n\tb\n"));
+
+    // Changing the level doesn't cause the first line to be indented
+    let mut writer = IndentWriter::new_skip_initial("\t", Vec::new());
+    writer.reset();
+    writer.inc();
+    writer.inc();
+    write!(writer, "a\nb\n").unwrap();
+    assert_eq!(from_utf8(writer.get_ref()), Ok("a\n\t\tb\n"));
+}
+
+#[test]
+fn test_first_line_prefix() {
+    let mut writer = IndentWriter::new("  ", Vec::new());
+    writer.set_first_line_prefix(Some("- "));
+    write!(writer, "one line\n").unwrap();
+    write!(writer, "after\n").unwrap();
+    assert_eq!(from_utf8(writer.get_ref()), Ok("- one line\n  after\n"));
+
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriter::new("  ", OneByteAtATime(&mut dest));
+        writer.set_first_line_prefix(Some("1. "));
+        let mut writer = OneByteAtATime(writer);
+        write!(writer, "\nmulti\n\nline\n").unwrap();
+
+        writer.0.set_first_line_prefix(Some(""));
+        write!(writer, "no prefix\n").unwrap();
+
+        writer.0.set_first_line_prefix(Some("* "));
+        writer.0.set_first_line_prefix(None);
+        write!(writer, "end\n").unwrap();
+    }
+    assert_eq!(
+        from_utf8(&dest),
+        Ok("\n1. multi\n\n  line\nno prefix\n  end\n")
+    );
+}
+
+#[test]
+fn test_align_continuation() {
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriter::new("  ", OneByteAtATime(&mut dest));
+        writer.set_align_continuation(true);
+
+        writer.set_first_line_prefix(Some("name: "));
+        let mut writer = OneByteAtATime(writer);
+        write!(writer, "first\nsecond\n\nlast\n").unwrap();
+
+        // The width is counted in chars, not bytes
+        writer.0.set_first_line_prefix(Some("→ "));
+        write!(writer, "a\nb\n").unwrap();
+
+        // Changing the level discards the aligned indent
+        writer.0.inc();
+        write!(writer, "nested\n").unwrap();
+    }
+    assert_eq!(
+        from_utf8(&dest),
+        Ok("name: first\n      second\n\n      last\n→ a\n  b\n    nested\n")
+    );
+
+    // Without the option, the continuation lines use the normal indent
+    use indent_write::io::IndentWriterBuilder;
+
+    let mut writer = IndentWriterBuilder::new("  ")
+        .first_line_prefix(Some("name: "))
+        .align_continuation(false)
+        .build(Vec::new());
+    write!(writer, "first\nsecond\n").unwrap();
+    assert_eq!(from_utf8(writer.get_ref()), Ok("name: first\n  second\n"));
+}
+
+#[test]
+fn test_with_levels() {
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriter::with_levels(&["│ ", "┆ "], OneByteAtATime(&mut dest));
+        assert_eq!(writer.indent(), Some("│ "));
+
+        for _ in 0..3 {
+            write!(writer, "Level {}\n\n", writer.indent_level()).unwrap();
+            writer.inc();
+        }
+        writer.dec_by(2);
+        write!(writer, "Level 2\n").unwrap();
+
+        writer.set_indent("  ");
+        write!(writer, "Plain\n").unwrap();
+    }
+    assert_eq!(
+        from_utf8(&dest),
+        Ok("│ Level 1\n\n│ ┆ Level 2\n\n│ ┆ │ Level 3\n\n│ ┆ Level 2\n    Plain\n")
+    );
+
+    let mut writer = IndentWriter::with_levels(&[], Vec::new());
+    writer.inc();
+    write!(writer, "Not indented\n").unwrap();
+    assert_eq!(from_utf8(writer.get_ref()), Ok("Not indented\n"));
+}
+
+#[test]
+fn test_collapse_blank_lines() {
+    use indent_write::io::IndentWriterBuilder;
+
+    let mut writer = IndentWriter::new("  ", Vec::new());
+    writer.set_collapse_blank_lines(Some(1));
+    write!(writer, "a\n\n\n\n\nb\n").unwrap();
+    assert_eq!(from_utf8(writer.get_ref()), Ok("  a\n\n  b\n"));
+
+    // Runs of blank lines split across writes are counted together
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriter::new("  ", OneByteAtATime(&mut dest));
+        writer.set_collapse_blank_lines(Some(2));
+        let mut writer = OneByteAtATime(writer);
+        write!(writer, "\n\n\na\n\n\n\n").unwrap();
+        write!(writer, "\n\nb\n\nc\n").unwrap();
+    }
+    assert_eq!(from_utf8(&dest), Ok("\n\n  a\n\n\n  b\n\n  c\n"));
+
+    // Blank lines which are indented, or which end with "\r\n", are collapsed
+    // too, as are lines which are only whitespace if it's trimmed
+    let configs = [
+        ("a\n\n\n\nb\n", "> a\n> \n> b\n", true, false),
+        ("a\r\n\r\n\r\nb\r\n", "> a\r\n\r\n> b\r\n", false, false),
+        ("a\n  \n\t\n\nb\n", "> a\n\n> b\n", false, true),
+    ];
+
+    for (input, expected, indent_empty_lines, trim) in configs {
+        let mut dest = Vec::new();
+        {
+            let writer = IndentWriterBuilder::new("> ")
+                .collapse_blank_lines(Some(1))
+                .indent_empty_lines(indent_empty_lines)
+                .crlf_aware(true)
+                .trim_trailing_whitespace(trim)
+                .build(OneByteAtATime(&mut dest));
+            let mut writer = OneByteAtATime(writer);
+            write!(writer, "{}", input).unwrap();
+        }
+        assert_eq!(from_utf8(&dest), Ok(expected), "input {:?}", input);
+    }
+
+    // A limit of 0 removes blank lines entirely
+    let mut writer = IndentWriter::new("  ", Vec::new());
+    writer.set_collapse_blank_lines(Some(0));
+    write!(writer, "{}", PARAGRAPHS).unwrap();
+    assert_eq!(
+        from_utf8(writer.get_ref()),
+        Ok("  Para 1\n  line 2\n  line 3\n  Para 2\n  line 2\n")
+    );
+}
+
+#[test]
+fn test_effective_indent() {
+    use indent_write::io::EffectiveIndent;
+
+    let mut dest = Vec::new();
+    let mut indent1 = IndentWriter::new("\t", &mut dest);
+    assert_eq!(indent1.effective_indent_len(), 1);
+
+    let mut indent2 = IndentWriter::new("  ", &mut indent1);
+    indent2.inc();
+    assert_eq!(indent2.effective_indent_len(), 5);
+
+    indent2.reset();
+    assert_eq!(indent2.effective_indent_len(), 1);
+
+    indent2.get_mut().set_first_line_prefix(Some("- "));
+    assert_eq!(indent2.effective_indent_len(), 2);
+
+    writeln!(indent2, "Line 1").unwrap();
+    indent2.inc();
+    writeln!(indent2, "Line 2").unwrap();
+    assert_eq!(indent2.effective_indent_len(), 3);
+
+    drop(indent2);
+    assert_eq!(from_utf8(&dest), Ok("- Line 1\n\t  Line 2\n"));
+}
+
+#[test]
+fn test_align_to_tab_stops() {
+    use indent_write::io::LineNumberConfig;
+
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriter::new("\t", OneByteAtATime(&mut dest));
+        writer.set_align_to_tab_stops(Some(4));
+        writer.set_line_numbers(Some(LineNumberConfig {
+            start: 98,
+            min_width: 1,
+            separator: " ",
+        }));
+        let mut writer = OneByteAtATime(writer);
+
+        write!(writer, "a\n").unwrap();
+        writer.0.inc();
+        write!(writer, "b\nc\n").unwrap();
+
+        writer.0.set_guide(Some("|"));
+        write!(writer, "d\n").unwrap();
+
+        writer.0.set_line_numbers(None);
+        write!(writer, "e\n").unwrap();
+
+        writer.0.set_align_to_tab_stops(None);
+        write!(writer, "f\n").unwrap();
+    }
+    assert_eq!(
+        from_utf8(&dest),
+        Ok("98  a\n99      b\n100         c\n101 |   d\n|   e\n|\tf\n")
+    );
+}
+
+#[test]
+fn test_newline() {
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriter::new("  ", OneByteAtATime(&mut dest));
+        writer.newline().unwrap();
+        write!(writer, "Line 1").unwrap();
+        writer.newline().unwrap();
+        writer.inc();
+        writer.set_trim_trailing_whitespace(true);
+        write!(writer, "Line 2 ").unwrap();
+        writer.newline().unwrap();
+        writer.newline().unwrap();
+        write!(writer, "Line 3").unwrap();
+        assert_eq!(writer.line_phase(), LinePhase::MidLine);
+        writer.newline().unwrap();
+        assert_eq!(writer.line_phase(), LinePhase::NeedIndent);
+    }
+    assert_eq!(
+        from_utf8(&dest),
+        Ok("\n  Line 1\n    Line 2\n\n    Line 3\n")
+    );
+}
+
+#[test]
+fn test_write_fmt_indented() {
+    let (a, b) = ("Line 1\n\nLine 2", 3);
+
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriter::new("> ", OneByteAtATime(&mut dest));
+        writer
+            .write_fmt_indented(format_args!("{}\n{}\n", a, b))
+            .unwrap();
+        writer.inc();
+        writer.write_fmt_indented(format_args!("{:>3}", b)).unwrap();
+    }
+    assert_eq!(from_utf8(&dest), Ok("> Line 1\n\n> Line 2\n> 3\n> >   3"));
+}
+
+#[test]
+fn test_ansi_aware() {
+    const RED: &str = "\x1b[31m";
+    const RESET: &str = "\x1b[0m";
+
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriter::new("\t", OneByteAtATime(&mut dest));
+        writer.set_ansi_aware(true);
+        writer.set_expand_tabs(Some(4));
+        writer.set_guide(Some(RED));
+        writer.inc();
+        let mut writer = OneByteAtATime(writer);
+
+        // The indent and escape sequences take up 1 column, so the tab is
+        // expanded to 3 spaces
+        write!(writer, "{}{}{}\tb", RED, RESET, RESET).unwrap();
+        assert_eq!(writer.0.column_chars(), 5);
+        assert_eq!(writer.0.column(), 23);
+
+        // A lone ESC only hides the byte after it
+        write!(writer, "\n\x1bXab\t{}c{}\n", RED, RESET).unwrap();
+        assert_eq!(writer.0.column_chars(), 0);
+    }
+    assert_eq!(
+        from_utf8(&dest),
+        Ok(format!(
+            "{red}\t{red}{reset}{reset}   b\n{red}\t\x1bXab {red}c{reset}\n",
+            red = RED,
+            reset = RESET
+        )
+        .as_str())
+    );
+
+    // Without it, escape sequences count towards the column
+    let mut writer = IndentWriter::new("", Vec::new());
+    write!(writer, "{}a", RED).unwrap();
+    assert_eq!(writer.column_chars(), 6);
+}
+
+#[test]
+fn test_treat_whitespace_only_as_blank() {
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriter::new("> ", OneByteAtATime(&mut dest));
+        writer.set_treat_whitespace_only_as_blank(true);
+        writer.set_crlf_aware(true);
+        let mut writer = OneByteAtATime(writer);
+        write!(writer, "a\n \t\n\n  b  \n\t\r\n\tc\r\n").unwrap();
+    }
+    assert_eq!(from_utf8(&dest), Ok("> a\n \t\n\n>   b  \n\t\r\n> \tc\r\n"));
+
+    // Whitespace-only lines count as blank lines when collapsing them
+    let mut writer = IndentWriter::new("> ", Vec::new());
+    writer.set_treat_whitespace_only_as_blank(true);
+    writer.set_collapse_blank_lines(Some(1));
+    write!(writer, "a\n  \n\n \nb\n").unwrap();
+    assert_eq!(from_utf8(writer.get_ref()), Ok("> a\n  \n> b\n"));
+
+    // Whitespace at the end of the output is ended with a newline by
+    // `finish`
+    let mut writer = IndentWriter::new("> ", Vec::new());
+    writer.set_treat_whitespace_only_as_blank(true);
+    write!(writer, "a\n  ").unwrap();
+    assert_eq!(writer.finish().unwrap(), b"> a\n  \n");
+}
+
+#[test]
+fn test_try_inc() {
+    let mut writer = IndentWriter::new("  ", Vec::new());
+    writer.set_max_level(Some(3));
+
+    writer.try_inc().unwrap();
+    writer.try_inc().unwrap();
+    let err = writer.try_inc().unwrap_err();
+
+    assert_eq!(err.max_level(), 3);
+    assert_eq!(
+        err.to_string(),
+        "indent level can't exceed the maximum of 3"
+    );
+
+    // The level is left unchanged
+    write!(writer, "Line\n").unwrap();
+    assert_eq!(from_utf8(writer.get_ref()), Ok("      Line\n"));
+
+    writer.set_max_level(None);
+    writer.set_indent_level(u16::MAX - 1);
+    writer.try_inc().unwrap();
+    assert_eq!(writer.try_inc().unwrap_err().max_level(), u16::MAX);
+}
+
+// Nested writers don't duplicate each other's indents; each one stores only
+// its own part of the total indent.
+#[test]
+fn test_nested_indent_storage() {
+    use indent_write::io::EffectiveIndent;
+
+    let mut dest = Vec::new();
+    let mut outer = IndentWriter::with_indent_level("\t", 2, &mut dest);
+    let mut inner = IndentWriter::new("- ", &mut outer);
+
+    assert_eq!(inner.current_indent(), b"- ");
+    assert_eq!(inner.get_ref().current_indent(), b"\t\t");
+    assert_eq!(inner.effective_indent_len(), 4);
+
+    write!(inner, "Line 1\nLine 2\n").unwrap();
+    drop(inner);
+    assert_eq!(from_utf8(&dest), Ok("\t\t- Line 1\n\t\t- Line 2\n"));
+}
+
+#[test]
+fn test_reserve_levels() {
+    let mut writer = IndentWriter::new("->", Vec::new());
+    writer.reserve_levels(20);
+
+    // The indent is unchanged, but it has room to grow without being moved
+    assert_eq!(writer.current_indent(), b"->");
+    let indent = writer.current_indent().as_ptr();
+
+    for _ in 0..20 {
+        writer.inc();
+        assert_eq!(writer.current_indent().as_ptr(), indent);
+    }
+
+    writer.reset();
+    writer.inc_by(2);
+    write!(writer, "Line\n").unwrap();
+    assert_eq!(from_utf8(writer.get_ref()), Ok("->->Line\n"));
+}
+
+#[test]
+fn test_write_joined() {
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriter::new("  ", OneByteAtATime(&mut dest));
+        writer.write_joined(["a", "b\nc", "", "d"], b",\n").unwrap();
+        writer
+            .write_joined(Vec::<String>::new(), b"unused")
+            .unwrap();
+        writer.write_joined([String::from("!")], b"unused").unwrap();
+        writer.write_joined([&b" e"[..], b"f"], b" |").unwrap();
+    }
+    assert_eq!(from_utf8(&dest), Ok("  a,\n  b\n  c,\n  ,\n  d! e |f"));
+}
+
+#[test]
+fn test_continuation_align() {
+    use indent_write::io::LineNumberConfig;
+
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriter::new("  ", OneByteAtATime(&mut dest));
+        writer.set_continuation_align(true);
+        writer.inc();
+
+        // Literal arguments are inlined into the format string by `write!`,
+        // so the values are written from variables.
+        let (name, ab, cd) = ("first\nsecond\n\nthird", "a\nb", "c\nd");
+        write!(writer, "name: {}\n", name).unwrap();
+        write!(writer, "{}\n", "no\nalignment").unwrap();
+        write!(writer, "→ {}, {}\n", ab, cd).unwrap();
+
+        writer.set_line_numbers(Some(LineNumberConfig {
+            start: 9,
+            min_width: 1,
+            separator: " ",
+        }));
+        let (n, xy) = (1, "x\ny");
+        write!(writer, "{}: {}\n", n, xy).unwrap();
+    }
+    assert_eq!(
+        from_utf8(&dest),
+        Ok(concat!(
+            "    name: first\n",
+            "          second\n",
+            "\n",
+            "          third\n",
+            "    no\n",
+            "    alignment\n",
+            "    → a\n",
+            "      b, c\n",
+            "         d\n",
+            "9     1: x\n",
+            "10       y\n",
+        ))
+    );
+
+    // Only formatted pieces are aligned, so `write_all` behaves the same as
+    // repeated calls to `write`
+    let mut all = IndentWriter::new("  ", Vec::new());
+    let mut each = IndentWriter::new("  ", OneByteAtATime(Vec::new()));
+    all.set_continuation_align(true);
+    each.set_continuation_align(true);
+
+    for chunk in [&b"name: "[..], b"first\nsecond\n"] {
+        all.write_all(chunk).unwrap();
+
+        let mut chunk = chunk;
+        while !chunk.is_empty() {
+            chunk = &chunk[each.write(chunk).unwrap()..];
+        }
+    }
+
+    assert_eq!(from_utf8(all.get_ref()), Ok("  name: first\n  second\n"));
+    assert_eq!(all.get_ref(), &each.get_ref().0);
+}
+
+#[test]
+fn test_tee() {
+    use indent_write::io::{Tee, TeeIndentWriter};
+
+    const EXPECTED: &str = "  Para 1\n  line 2\n  line 3\n\n\n  Para 2\n  line 2\n";
+
+    // Both writers get the same content, even if they accept different
+    // amounts at a time
+    let mut first = Vec::new();
+    let mut second = Vec::new();
+    {
+        let tee = Tee::new(OneByteAtATime(&mut first), &mut second);
+        let mut writer = TeeIndentWriter::new("  ", tee);
+        write!(writer, "{}", PARAGRAPHS).unwrap();
+        writer.flush().unwrap();
+    }
+    assert_eq!(from_utf8(&first), Ok(EXPECTED));
+    assert_eq!(first, second);
+
+    let mut first = Vec::new();
+    let mut second = Vec::new();
+    {
+        let tee = Tee::new(&mut first, OneByteAtATime(&mut second));
+        let mut writer = TeeIndentWriter::new("  ", tee);
+        write!(writer, "{}", PARAGRAPHS).unwrap();
+    }
+    assert_eq!(from_utf8(&second), Ok(EXPECTED));
+    assert_eq!(first, second);
+
+    // An error from the first writer is returned
+    let tee = Tee::new(
+        FailAfter {
+            writer: Vec::new(),
+            budget: 4,
+        },
+        Vec::new(),
+    );
+    let mut writer = TeeIndentWriter::new("  ", tee);
+    assert!(writer.write_all(b"Line 1\n").is_err());
+    assert_eq!(writer.get_ref().get_ref().0.writer, b"  Li");
+    assert_eq!(writer.get_ref().get_ref().1, b"  Li");
+
+    // An error from the second writer is returned by the next write or
+    // flush, and retrying never sends anything to the first writer twice
+    let tee = Tee::new(
+        Vec::new(),
+        FailAfter {
+            writer: Vec::new(),
+            budget: 4,
+        },
+    );
+    let mut writer = TeeIndentWriter::new("  ", tee);
+    writer.write_all(b"Line 1\n").unwrap();
+    assert!(writer.write_all(b"Line 2\n").is_err());
+    assert!(writer.flush().is_err());
+    assert_eq!(writer.get_ref().get_ref().0, b"  Line 1\n");
+    assert_eq!(writer.get_ref().get_ref().1.writer, b"  Li");
+
+    writer.get_mut().get_mut().1.budget = usize::MAX;
+    writer.write_all(b"Line 2\n").unwrap();
+    writer.flush().unwrap();
+
+    let (first, second) = writer.into_inner().into_inner().unwrap();
+    assert_eq!(from_utf8(&first), Ok("  Line 1\n  Line 2\n"));
+    assert_eq!(first, second.writer);
+
+    // If the second writer fails on the final write, extracting the writers
+    // catches it up, or returns the error if it fails again
+    let tee = Tee::new(
+        Vec::new(),
+        FailAfter {
+            writer: Vec::new(),
+            budget: 4,
+        },
+    );
+    let mut writer = TeeIndentWriter::new("  ", tee);
+    writer.write_all(b"Line 1\n").unwrap();
+    assert!(writer.into_inner().into_inner().is_err());
+
+    let tee = Tee::new(
+        Vec::new(),
+        FailAfter {
+            writer: Vec::new(),
+            budget: 4,
+        },
+    );
+    let mut writer = TeeIndentWriter::new("  ", tee);
+    writer.write_all(b"Line 1\n").unwrap();
+    writer.get_mut().get_mut().1.budget = usize::MAX;
+
+    let (first, second) = writer.into_inner().into_inner().unwrap();
+    assert_eq!(from_utf8(&first), Ok("  Line 1\n"));
+    assert_eq!(first, second.writer);
+}
+
+// Changing the indent level without writing anything produces no output
+#[test]
+fn test_level_changes_write_nothing() {
+    use indent_write::io::{IndentWriterBuilder, LineNumberConfig};
+
+    let builders = [
+        (IndentWriterBuilder::new("  "), "Line\n"),
+        (
+            IndentWriterBuilder::new("  ").indent_empty_lines(true),
+            "Line\n",
+        ),
+        (IndentWriterBuilder::new("  ").guide(Some("| ")), "Line\n"),
+        (
+            IndentWriterBuilder::new("  ").first_line_prefix(Some("- ")),
+            "- Line\n",
+        ),
+        (
+            IndentWriterBuilder::new("  ").line_numbers(Some(LineNumberConfig::default())),
+            "   1 | Line\n",
+        ),
+        (
+            IndentWriterBuilder::new("  ").hanging_indent(true),
+            "Line\n",
+        ),
+    ];
+
+    for (builder, expected) in builders {
+        let mut writer = builder.build(Cursor::new(Vec::new()));
+
+        writer.inc();
+        writer.flush().unwrap();
+        writer.dec_by(2);
+        writer.write_all(b"").unwrap();
+        writer.indented().flush().unwrap();
+        writer.set_indent_level(5);
+        writer.stream_position().unwrap();
+        assert_eq!(writer.pending_indent_len(), 0);
+        assert_eq!(writer.get_ref().get_ref(), b"");
+
+        writer.reset();
+        write!(writer, "Line\n").unwrap();
+        writer.inc_by(3);
+        writer.flush().unwrap();
+        writer.dec_by(3);
+
+        let dest = writer.finish().unwrap().into_inner();
+        assert_eq!(from_utf8(&dest), Ok(expected));
+    }
+}
+
+// An empty line written on its own, including straight after a level change,
+// is never indented, and the line after it always is
+#[test]
+fn test_lone_newline_write() {
+    for start_mid_line in [false, true] {
+        let mut writer = IndentWriter::new("  ", Vec::new());
+
+        if start_mid_line {
+            writer.write_all(b"a").unwrap();
+        }
+
+        writer.write_all(b"\n").unwrap();
+        writer.inc();
+        writer.write_all(b"\n").unwrap();
+        writer.write_all(b"\n").unwrap();
+        writer.write_all(b"x").unwrap();
+        writer.write_all(b"\n").unwrap();
+        writer.dec();
+        writer.write_all(b"\n").unwrap();
+        writer.write_all(b"y").unwrap();
+
+        let expected = match start_mid_line {
+            false => "\n\n\n    x\n\n  y",
+            true => "  a\n\n\n    x\n\n  y",
+        };
+        assert_eq!(from_utf8(writer.get_ref()), Ok(expected));
+    }
+
+    // The same, with a held '\r' and with empty lines indented
+    let mut writer = IndentWriter::new("  ", Vec::new());
+    writer.set_crlf_aware(true);
+    writer.write_all(b"\r").unwrap();
+    writer.write_all(b"\n").unwrap();
+    writer.write_all(b"x").unwrap();
+    assert_eq!(from_utf8(writer.get_ref()), Ok("\r\n  x"));
+
+    let mut writer = IndentWriter::new("  ", Vec::new());
+    writer.set_indent_empty_lines(true);
+    writer.write_all(b"\n").unwrap();
+    writer.write_all(b"x").unwrap();
+    assert_eq!(from_utf8(writer.get_ref()), Ok("  \n  x"));
+}
+
+#[test]
+fn test_inc_by_dec_by() {
+    let mut writer = IndentWriter::new("→ ", Vec::new());
+    let mut expected = String::new();
+
+    for &(delta, level) in &[(3i32, 4u16), (-2, 2), (5, 7), (-7, 0), (2, 2), (-10, 0)] {
+        if delta > 0 {
+            writer.inc_by(delta as u16);
+        } else {
+            writer.dec_by(-delta as u16);
+        }
+        assert_eq!(writer.indent_level(), level);
+
+        writeln!(writer, "Level {}", level).unwrap();
+        expected.push_str(&"→ ".repeat(level.into()));
+        expected.push_str(&format!("Level {}\n", level));
+    }
+
+    writer.inc_by(u16::MAX);
+    assert_eq!(writer.indent_level(), u16::MAX);
+    writer.dec_by(u16::MAX - 1);
+    assert_eq!(writer.indent_level(), 1);
+
+    assert_eq!(from_utf8(writer.get_ref()), Ok(expected.as_str()));
+}
+
+#[test]
+fn test_fmt_write() {
+    fn emit(dest: &mut impl std::fmt::Write) -> std::fmt::Result {
+        for line in CONTENT {
+            writeln!(dest, "{}", line)?;
+        }
+        dest.write_str("\n😀\n")
+    }
+
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriter::new("    ", OneByteAtATime(&mut dest));
+        emit(&mut writer).unwrap();
+    }
+    assert_eq!(
+        from_utf8(&dest),
+        Ok("    \t😀 😀 😀\n    \t\t😀 😀 😀\n    \t😀 😀 😀\n\n    😀\n")
+    );
+
+    let mut writer = IndentWriter::new(
+        "    ",
+        FailAfter {
+            writer: Vec::new(),
+            budget: 2,
+        },
+    );
+    assert_eq!(emit(&mut writer), Err(std::fmt::Error));
+}
+
+#[test]
+fn test_save_restore() {
+    let mut writer = IndentWriter::with_indent_level("\t", 2, Vec::new());
+    let outer = writer.save();
+    assert_eq!(outer.indent_level(), 2);
+
+    {
+        let mut writer = writer.indented();
+        let inner = writer.save();
+        assert_eq!(inner.indent_level(), 3);
+
+        writer.reset();
+        write!(writer, "Level 0 ").unwrap();
+
+        // Restoring mid-line takes effect on the next line
+        writer.restore(inner);
+        writeln!(writer, "continued\nLevel 3").unwrap();
+
+        writer.restore(outer);
+    }
+    assert_eq!(writer.save(), outer);
+
+    writeln!(writer, "Level 2").unwrap();
+    assert_eq!(
+        from_utf8(writer.get_ref()),
+        Ok("Level 0 continued\n\t\t\tLevel 3\n\t\tLevel 2\n")
+    );
+}
+
+#[test]
+fn test_line_ending() {
+    use indent_write::io::LineEnding;
+
+    const MIXED: &str = "a\r\n\r\n\nb\rc\n\r";
+
+    for &(ending, expected) in &[
+        (None, "\ta\r\n\r\n\n\tb\rc\n\r"),
+        (Some(LineEnding::Lf), "\ta\n\n\n\tb\rc\n\r"),
+        (Some(LineEnding::CrLf), "\ta\r\n\r\n\r\n\tb\rc\r\n\r"),
+        (Some(LineEnding::Cr), "\ta\r\r\r\tb\rc\r\r"),
+    ] {
+        let mut writer = IndentWriter::new("\t", Vec::new());
+        writer.set_crlf_aware(true);
+        writer.set_line_ending(ending);
+        write!(writer, "{}", MIXED).unwrap();
+        writer.flush().unwrap();
+        assert_eq!(from_utf8(writer.get_ref()), Ok(expected), "{:?}", ending);
+        assert_eq!(writer.bytes_written(), expected.len() as u64);
+        assert_eq!(writer.lines_written(), 4);
+
+        let mut dest = Vec::new();
+        {
+            let mut writer = IndentWriter::new("\t", OneByteAtATime(&mut dest));
+            writer.set_crlf_aware(true);
+            writer.set_line_ending(ending);
+            let mut writer = OneByteAtATime(writer);
+            write!(writer, "{}", MIXED).unwrap();
+            writer.flush().unwrap();
+        }
+        assert_eq!(from_utf8(&dest), Ok(expected), "{:?}", ending);
+    }
+}
+
+const PARAGRAPHS: &str = "Para 1\nline 2\nline 3\n\n\nPara 2\nline 2\n";
+
+#[test]
+fn test_preserve_shebang() {
+    use indent_write::io::IndentWriterBuilder;
+
+    fn indent(input: &str) -> String {
+        // Write a byte at a time, so that the start of the output is held
+        let mut dest = Vec::new();
+        {
+            let mut writer = IndentWriter::new("  ", OneByteAtATime(&mut dest));
+            writer.set_preserve_shebang(true);
+            let mut writer = OneByteAtATime(writer);
+            writer.write_all(input.as_bytes()).unwrap();
+            writer.flush().unwrap();
+        }
+
+        // Writing all at once should give the same result
+        let mut writer = IndentWriter::new("  ", Vec::new());
+        writer.set_preserve_shebang(true);
+        writer.write_all(input.as_bytes()).unwrap();
+        writer.flush().unwrap();
+        assert_eq!(writer.get_ref(), &dest);
+
+        String::from_utf8(dest).unwrap()
+    }
+
+    assert_eq!(
+        indent("#!/bin/sh\necho hello\n"),
+        "#!/bin/sh\n  echo hello\n"
+    );
+    assert_eq!(
+        indent("\u{FEFF}Line 1\nLine 2\n"),
+        "\u{FEFF}Line 1\n  Line 2\n"
+    );
+
+    // Anything else is indented as usual, including a '#' on its own
+    assert_eq!(indent("# comment\nLine 2"), "  # comment\n  Line 2");
+    assert_eq!(indent("#"), "  #");
+    assert_eq!(indent("\n#!/bin/sh\n"), "\n  #!/bin/sh\n");
+
+    // Only the start of the output is checked
+    let mut writer = IndentWriter::new("  ", Vec::new());
+    write!(writer, "Line 1\n").unwrap();
+    writer.set_preserve_shebang(true);
+    write!(writer, "#!/bin/sh\n").unwrap();
+    assert_eq!(writer.finish().unwrap(), b"  Line 1\n  #!/bin/sh\n");
+
+    // A held start of a line is ended by finish
+    let mut writer = IndentWriterBuilder::new("  ")
+        .preserve_shebang(true)
+        .build(Vec::new());
+    write!(writer, "#").unwrap();
+    assert_eq!(writer.get_ref(), b"");
+    assert_eq!(writer.finish().unwrap(), b"  #\n");
+}
+
+#[test]
+fn test_hanging_indent() {
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriter::new("  ", OneByteAtATime(&mut dest));
+        writer.set_hanging_indent(true);
+        let mut writer = OneByteAtATime(writer);
+        write!(writer, "{}", PARAGRAPHS).unwrap();
+    }
+    assert_eq!(
+        from_utf8(&dest),
+        Ok("Para 1\n  line 2\n  line 3\n\n\nPara 2\n  line 2\n")
+    );
+
+    let mut writer = IndentWriter::new("  ", Vec::new());
+    writer.set_hanging_indent(true);
+    writer.set_indent_empty_lines(true);
+    write!(writer, "{}", PARAGRAPHS).unwrap();
+    assert_eq!(
+        from_utf8(writer.get_ref()),
+        Ok("Para 1\n  line 2\n  line 3\n  \n  \nPara 2\n  line 2\n")
+    );
+
+    // With new_skip_initial, the initial line is the start of the first
+    // paragraph
+    let mut writer = IndentWriter::new_skip_initial("  ", Vec::new());
+    writer.set_hanging_indent(true);
+    write!(writer, "{}", PARAGRAPHS).unwrap();
+    assert_eq!(
+        from_utf8(writer.get_ref()),
+        Ok("Para 1\n  line 2\n  line 3\n\n\nPara 2\n  line 2\n")
+    );
+}
+
+#[test]
+fn test_write_line() {
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriter::new("\t", OneByteAtATime(&mut dest));
+        writer.write_line("Line 1").unwrap();
+        writer.write_line(b"Line 2").unwrap();
+        write!(writer, "Partial").unwrap();
+        writer.write_line("Line 3").unwrap();
+        writer.write_line("").unwrap();
+        writer.write_line(String::from("😀 😀 😀")).unwrap();
+    }
+    assert_eq!(
+        from_utf8(&dest),
+        Ok("\tLine 1\n\tLine 2\n\tPartial\n\tLine 3\n\n\t😀 😀 😀\n")
+    );
+
+    let mut writer = IndentWriter::new_skip_initial("\t", Vec::new());
+    writer.write_line("Line 1").unwrap();
+    assert_eq!(from_utf8(writer.get_ref()), Ok("\n\tLine 1\n"));
+}
+
+#[test]
+fn test_write_block() {
+    // A snippet with two levels of its own indentation
+    const SNIPPET: &str = "outer:\n  middle:\n    inner\n\n  middle";
+
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriter::new("  ", OneByteAtATime(&mut dest));
+        writer.set_reindent(true);
+        write!(writer, "root:").unwrap();
+        writer.inc();
+        writer.write_block(SNIPPET).unwrap();
+        writer.dec();
+
+        // Reindenting is restored afterwards
+        write!(writer, "    end\n").unwrap();
+    }
+    assert_eq!(
+        from_utf8(&dest),
+        Ok("  root:\n    outer:\n      middle:\n        inner\n\n      middle\n  end\n")
+    );
+
+    // A block ending with a newline doesn't get another
+    let mut writer = IndentWriter::new("> ", Vec::new());
+    writer.write_block("  a\n").unwrap();
+    writer.write_block("").unwrap();
+    assert_eq!(from_utf8(writer.get_ref()), Ok(">   a\n"));
+}
+
+#[test]
+fn test_builder() {
+    use indent_write::io::IndentWriterBuilder;
+
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriterBuilder::new("  ")
+            .skip_initial(true)
+            .indent_empty_lines(true)
+            .crlf_aware(true)
+            .build(OneByteAtATime(&mut dest));
+        write!(writer, "a\r\n\r\nb\r\n").unwrap();
+    }
+    assert_eq!(from_utf8(&dest), Ok("a\r\n  \r\n  b\r\n"));
+
+    let mut writer = IndentWriterBuilder::new("  ")
+        .indent_level(0)
+        .first_line_prefix(Some("- "))
+        .build(Vec::new());
+    assert_eq!(writer.indent_level(), 0);
+    writer.inc();
+    write!(writer, "{}", PARAGRAPHS).unwrap();
+    assert_eq!(
+        from_utf8(writer.get_ref()),
+        Ok("- Para 1\n  line 2\n  line 3\n\n\n  Para 2\n  line 2\n")
+    );
+
+    let mut writer = IndentWriterBuilder::new("  ")
+        .hanging_indent(true)
+        .build(Vec::new());
+    write!(writer, "{}", PARAGRAPHS).unwrap();
+    assert_eq!(
+        from_utf8(writer.get_ref()),
+        Ok("Para 1\n  line 2\n  line 3\n\n\nPara 2\n  line 2\n")
+    );
+}
+
+#[test]
+fn test_builder_eq() {
+    use std::collections::HashSet;
+
+    use indent_write::io::{IndentWriterBuilder, LineEnding, LineNumberConfig};
+
+    let config = |indent| {
+        IndentWriterBuilder::new(indent)
+            .indent_level(2)
+            .line_ending(Some(LineEnding::CrLf))
+            .line_numbers(Some(LineNumberConfig::default()))
+    };
+
+    assert_eq!(config("  "), config("  "));
+    assert_ne!(config("  "), config("\t"));
+    assert_ne!(config("  "), config("  ").indent_level(3));
+    assert_ne!(config("  "), config("  ").line_numbers(None));
+
+    let configs: HashSet<_> = [config("  "), config("\t"), config("  ")].into();
+    assert_eq!(configs.len(), 2);
+    assert!(configs.contains(&config("\t")));
+}
+
+#[test]
+fn test_at_line_start() {
+    let mut writer = IndentWriter::new("\t", Vec::new());
+    assert!(writer.at_line_start());
+
+    write!(writer, "\n\n").unwrap();
+    assert!(writer.at_line_start());
+
+    write!(writer, "a").unwrap();
+    assert!(!writer.at_line_start());
+
+    write!(writer, "b\nc").unwrap();
+    assert!(!writer.at_line_start());
+
+    write!(writer, "\n").unwrap();
+    assert!(writer.at_line_start());
+
+    // Interleave a direct write of a complete line, ending the current line
+    // first if necessary
+    write!(writer, "d").unwrap();
+    if !writer.at_line_start() {
+        writeln!(writer).unwrap();
+    }
+    writer.get_mut().extend_from_slice(b"raw\n");
+    write!(writer, "e").unwrap();
+    assert_eq!(
+        from_utf8(writer.get_ref()),
+        Ok("\n\n\tab\n\tc\n\td\nraw\n\te")
+    );
+
+    let writer = IndentWriter::new_skip_initial("\t", Vec::new());
+    assert!(!writer.at_line_start());
+}
+
+#[test]
+fn test_guide() {
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriter::new("+-", OneByteAtATime(&mut dest));
+        writer.set_guide(Some("| "));
+        writeln!(writer, "a").unwrap();
+        writer.inc();
+        writeln!(writer, "b\n\nc").unwrap();
+        writer.inc_by(2);
+        writeln!(writer, "d").unwrap();
+        writer.dec_by(3);
+        writeln!(writer, "e").unwrap();
+        writer.inc_by(2);
+        writer.set_guide(None);
+        writeln!(writer, "f").unwrap();
+    }
+    assert_eq!(
+        from_utf8(&dest),
+        Ok("+-a\n| +-b\n\n| +-c\n| | | +-d\n+-e\n+-+-+-f\n")
+    );
+}
+
+#[test]
+fn test_max_level() {
+    let mut writer = IndentWriter::new("→ ", Vec::new());
+    assert_eq!(writer.max_level(), None);
+    writer.set_max_level(Some(3));
+    assert_eq!(writer.max_level(), Some(3));
+
+    let mut indent_lengths = Vec::new();
+    for _ in 0..6 {
+        writer.inc();
+        write!(writer, "x").unwrap();
+        indent_lengths.push(writer.column() - 1);
+        writeln!(writer).unwrap();
+    }
+    assert_eq!(indent_lengths, [8, 12, 12, 12, 12, 12]);
+    assert_eq!(writer.indent_level(), 3);
+
+    writer.inc_by(u16::MAX);
+    writer.set_indent_level(100);
+    assert_eq!(writer.indent_level(), 3);
+
+    // Lowering the maximum lowers the current level
+    writer.set_max_level(Some(1));
+    assert_eq!(writer.indent_level(), 1);
+
+    // Removing the maximum doesn't change the current level
+    writer.set_max_level(None);
+    assert_eq!(writer.indent_level(), 1);
+    writer.inc_by(9);
+    assert_eq!(writer.indent_level(), 10);
+}
+
+#[test]
+fn test_expand_tabs() {
+    const TABBED: &str = "a\tb\n\tc\n😀\té\td\nabcdefgh\tx\t\n";
+    #[cfg(not(feature = "unicode-width"))]
+    const EXPECTED: &str = "\ta  b\n\t   c\n\t😀  é   d\n\tabcdefgh   x   \n";
+
+    // The emoji is two columns wide
+    #[cfg(feature = "unicode-width")]
+    const EXPECTED: &str = "\ta  b\n\t   c\n\t😀 é   d\n\tabcdefgh   x   \n";
+
+    let mut writer = IndentWriter::new("\t", Vec::new());
+    writer.set_expand_tabs(Some(4));
+    write!(writer, "{}", TABBED).unwrap();
+    assert_eq!(from_utf8(writer.get_ref()), Ok(EXPECTED));
+    assert_eq!(writer.bytes_written(), EXPECTED.len() as u64);
+
+    write!(writer, "ab\t").unwrap();
+    assert_eq!(writer.column(), 4);
+    assert_eq!(writer.column_chars(), 4);
+
+    // Tabs split across writes, and expansions split across partial writes
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriter::new("\t", OneByteAtATime(&mut dest));
+        writer.set_expand_tabs(Some(4));
+        let mut writer = OneByteAtATime(writer);
+        write!(writer, "{}", TABBED).unwrap();
+        writer.flush().unwrap();
+    }
+    assert_eq!(from_utf8(&dest), Ok(EXPECTED));
+}
+
+#[test]
+fn test_wrap_at() {
+    use indent_write::io::IndentWriterBuilder;
+
+    fn wrap(input: &str, width: usize) -> String {
+        let mut writer = IndentWriter::new("  ", Vec::new());
+        writer.set_wrap_at(Some(width));
+        writer.write_all(input.as_bytes()).unwrap();
+        let output = String::from_utf8(writer.finish().unwrap()).unwrap();
+
+        // Writing a byte at a time should give the same result
+        let mut dest = Vec::new();
+        {
+            let mut writer = IndentWriter::new("  ", OneByteAtATime(&mut dest));
+            writer.set_wrap_at(Some(width));
+            let mut writer = OneByteAtATime(writer);
+            writer.write_all(input.as_bytes()).unwrap();
+            writer.0.finish().unwrap();
+        }
+        assert_eq!(from_utf8(&dest), Ok(output.as_str()));
+
+        output
+    }
+
+    assert_eq!(
+        wrap("The quick brown fox jumps over the lazy dog\n", 20),
+        "  The quick brown\n  fox jumps over the\n  lazy dog\n"
+    );
+
+    // Existing line breaks are kept, and leading whitespace is part of the
+    // line
+    assert_eq!(
+        wrap("a b c\n\n    d e f g h i j k\n", 12),
+        "  a b c\n\n      d e f\n  g h i j k\n"
+    );
+
+    // Words longer than the width are broken, but never inside a character
+    assert_eq!(
+        wrap("abcdefghijklmnopqrstuvwxyz\n", 20),
+        "  abcdefghijklmnopqr\n  stuvwxyz\n"
+    );
+    assert_eq!(
+        wrap("x ééééééééééééééééééééé", 20),
+        "  x\n  éééééééééééééééééé\n  ééé\n"
+    );
+
+    // The held word is written by a flush
+    let mut writer = IndentWriterBuilder::new("  ")
+        .wrap_at(Some(8))
+        .build(Vec::new());
+    write!(writer, "one two").unwrap();
+    assert_eq!(writer.get_ref(), b"  one");
+    writer.flush().unwrap();
+    assert_eq!(writer.get_ref(), b"  one\n  two");
+}
+
+#[test]
+fn test_expand_tabs_wide() {
+    let mut writer = IndentWriter::new("", Vec::new());
+    writer.set_expand_tabs(Some(50));
+    write!(writer, "a\tb").unwrap();
+    assert_eq!(writer.get_ref().len(), 51);
+    assert!(writer.get_ref().ends_with(b" b"));
+    assert_eq!(writer.column(), 51);
+}
+
+#[test]
+fn test_reindent() {
+    const MESSY: &str = "fn main() {\n\t  let x = 1;\n  \n\n      if x {\n \t\t}\n}\n";
+    const EXPECTED: &str = "  fn main() {\n  let x = 1;\n\n\n  if x {\n  }\n  }\n";
+
+    let mut writer = IndentWriter::new("  ", Vec::new());
+    writer.set_reindent(true);
+    write!(writer, "{}", MESSY).unwrap();
+    assert_eq!(from_utf8(writer.get_ref()), Ok(EXPECTED));
+
+    // Leading whitespace split across writes
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriter::new("  ", OneByteAtATime(&mut dest));
+        writer.set_reindent(true);
+        let mut writer = OneByteAtATime(writer);
+        write!(writer, "{}", MESSY).unwrap();
+        writer.flush().unwrap();
+    }
+    assert_eq!(from_utf8(&dest), Ok(EXPECTED));
+}
+
+#[test]
+fn test_reindent_levels() {
+    let mut writer = IndentWriter::new("    ", Vec::new());
+    writer.set_reindent(true);
+
+    write!(writer, "  a\n").unwrap();
+    writer.inc();
+    write!(writer, "\tb\n").unwrap();
+    write!(writer, "c\n").unwrap();
+    writer.dec();
+    write!(writer, "   \t").unwrap();
+    write!(writer, "d\n").unwrap();
+
+    assert_eq!(
+        from_utf8(writer.get_ref()),
+        Ok("    a\n        b\n        c\n    d\n")
+    );
+}
+
+#[test]
+fn test_flush_pending_indent() {
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriter::new("    ", OneByteAtATime(&mut dest));
+
+        writer.flush_pending_indent().unwrap();
+        assert_eq!(from_utf8(writer.get_ref().0), Ok("    "));
+        assert!(!writer.at_line_start());
+
+        // Flushing again mid-line does nothing
+        writer.flush_pending_indent().unwrap();
+        write!(writer, "Line 1\n").unwrap();
+        assert_eq!(from_utf8(writer.get_ref().0), Ok("    Line 1\n"));
+
+        // The indent is written even if the line stays empty
+        writer.inc();
+        writer.flush_pending_indent().unwrap();
+        write!(writer, "\nLine 2\n").unwrap();
+    }
+    assert_eq!(
+        from_utf8(&dest),
+        Ok("    Line 1\n        \n        Line 2\n")
+    );
+}
+
+// Write all of `bufs` to `writer` with `write_vectored`
+fn write_all_vectored(writer: &mut impl Write, bufs: &[&str]) -> io::Result<()> {
+    let mut slices: Vec<IoSlice<'_>> = bufs
+        .iter()
+        .map(|buf| IoSlice::new(buf.as_bytes()))
+        .collect();
+    let mut slices = &mut slices[..];
+
+    while !slices.is_empty() {
+        match writer.write_vectored(slices)? {
+            0 => return Err(io::ErrorKind::WriteZero.into()),
+            n => IoSlice::advance_slices(&mut slices, n),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_write_vectored() {
+    const SLICES: &[&str] = &[
+        "Line 1", "\n", "", "Line 2\n", "Li", "ne", " 3\n\n", "\n", "Line 4",
+    ];
+    const EXPECTED: &str = "    Line 1\n    Line 2\n    Line 3\n\n\n    Line 4";
+
+    let mut writer = IndentWriter::new("    ", Vec::new());
+    write_all_vectored(&mut writer, SLICES).unwrap();
+    assert_eq!(from_utf8(writer.get_ref()), Ok(EXPECTED));
+    assert_eq!(writer.bytes_written(), EXPECTED.len() as u64);
+    assert_eq!(writer.lines_written(), 5);
+    assert_eq!(writer.column(), 10);
+
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriter::new("    ", OneByteAtATime(&mut dest));
+        write_all_vectored(&mut writer, SLICES).unwrap();
+    }
+    assert_eq!(from_utf8(&dest), Ok(EXPECTED));
+
+    // Translation still applies to vectored writes
+    let mut writer = IndentWriter::new("\t", Vec::new());
+    writer.set_line_ending(Some(indent_write::io::LineEnding::CrLf));
+    writer.set_expand_tabs(Some(4));
+    write_all_vectored(&mut writer, &["a", "\tb", "\n", "c\n"]).unwrap();
+    assert_eq!(from_utf8(writer.get_ref()), Ok("\ta  b\r\n\tc\r\n"));
+}
+
+#[test]
+fn test_current_indent() {
+    let mut writer = IndentWriter::with_indent_level(
+        "→",
+        0,
+        FailAfter {
+            writer: Vec::new(),
+            budget: 1,
+        },
+    );
+    assert_eq!(writer.current_indent_str(), Some(""));
+
+    writer.inc_by(2);
+    assert_eq!(writer.current_indent_str(), Some("→→"));
+    assert_eq!(writer.current_indent(), "→→".as_bytes());
+
+    writer.set_first_line_prefix(Some("- "));
+    assert_eq!(writer.current_indent_str(), Some("- "));
+
+    // Partway through writing the prefix, changing the level doesn't affect
+    // the indent for the current line
+    assert!(writer.write_all(b"x\n").is_err());
+    writer.dec();
+    assert_eq!(writer.current_indent_str(), Some("- "));
+
+    writer.get_mut().budget = usize::MAX;
+    writer.write_all(b"x\n").unwrap();
+    assert_eq!(writer.current_indent_str(), Some("→"));
+    assert_eq!(from_utf8(&writer.get_ref().writer), Ok("- x\n"));
+}
+
+#[test]
+fn test_empty_indent() {
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriter::new("", OneByteAtATime(&mut dest));
+        writer.dec();
+        writer.dec();
+        assert_eq!(writer.indent_level(), 0);
+        write!(writer, "Line 1\n\n").unwrap();
+
+        writer.inc_by(3);
+        assert_eq!(writer.current_indent(), b"");
+        write!(writer, "Line 2\n").unwrap();
+        writer.dec_by(5);
+        writer.flush_pending_indent().unwrap();
+        write!(writer, "Line 3").unwrap();
+        writer.flush().unwrap();
+
+        assert_eq!(writer.bytes_written(), 21);
+        assert_eq!(writer.column(), 6);
+    }
+    assert_eq!(from_utf8(&dest), Ok("Line 1\n\nLine 2\nLine 3"));
+}
+
+#[test]
+fn test_dec_past_zero() {
+    let mut writer = IndentWriter::new("    ", Vec::new());
+    writer.dec();
+    writer.dec();
+    writer.dec();
+    assert_eq!(writer.indent_level(), 0);
+    assert_eq!(writer.current_indent(), b"");
+
+    write!(writer, "Line\n").unwrap();
+    writer.inc();
+    write!(writer, "Line\n").unwrap();
+    assert_eq!(from_utf8(writer.get_ref()), Ok("Line\n    Line\n"));
+}
+
+#[test]
+fn test_base_level() {
+    let mut writer = IndentWriter::new("  ", Vec::new());
+    writer.set_base_level(1);
+    writer.inc_by(2);
+    write!(writer, "Level 3\n").unwrap();
+
+    writer.reset();
+    assert_eq!(writer.indent_level(), 1);
+    write!(writer, "Level 1\n").unwrap();
+
+    writer.dec_by(5);
+    writer.set_indent_level(0);
+    assert_eq!(writer.indent_level(), 1);
+    write!(writer, "Level 1\n").unwrap();
+
+    // The base takes precedence over the maximum
+    writer.set_max_level(Some(0));
+    writer.set_base_level(2);
+    assert_eq!(writer.indent_level(), 2);
+    write!(writer, "Level 2\n").unwrap();
+
+    assert_eq!(
+        from_utf8(writer.get_ref()),
+        Ok("      Level 3\n  Level 1\n  Level 1\n    Level 2\n")
+    );
+}
+
+#[test]
+fn test_seek() {
+    let mut writer = IndentWriter::new("  ", Cursor::new(Vec::new()));
+    write!(writer, "Count: 0\nItem 1\nIte").unwrap();
+
+    // Getting the position doesn't affect the current line
+    assert_eq!(writer.stream_position().unwrap(), 25);
+    write!(writer, "m 2\n").unwrap();
+
+    writer.seek(SeekFrom::Start(0)).unwrap();
+    assert!(writer.at_line_start());
+    write!(writer, "Count: 2").unwrap();
+
+    writer.seek(SeekFrom::End(0)).unwrap();
+    write!(writer, "Item 3\n").unwrap();
+
+    assert_eq!(
+        from_utf8(writer.get_ref().get_ref()),
+        Ok("  Count: 2\n  Item 1\n  Item 2\n  Item 3\n")
+    );
+}
+
+#[test]
+fn test_seek_mid_indent() {
+    let mut writer = IndentWriter::new(
+        "    ",
+        FailAfter {
+            writer: Cursor::new(Vec::new()),
+            budget: 11,
+        },
+    );
+    assert!(write!(writer, "Line\nLine\n").is_err());
+    assert_eq!(writer.pending_indent_len(), 2);
+
+    // The rest of the indent is written before seeking
+    writer.get_mut().budget = usize::MAX;
+    assert_eq!(writer.seek(SeekFrom::Current(-4)).unwrap(), 9);
+    assert_eq!(writer.pending_indent_len(), 0);
+    write!(writer, "Line\n").unwrap();
+
+    assert_eq!(
+        from_utf8(writer.get_ref().writer.get_ref()),
+        Ok("    Line\n    Line\n")
+    );
+}
+
+#[test]
+fn test_reset_line() {
+    let mut writer = IndentWriter::new("  ", Vec::new());
+    write!(writer, "Header: ").unwrap();
+    assert!(!writer.at_line_start());
+
+    writer.get_mut().extend_from_slice(b"raw\n");
+    writer.reset_line();
+    assert!(writer.at_line_start());
+    assert_eq!(writer.column(), 0);
+
+    writer.inc();
+    write!(writer, "{}\n", "Nested").unwrap();
+
+    writer.get_mut().extend_from_slice(b"raw\n");
+    writer.reset_all();
+    assert_eq!(writer.indent_level(), 0);
+    write!(writer, "{}\n", "Base").unwrap();
+
+    assert_eq!(
+        from_utf8(writer.get_ref()),
+        Ok("  Header: raw\n    Nested\nraw\nBase\n")
+    );
+}
+
+#[test]
+fn test_reset_line_mid_indent() {
+    let mut writer = IndentWriter::new(
+        "    ",
+        FailAfter {
+            writer: Vec::new(),
+            budget: 11,
+        },
+    );
+    assert!(write!(writer, "Line\nLine\n").is_err());
+    assert_eq!(writer.pending_indent_len(), 2);
+
+    // The partial indent is abandoned, and the next line gets a full indent
+    writer.get_mut().budget = usize::MAX;
+    writer.get_mut().writer.extend_from_slice(b"\n");
+    writer.reset_line();
+    assert_eq!(writer.pending_indent_len(), 0);
+    write!(writer, "Line\n").unwrap();
+
+    assert_eq!(
+        from_utf8(&writer.get_ref().writer),
+        Ok("    Line\n  \n    Line\n")
+    );
+}
+
+#[test]
+fn test_write_counted() {
+    let mut writer = IndentWriter::new("  ", Vec::new());
+    let mut consumed = 0;
+    let mut emitted = 0;
+
+    for chunk in ["Line 1\nLi", "ne 2\n\n", "Line 3\nLine 4\n"] {
+        let (c, e) = writer.write_counted(chunk.as_bytes()).unwrap();
+        consumed += c;
+        emitted += e;
+    }
+
+    let expected = "  Line 1\n  Line 2\n\n  Line 3\n  Line 4\n";
+    assert_eq!(from_utf8(writer.get_ref()), Ok(expected));
+    assert_eq!(consumed, expected.len() - 8);
+    assert_eq!(emitted, expected.len());
+
+    // All of the buffer is written, even if the wrapped writer takes it a
+    // byte at a time
+    let mut writer = IndentWriter::new("  ", OneByteAtATime(Vec::new()));
+    assert_eq!(
+        writer.write_counted(b"Line 1\n\nLine 2\n").unwrap(),
+        (15, 19)
+    );
+    assert_eq!(from_utf8(&writer.get_ref().0), Ok("  Line 1\n\n  Line 2\n"));
+
+    // Trailing whitespace is consumed now, but only emitted with the line
+    writer.set_trim_trailing_whitespace(true);
+    assert_eq!(writer.write_counted(b"Line").unwrap(), (4, 6));
+    assert_eq!(writer.write_counted(b"  ").unwrap(), (2, 0));
+    assert_eq!(writer.write_counted(b"x").unwrap(), (1, 3));
+}
+
+#[test]
+fn test_line_phase() {
+    let mut writer = IndentWriter::new(
+        "    ",
+        FailAfter {
+            writer: Vec::new(),
+            budget: 13,
+        },
+    );
+    assert_eq!(writer.line_phase(), LinePhase::NeedIndent);
+
+    // Empty lines don't start an indent
+    writer.write_all(b"\n\n").unwrap();
+    assert_eq!(writer.line_phase(), LinePhase::NeedIndent);
+
+    writer.write_all(b"Line").unwrap();
+    assert_eq!(writer.line_phase(), LinePhase::MidLine);
+
+    writer.write_all(b"\n").unwrap();
+    assert_eq!(writer.line_phase(), LinePhase::NeedIndent);
+
+    // The budget runs out partway through the indent
+    assert!(writer.write_all(b"Line\n").is_err());
+    assert_eq!(writer.line_phase(), LinePhase::WritingIndent);
+
+    writer.get_mut().budget = usize::MAX;
+    writer.flush().unwrap();
+    assert_eq!(writer.line_phase(), LinePhase::MidLine);
+}
+
+#[test]
+fn test_bytes_indent() {
+    let mut writer = IndentWriter::new_bytes(&[0xFF, b'|'], OneByteAtATime(Vec::new()));
+    assert_eq!(writer.indent(), None);
+    assert_eq!(writer.indent_bytes(), b"\xFF|");
+
+    write!(writer, "Line 1\n\n").unwrap();
+    writer.inc();
+    assert_eq!(writer.current_indent(), b"\xFF|\xFF|");
+    assert_eq!(writer.current_indent_str(), None);
+    write!(writer, "Line 2\n").unwrap();
+
+    writer.set_guide(Some(": "));
+    write!(writer, "Line 3\n").unwrap();
+
+    assert_eq!(
+        writer.get_ref().0,
+        b"\xFF|Line 1\n\n\xFF|\xFF|Line 2\n: \xFF|Line 3\n"
+    );
+}
+
+#[test]
+fn test_write_raw() {
+    let mut writer = IndentWriter::new("  ", OneByteAtATime(Vec::new()));
+
+    fn write_all_raw<W: Write>(writer: &mut IndentWriter<W>, mut buf: &[u8]) {
+        while !buf.is_empty() {
+            let n = writer.write_raw(buf).unwrap();
+            buf = &buf[n..];
+        }
+    }
+
+    write!(writer, "Line 1\n").unwrap();
+
+    // A raw block is written as-is, and leaves the writer at the start of a
+    // line
+    write_all_raw(&mut writer, b"Raw 1\n\tRaw 2\n");
+    assert!(writer.at_line_start());
+    write!(writer, "Line 2 ").unwrap();
+
+    // A raw segment without a newline leaves the writer mid-line
+    write_all_raw(&mut writer, b"(raw)");
+    assert!(!writer.at_line_start());
+    write!(writer, " continued\nLine 3\n").unwrap();
+
+    assert_eq!(
+        from_utf8(&writer.get_ref().0),
+        Ok("  Line 1\nRaw 1\n\tRaw 2\n  Line 2 (raw) continued\n  Line 3\n")
+    );
+    assert_eq!(writer.lines_written(), 5);
+}
+
+#[test]
+fn test_write_raw_pending() {
+    let mut writer = IndentWriter::new(
+        "    ",
+        FailAfter {
+            writer: Vec::new(),
+            budget: 2,
+        },
+    );
+    writer.set_trim_trailing_whitespace(true);
+
+    // The partially written indent is completed before the raw write
+    assert!(writer.write_all(b"Line").is_err());
+    writer.get_mut().budget = usize::MAX;
+    writer.write_raw(b"[raw]").unwrap();
+
+    // Held whitespace is no longer trailing, so it's written too
+    write!(writer, "Line  ").unwrap();
+    writer.write_raw(b"[raw]\n").unwrap();
+
+    assert_eq!(
+        from_utf8(&writer.get_ref().writer),
+        Ok("    [raw]Line  [raw]\n")
+    );
+}
+
+#[test]
+fn test_clone_mid_indent() {
+    let mut writer = IndentWriter::with_indent_level(
+        "1234",
+        2,
+        FailAfter {
+            writer: Vec::new(),
+            budget: 18,
+        },
+    );
+    assert!(write!(writer, "Line 1\nLine 2\n").is_err());
+    assert_eq!(writer.pending_indent_len(), 5);
+
+    // Each writer finishes the indent it started with, independently of any
+    // changes made to the other
+    let mut clone = writer.clone();
+
+    writer.get_mut().budget = usize::MAX;
+    writer.dec();
+    write!(writer, "Line 2\nLine 3\n").unwrap();
+
+    clone.get_mut().budget = usize::MAX;
+    clone.set_indent("-");
+    write!(clone, "Line 2\nLine 3\n").unwrap();
+
+    assert_eq!(
+        from_utf8(&writer.get_ref().writer),
+        Ok("12341234Line 1\n12341234Line 2\n1234Line 3\n")
+    );
+    assert_eq!(
+        from_utf8(&clone.get_ref().writer),
+        Ok("12341234Line 1\n12341234Line 2\n--Line 3\n")
+    );
+}
+
+#[test]
+fn test_line_filter() {
+    let mut writer = IndentWriter::new("  ", OneByteAtATime(Vec::new()));
+    writer.set_line_filter(|line: &[u8]| !line.starts_with(b"#"));
+
+    write!(writer, "# Head").unwrap();
+
+    // The line is buffered until the filter can be called
+    assert!(!writer.at_line_start());
+    assert_eq!(writer.line_phase(), LinePhase::MidLine);
+    assert_eq!(writer.bytes_written(), 0);
+
+    write!(writer, "ing\nBody\n\n").unwrap();
+    writer.write_line("#").unwrap();
+    writer.write_line("Body").unwrap();
+
+    // Once cleared, every line is indented again
+    writer.clear_line_filter();
+    write!(writer, "# Comment\n").unwrap();
+
+    assert_eq!(
+        from_utf8(&writer.get_ref().0),
+        Ok("# Heading\n  Body\n\n#\n  Body\n  # Comment\n")
+    );
+}
+
+#[test]
+fn test_right_align() {
+    let mut writer = IndentWriter::new("  ", OneByteAtATime(Vec::new()));
+    writer.set_right_align(Some(10));
+
+    write!(writer, "{}\n{}\n\n", "a", "12345").unwrap();
+    writer.inc();
+    write!(writer, "{}\n{}\n", "1234567890", "longer than ten").unwrap();
+    writer.dec();
+
+    // A line flushed partway through is aligned by what's been written
+    write!(writer, "{}", "abc").unwrap();
+    writer.flush().unwrap();
+    write!(writer, "{}\n", "def").unwrap();
+
+    writer.set_right_align(None);
+    write!(writer, "{}\n", "left").unwrap();
+
+    assert_eq!(
+        from_utf8(&writer.get_ref().0),
+        Ok(concat!(
+            "           a\n",
+            "       12345\n",
+            "\n",
+            "    1234567890\n",
+            "    longer than ten\n",
+            "         abcdef\n",
+            "  left\n",
+        ))
+    );
+}
+
+#[test]
+fn test_flush_on_newline() {
+    use indent_write::io::LineEnding;
+
+    let mut writer = IndentWriter::new("  ", OneByteAtATime(FlushLog::default()));
+    writer.set_flush_on_newline(true);
+    writer.set_line_ending(Some(LineEnding::CrLf));
+
+    // Each line is flushed once its whole line ending has been written, but
+    // before the next indent
+    write!(writer, "{}\n{}\n\nLi", "Line 1", "Line 2").unwrap();
+    assert_eq!(writer.get_ref().0.flushed_at, [10, 20, 22]);
+
+    write!(writer, "{}", "ne 3").unwrap();
+    assert_eq!(writer.get_ref().0.flushed_at, [10, 20, 22]);
+
+    writer.set_flush_on_newline(false);
+    write!(writer, "{}", "\nLine 4\n").unwrap();
+    assert_eq!(writer.get_ref().0.flushed_at, [10, 20, 22]);
+
+    writer.flush().unwrap();
+    assert_eq!(
+        from_utf8(&writer.get_ref().0.written),
+        Ok("  Line 1\r\n  Line 2\r\n\r\n  Line 3\r\n  Line 4\r\n")
+    );
+}
+
+#[test]
+fn test_on_newline() {
+    use indent_write::io::LineEnding;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let last_line = Rc::new(Cell::new(0));
+    let calls = Cell::new(0);
+
+    let mut writer = IndentWriter::new("  ", OneByteAtATime(Vec::new()));
+    writer.set_line_ending(Some(LineEnding::CrLf));
+    writer.set_on_newline({
+        let last_line = Rc::clone(&last_line);
+        let calls = &calls;
+        move |line| {
+            last_line.set(line);
+            calls.set(calls.get() + 1);
+        }
+    });
+
+    // Each translated newline is written a byte at a time, but reported once
+    write!(writer, "Line 1\n\nLine 2\r\nLi").unwrap();
+    assert_eq!((last_line.get(), calls.get()), (3, 3));
+
+    // A clone of the writer has no callback
+    let mut clone = writer.clone();
+    writeln!(clone, "ne 3").unwrap();
+    assert_eq!((last_line.get(), calls.get()), (3, 3));
+
+    write!(writer, "ne 3\n").unwrap();
+    writer.clear_on_newline();
+    write!(writer, "Line 4\n").unwrap();
+
+    writer.flush().unwrap();
+
+    assert_eq!((last_line.get(), calls.get()), (4, 4));
+    assert_eq!(writer.lines_written(), 5);
+    assert_eq!(
+        from_utf8(&writer.get_ref().0),
+        Ok("  Line 1\r\n\r\n  Line 2\r\n  Line 3\r\n  Line 4\r\n")
+    );
+}
+
+#[test]
+fn test_line_filter_stateful() {
+    // Indent every other line, recording each line the filter sees. The
+    // filter borrows its state, so it can't be cloned.
+    let mut seen = Vec::new();
+    let mut writer = IndentWriter::new("  ", Vec::new());
+    writer.set_line_filter(|line: &[u8]| {
+        seen.push(line.to_vec());
+        seen.len() % 2 == 0
+    });
+
+    write!(writer, "Line 1\nLine 2\n\nLine 3\nLi").unwrap();
+
+    // A clone has no filter, so it indents every line, including the one
+    // that was buffered
+    let mut clone = writer.clone();
+    writeln!(clone, "ne 4\nLine 5").unwrap();
+    assert_eq!(
+        from_utf8(clone.get_ref()),
+        Ok("Line 1\n  Line 2\n\nLine 3\n  Line 4\n  Line 5\n")
+    );
+
+    writeln!(writer, "ne 4\nLine 5").unwrap();
+    assert_eq!(
+        from_utf8(writer.get_ref()),
+        Ok("Line 1\n  Line 2\n\nLine 3\n  Line 4\nLine 5\n")
+    );
+
+    drop((writer, clone));
+    assert_eq!(
+        seen,
+        [&b"Line 1"[..], b"Line 2", b"Line 3", b"Line 4", b"Line 5"]
+    );
+}
+
+#[test]
+fn test_line_filter_flush() {
+    let mut writer = IndentWriter::new("  ", Vec::new());
+    writer.set_line_filter(|line: &[u8]| line.len() > 3);
+
+    // Flushing calls the filter with the line so far
+    write!(writer, "Line").unwrap();
+    writer.flush().unwrap();
+    write!(writer, " 1\nabc").unwrap();
+    writer.flush().unwrap();
+    write!(writer, "defg\n").unwrap();
+
+    // An indent can't be written until the filter has seen the line
+    writer.flush_pending_indent().unwrap();
+    assert_eq!(writer.pending_indent_len(), 0);
+    write!(writer, "abc\n").unwrap();
+    write!(writer, "Line 2").unwrap();
+    writer.flush_pending_indent().unwrap();
+
+    assert_eq!(
+        from_utf8(writer.get_ref()),
+        Ok("  Line 1\nabcdefg\nabc\n  Line 2")
+    );
+}
+
+#[test]
+fn test_line_filter_error() {
+    let mut writer = IndentWriter::new(
+        "  ",
+        FailAfter {
+            writer: Vec::new(),
+            budget: 9,
+        },
+    );
+    writer.set_line_filter(|line: &[u8]| line != b"Skip");
+
+    // The line is accepted in full before it's written, so the error is
+    // reported by the next write
+    assert_eq!(writer.write(b"Line 1\n").unwrap(), 6);
+    assert_eq!(writer.write(b"\n").unwrap(), 1);
+    assert_eq!(writer.write(b"Line 2\n").unwrap(), 6);
+    assert!(writer.write(b"\n").is_err());
+
+    writer.get_mut().budget = usize::MAX;
+    write!(writer, "\nSkip\nLine 3\n").unwrap();
+
+    assert_eq!(
+        from_utf8(&writer.get_ref().writer),
+        Ok("  Line 1\n  Line 2\nSkip\n  Line 3\n")
+    );
+}
+
+#[test]
+fn test_replace_indent_level() {
+    let mut writer = IndentWriter::new("\t", Vec::new());
+    writer.set_max_level(Some(4));
+
+    assert_eq!(writer.replace_indent_level(2), 1);
+    write!(writer, "Level 2\n").unwrap();
+
+    // The returned level is the one actually in effect, after clamping
+    assert_eq!(writer.replace_indent_level(10), 2);
+    assert_eq!(writer.replace_indent_level(0), 4);
+    write!(writer, "Level 0\n").unwrap();
+
+    writer.set_indent_level(4);
+    write!(writer, "Level 4\n").unwrap();
+
+    assert_eq!(
+        from_utf8(writer.get_ref()),
+        Ok("\t\tLevel 2\nLevel 0\n\t\t\t\tLevel 4\n")
+    );
+}
+
+#[test]
+fn test_finish() {
+    use indent_write::io::LineEnding;
+
+    // Mid-line, a newline is added
+    let mut writer = IndentWriter::new("  ", Vec::new());
+    write!(writer, "Line 1\nLine 2").unwrap();
+    assert_eq!(
+        from_utf8(&writer.finish().unwrap()),
+        Ok("  Line 1\n  Line 2\n")
+    );
+
+    // At the start of a line, nothing is added
+    let mut writer = IndentWriter::new("  ", Vec::new());
+    write!(writer, "Line 1\n\n").unwrap();
+    assert_eq!(from_utf8(&writer.finish().unwrap()), Ok("  Line 1\n\n"));
+
+    // Nothing written at all
+    let writer = IndentWriter::new_skip_initial("  ", Vec::new());
+    assert_eq!(writer.finish().unwrap(), b"");
+
+    // The newline goes through the usual translation and trimming
+    let mut writer = IndentWriter::new("  ", Vec::new());
+    writer.set_line_ending(Some(LineEnding::CrLf));
+    writer.set_trim_trailing_whitespace(true);
+    write!(writer, "Line 1  ").unwrap();
+    assert_eq!(from_utf8(&writer.finish().unwrap()), Ok("  Line 1\r\n"));
+
+    // A partially written indent is finished, then the line ended
+    let mut writer = IndentWriter::new(
+        "    ",
+        FailAfter {
+            writer: Vec::new(),
+            budget: 2,
+        },
+    );
+    assert!(write!(writer, "Line 1").is_err());
+    writer.get_mut().budget = usize::MAX;
+    assert_eq!(from_utf8(&writer.finish().unwrap().writer), Ok("    \n"));
+}
+
+#[test]
+fn test_finish_policy() {
+    use indent_write::io::FinishPolicy;
+
+    let finish = |text: &str, trim: bool, policy| {
+        let mut writer = IndentWriter::new("  ", Vec::new());
+        writer.set_trim_trailing_whitespace(trim);
+        writer.write_all(text.as_bytes()).unwrap();
+        writer
+            .finish_with(policy)
+            .map(|output| String::from_utf8(output).unwrap())
+    };
+
+    // At the start of a line, every policy leaves the output alone
+    for policy in [
+        FinishPolicy::AddNewline,
+        FinishPolicy::Keep,
+        FinishPolicy::Error,
+    ] {
+        assert_eq!(finish("", false, policy).unwrap(), "");
+        assert_eq!(finish("Line 1\n\n", false, policy).unwrap(), "  Line 1\n\n");
+    }
+
+    assert_eq!(
+        finish("Line 1\nLine 2", false, FinishPolicy::AddNewline).unwrap(),
+        "  Line 1\n  Line 2\n"
+    );
+
+    // The partial line is kept, minus any trimmed whitespace
+    assert_eq!(
+        finish("Line 1\nLine 2 ", false, FinishPolicy::Keep).unwrap(),
+        "  Line 1\n  Line 2 "
+    );
+    assert_eq!(
+        finish("Line 1\nLine 2 ", true, FinishPolicy::Keep).unwrap(),
+        "  Line 1\n  Line 2"
+    );
+
+    let err = finish("Line 1\nLine 2", false, FinishPolicy::Error).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_annotate_errors() {
+    use indent_write::io::LineError;
+
+    fn line_error(err: &io::Error) -> &LineError {
+        err.get_ref().unwrap().downcast_ref::<LineError>().unwrap()
+    }
+
+    let mut writer = IndentWriter::new(
+        "  ",
+        FailAfter {
+            writer: Vec::new(),
+            budget: 20,
+        },
+    );
+    writer.set_annotate_errors(true);
+
+    // The budget runs out partway through the third line
+    let err = write!(writer, "Line 1\n\nLine 2\nLine 3\n").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::Other);
+    assert_eq!(line_error(&err).lines_written(), 3);
+    assert_eq!(line_error(&err).error().to_string(), "budget exhausted");
+
+    // Errors raised by the IndentWriter itself are annotated too
+    writer.set_reject_lone_cr(true);
+    writer.get_mut().budget = usize::MAX;
+    writer.write_all(b"Line 3\nLine 4\r").unwrap();
+    let err = writer.write_all(b"x").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(line_error(&err).lines_written(), 4);
+
+    // Without annotation, the original error is returned
+    writer.set_annotate_errors(false);
+    writer.set_reject_lone_cr(false);
+    writer.get_mut().budget = 0;
+    let err = writer.write_all(b"x").unwrap_err();
+    assert!(err.get_ref().unwrap().downcast_ref::<LineError>().is_none());
+}
+
+#[test]
+fn test_indent_reader() {
+    use indent_write::io::indent_reader;
+    use std::io::BufReader;
+
+    let input = PARAGRAPHS.repeat(3);
+
+    // A tiny buffer, so that lines are split across reads
+    let reader = BufReader::with_capacity(3, input.as_bytes());
+    let mut writer = IndentWriter::new("> ", OneByteAtATime(Vec::new()));
+
+    let copied = indent_reader(reader, &mut writer).unwrap();
+    assert_eq!(copied, input.len() as u64);
+
+    let mut expected = IndentWriter::new("> ", Vec::new());
+    expected.write_all(input.as_bytes()).unwrap();
+    assert_eq!(writer.get_ref().0, expected.into_inner());
+}
+
+#[test]
+fn test_raw_indent() {
+    let mut writer = IndentWriter::new("  ", Vec::new());
+    writer.set_guide(Some("| "));
+    writer.inc();
+
+    writer.set_raw_indent(b">>> ".to_vec());
+    assert_eq!(writer.current_indent_str(), Some(">>> "));
+    write!(writer, "a\nb\n").unwrap();
+
+    // The first line prefix takes precedence
+    writer.set_first_line_prefix(Some("- "));
+    write!(writer, "c\nd\n").unwrap();
+
+    // Changing the level discards the raw indent
+    writer.dec();
+    write!(writer, "e\n").unwrap();
+
+    writer.set_raw_indent(b"# ".to_vec());
+    writer.clear_raw_indent();
+    writer.clear_raw_indent();
+    write!(writer, "f\n").unwrap();
+
+    assert_eq!(
+        from_utf8(writer.get_ref()),
+        Ok(">>> a\n>>> b\n- c\n>>> d\n  e\n  f\n")
+    );
+
+    // The raw indent needn't be valid UTF-8
+    let mut writer = IndentWriter::new("  ", Vec::new());
+    writer.set_raw_indent(vec![0xFF, b' ']);
+    write!(writer, "a\nb\n").unwrap();
+
+    assert_eq!(writer.current_indent_str(), None);
+    assert_eq!(writer.get_ref(), b"\xFF a\n\xFF b\n");
+}
+
+#[test]
+fn test_raw_indent_mid_indent() {
+    let mut writer = IndentWriter::new(
+        "----",
+        FailAfter {
+            writer: Vec::new(),
+            budget: 2,
+        },
+    );
+    writer.set_max_level(Some(1));
+
+    // The indent is only partially written before the error
+    assert!(writer.write_all(b"Line 1\n").is_err());
+    writer.set_raw_indent(b"++".to_vec());
+
+    // Setting a max level that isn't exceeded keeps the raw indent
+    writer.set_max_level(Some(2));
+
+    writer.get_mut().budget = usize::MAX;
+    writer.write_all(b"Line 1\nLine 2\n").unwrap();
+
+    assert_eq!(
+        from_utf8(&writer.get_ref().writer),
+        Ok("----Line 1\n++Line 2\n")
+    );
+}
+
+#[test]
+fn test_map_writer() {
+    use std::io::BufWriter;
+
+    let mut writer = IndentWriter::new(
+        "----",
+        FailAfter {
+            writer: Vec::new(),
+            budget: 17,
+        },
+    );
+    writer.inc();
+
+    // Stop partway through the indent of the second line
+    assert!(writer.write_all(b"Line 1\nLine 2\n").is_err());
+    assert_eq!(writer.bytes_written(), 17);
+
+    let mut writer = writer.map_writer(|writer| BufWriter::new(writer.writer));
+    writer.dec();
+    writer.write_all(b"Line 2\nLine 3\n").unwrap();
+    writer.flush().unwrap();
+
+    assert_eq!(writer.bytes_written(), 41);
+    assert_eq!(writer.lines_written(), 3);
+    assert_eq!(
+        from_utf8(writer.get_ref().get_ref()),
+        Ok("--------Line 1\n--------Line 2\n----Line 3\n")
+    );
+}
+
+#[test]
+fn test_line_numbers() {
+    use indent_write::io::LineNumberConfig;
+
+    const CONFIG: LineNumberConfig<'static> = LineNumberConfig {
+        start: 8,
+        min_width: 1,
+        separator: ": ",
+    };
+
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriter::new("  ", OneByteAtATime(&mut dest));
+        write!(writer, "Unnumbered\nPartial").unwrap();
+
+        // Numbering starts on the next line, and widens past 9
+        writer.set_line_numbers(Some(CONFIG));
+        write!(writer, " line\na\n\nb\nc\n").unwrap();
+        writer.set_line_numbers(None);
+        write!(writer, "d\n").unwrap();
+    }
+    assert_eq!(
+        from_utf8(&dest),
+        Ok("  Unnumbered\n  Partial line\n8:   a\n\n10:   b\n11:   c\n  d\n")
+    );
+
+    // Empty lines are numbered when they're indented
+    let mut writer = IndentWriter::new("  ", Vec::new());
+    writer.set_indent_empty_lines(true);
+    writer.set_line_numbers(Some(LineNumberConfig::default()));
+    write!(writer, "a\n\nb\n").unwrap();
+    assert_eq!(
+        from_utf8(writer.get_ref()),
+        Ok("   1 |   a\n   2 |   \n   3 |   b\n")
+    );
+}
+
+#[test]
+fn test_line_numbers_hanging_indent() {
+    use indent_write::io::{IndentWriterBuilder, LineNumberConfig};
+
+    let mut writer = IndentWriterBuilder::new("  ")
+        .hanging_indent(true)
+        .line_numbers(Some(LineNumberConfig {
+            start: 1,
+            min_width: 2,
+            separator: "|",
+        }))
+        .build(Vec::new());
+
+    writer.set_first_line_prefix(Some("- "));
+    write!(writer, "{}", PARAGRAPHS).unwrap();
+    assert_eq!(
+        from_utf8(writer.get_ref()),
+        Ok(" 1|Para 1\n 2|- line 2\n 3|  line 3\n\n\n 6|Para 2\n 7|  line 2\n")
+    );
+}
+
+#[test]
+fn test_with_char() {
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriter::with_char('█', OneByteAtATime(&mut dest));
+        assert_eq!(writer.indent(), Some("█"));
+        writeln!(writer, "{}", "😀 😀 😀").unwrap();
+        writer.inc_by(2);
+        writeln!(writer, "{}\n", "😀 😀 😀").unwrap();
+        writer.dec();
+        assert_eq!(writer.current_indent_str(), Some("██"));
+        writeln!(writer, "{}", "😀 😀 😀").unwrap();
+
+        // Replacing the indent works as usual
+        writer.set_indent("-");
+        writeln!(writer, "{}", "😀 😀 😀").unwrap();
+    }
+    assert_eq!(
+        from_utf8(&dest),
+        Ok("█😀 😀 😀\n███😀 😀 😀\n\n██😀 😀 😀\n--😀 😀 😀\n")
+    );
+}
+
+#[test]
+fn test_reject_lone_cr() {
+    let mut writer = IndentWriter::new("  ", Vec::new());
+    writer.set_reject_lone_cr(true);
+
+    write!(writer, "a\r\n\r\nb\r").unwrap();
+    write!(writer, "\nc").unwrap();
+
+    // The write stops before the lone '\r', and the next write fails
+    assert_eq!(writer.write(b"d\re\n").unwrap(), 1);
+    let err = writer.write(b"\re\n").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(from_utf8(writer.get_ref()), Ok("  a\r\n  \r\n  b\r\n  cd"));
+
+    // A '\r' at the very end of a write is checked by the next one
+    let mut dest = Vec::new();
+    {
+        let mut writer = IndentWriter::new("  ", &mut dest);
+        writer.set_reject_lone_cr(true);
+        let mut writer = OneByteAtATime(writer);
+        write!(writer, "a\r\nb").unwrap();
+        let err = write!(writer, "\rc").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+    assert_eq!(from_utf8(&dest), Ok("  a\r\n  b\r"));
+
+    // Lone '\r's are allowed by default
+    let mut writer = IndentWriter::new("  ", Vec::new());
+    write!(writer, "a\rb\n").unwrap();
+    assert_eq!(from_utf8(writer.get_ref()), Ok("  a\rb\n"));
+}
+
+#[test]
+fn test_pending_indent_len() {
+    let mut writer = IndentWriter::new(
+        "----",
+        FailAfter {
+            writer: Vec::new(),
+            budget: 1,
+        },
+    );
+    writer.inc();
+    assert_eq!(writer.pending_indent_len(), 0);
+
+    for pending in (1..8).rev() {
+        assert!(writer.write_all(b"Line\n").is_err());
+        assert_eq!(writer.pending_indent_len(), pending);
+
+        // Level changes don't affect the indent being written
+        writer.dec();
+        writer.get_mut().budget = 1;
+    }
+
+    writer.get_mut().budget = usize::MAX;
+    writer.flush().unwrap();
+    assert_eq!(writer.pending_indent_len(), 0);
+    writer.write_all(b"Line\n").unwrap();
+    assert_eq!(from_utf8(&writer.get_ref().writer), Ok("--------Line\n"));
+}
@@ -0,0 +1,107 @@
+// These tests only use `core`, to check that the `Display` adapters work
+// without `std`. Run them with `cargo test --no-default-features` to build the
+// library without `std` as well.
+#![no_std]
+
+use core::fmt::{self, Write};
+
+use indent_write::fmt::IndentWriter;
+use indent_write::indentable::{indent_each, Indentable, IndentableOption};
+
+// This is a fixed capacity string, like `heapless::String`. Writes which
+// don't fit are an error.
+struct Buffer {
+    bytes: [u8; 256],
+    len: usize,
+}
+
+impl Buffer {
+    fn new() -> Self {
+        Buffer {
+            bytes: [0; 256],
+            len: 0,
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.bytes[..self.len]).unwrap()
+    }
+}
+
+impl Write for Buffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        let dest = self.bytes.get_mut(self.len..end).ok_or(fmt::Error)?;
+
+        dest.copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+const CONTENT: &str = "Line 1\nLine 2\n\nLine 3\n";
+
+#[test]
+fn test_indentable() {
+    let mut buffer = Buffer::new();
+    write!(buffer, "{}", CONTENT.indented("  ")).unwrap();
+    assert_eq!(buffer.as_str(), "  Line 1\n  Line 2\n\n  Line 3\n");
+
+    let mut buffer = Buffer::new();
+    write!(buffer, "{}", CONTENT.indent_all("> ")).unwrap();
+    assert_eq!(buffer.as_str(), "> Line 1\n> Line 2\n> \n> Line 3\n");
+
+    let mut buffer = Buffer::new();
+    write!(buffer, "{}", CONTENT.indented_skip_initial("  ")).unwrap();
+    assert_eq!(buffer.as_str(), "Line 1\n  Line 2\n\n  Line 3\n");
+
+    let mut buffer = Buffer::new();
+    write!(buffer, "{}", CONTENT.indented_with_level("  ", 2)).unwrap();
+    assert_eq!(buffer.as_str(), "    Line 1\n    Line 2\n\n    Line 3\n");
+}
+
+#[test]
+fn test_indent_with() {
+    let mut buffer = Buffer::new();
+    let prefixes = ["a) ", "b) ", "c) ", "d) "];
+    write!(buffer, "{}", CONTENT.indent_with(|i| prefixes[i])).unwrap();
+    assert_eq!(buffer.as_str(), "a) Line 1\nb) Line 2\n\nd) Line 3\n");
+}
+
+#[test]
+fn test_indent_each() {
+    let mut buffer = Buffer::new();
+    write!(buffer, "{}", indent_each([1, 2, 3], "- ")).unwrap();
+    assert_eq!(buffer.as_str(), "- 1\n- 2\n- 3");
+}
+
+#[test]
+fn test_option() {
+    let mut buffer = Buffer::new();
+    write!(
+        buffer,
+        "{} {}",
+        Some(1).indented("  "),
+        None::<u8>.indented("  ")
+    )
+    .unwrap();
+    assert_eq!(buffer.as_str(), "  1 None");
+}
+
+#[test]
+fn test_fmt_writer() {
+    let mut writer = IndentWriter::new("\t", Buffer::new());
+    write!(writer, "{}", CONTENT).unwrap();
+    assert_eq!(
+        writer.get_ref().as_str(),
+        "\tLine 1\n\tLine 2\n\n\tLine 3\n"
+    );
+}
+
+#[test]
+fn test_overflow() {
+    // Running out of space is reported as an error, rather than a panic
+    let mut buffer = Buffer::new();
+    let result = write!(buffer, "{}", indent_each(0..100, "    "));
+    assert_eq!(result, Err(fmt::Error));
+}
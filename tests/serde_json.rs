@@ -0,0 +1,95 @@
+#![cfg(feature = "serde_json")]
+
+use std::io::Write;
+use std::str::from_utf8;
+
+use indent_write::io::IndentWriter;
+use serde_json::{json, Value};
+
+fn sample() -> Value {
+    json!({
+        "name": "indent-write",
+        "version": [1, 0, 0],
+        "empty": {},
+        "none": [],
+        "features": {
+            "std": true,
+            "serde_json": { "deps": ["serde", "serde_json"] },
+        },
+        "text": "line 1\nline 2",
+    })
+}
+
+#[test]
+fn test_to_writer_round_trip() {
+    let value = sample();
+
+    let mut writer = IndentWriter::with_indent_level("  ", 0, Vec::new());
+    indent_write::serde_json::to_writer(&mut writer, &value).unwrap();
+    assert_eq!(writer.indent_level(), 0);
+
+    let output = writer.into_inner();
+    assert_eq!(
+        from_utf8(&output).unwrap(),
+        serde_json::to_string_pretty(&value).unwrap()
+    );
+    assert_eq!(serde_json::from_slice::<Value>(&output).unwrap(), value);
+}
+
+#[test]
+fn test_to_writer_nested() {
+    let value = sample();
+    let pretty = serde_json::to_string_pretty(&value).unwrap();
+
+    let mut writer = IndentWriter::new("  ", Vec::new());
+    write!(writer, "value = ").unwrap();
+    indent_write::serde_json::to_writer(&mut writer, &value).unwrap();
+    assert_eq!(writer.indent_level(), 1);
+    writeln!(writer, ";").unwrap();
+
+    let output = writer.into_inner();
+    let output = from_utf8(&output).unwrap();
+
+    let expected: String = pretty
+        .lines()
+        .enumerate()
+        .map(|(i, line)| match i {
+            0 => format!("  value = {}\n", line),
+            _ => format!("  {}\n", line),
+        })
+        .collect();
+    let expected = expected.trim_end().to_owned() + ";\n";
+
+    assert_eq!(output, expected);
+
+    let json = output.trim_start().trim_start_matches("value = ");
+    let json = json.trim_end().trim_end_matches(';');
+    assert_eq!(serde_json::from_str::<Value>(json).unwrap(), value);
+}
+
+#[test]
+fn test_to_writer_raw_indent() {
+    let value = json!({ "a": [1], "b": {} });
+
+    let mut writer = IndentWriter::new("  ", Vec::new());
+    writer.set_raw_indent(b"[t1] ".to_vec());
+    write!(writer, "value = ").unwrap();
+    indent_write::serde_json::to_writer(&mut writer, &value).unwrap();
+    writeln!(writer, ";\nafter").unwrap();
+
+    // The raw indent is kept, both for the JSON and afterwards
+    assert_eq!(writer.raw_indent(), Some(&b"[t1] "[..]));
+    assert_eq!(writer.indent_level(), 1);
+    assert_eq!(
+        from_utf8(writer.get_ref()),
+        Ok(concat!(
+            "[t1] value = {\n",
+            "[t1]   \"a\": [\n",
+            "[t1]     1\n",
+            "[t1]   ],\n",
+            "[t1]   \"b\": {}\n",
+            "[t1] };\n",
+            "[t1] after\n",
+        ))
+    );
+}
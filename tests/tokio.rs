@@ -0,0 +1,121 @@
+#![cfg(feature = "tokio")]
+
+use std::io;
+use std::pin::Pin;
+use std::str::from_utf8;
+use std::task::{Context, Poll};
+
+use indent_write::tokio::IndentWriter;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio_test::io::Builder;
+
+// This is a wrapper for AsyncWrite that accepts at most one byte per poll,
+// and is only ready on every other poll, to test that a partially written
+// indent is resumed correctly after `Poll::Pending`.
+#[derive(Debug)]
+struct OneBytePerPoll<W> {
+    writer: W,
+    ready: bool,
+}
+
+impl<W> OneBytePerPoll<W> {
+    fn new(writer: W) -> Self {
+        OneBytePerPoll {
+            writer,
+            ready: false,
+        }
+    }
+
+    // Returns true if this poll should be `Pending`, waking the task so that
+    // it's polled again.
+    fn pending(&mut self, cx: &mut Context<'_>) -> bool {
+        self.ready = !self.ready;
+
+        if !self.ready {
+            cx.waker().wake_by_ref();
+        }
+
+        !self.ready
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for OneBytePerPoll<W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if self.pending(cx) {
+            return Poll::Pending;
+        }
+
+        let len = buf.len().min(1);
+        Pin::new(&mut self.writer).poll_write(cx, &buf[..len])
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.writer).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.writer).poll_shutdown(cx)
+    }
+}
+
+// A mock which expects `expected`, one byte per write.
+fn one_byte_mock(expected: &[u8]) -> tokio_test::io::Mock {
+    expected
+        .iter()
+        .fold(&mut Builder::new(), |builder, &b| builder.write(&[b]))
+        .build()
+}
+
+#[test]
+fn test_one_byte_per_poll() {
+    tokio_test::block_on(async {
+        let expected = "    Line 1\n\n    Line 2\n        Nested\nNot indented\n";
+        let mock = one_byte_mock(expected.as_bytes());
+
+        let mut writer = IndentWriter::new("    ", OneBytePerPoll::new(mock));
+        writer.write_all(b"Line 1\n\nLine 2\n").await.unwrap();
+        writer.inc();
+        writer.write_all(b"Nested\n").await.unwrap();
+        writer.reset();
+        writer.write_all(b"Not indented\n").await.unwrap();
+        writer.shutdown().await.unwrap();
+    });
+}
+
+#[test]
+fn test_pending_mid_indent() {
+    tokio_test::block_on(async {
+        let mut writer = IndentWriter::new("  ", OneBytePerPoll::new(Vec::new()));
+        writer.set_indent_level(3);
+
+        for chunk in ["Line", " 1\n", "\nLine 2", "\n"] {
+            writer.write_all(chunk.as_bytes()).await.unwrap();
+        }
+
+        assert_eq!(
+            from_utf8(&writer.get_ref().writer),
+            Ok("      Line 1\n\n      Line 2\n")
+        );
+    });
+}
+
+#[test]
+fn test_new_skip_initial() {
+    tokio_test::block_on(async {
+        let mut writer = IndentWriter::new("\t", Vec::new());
+        writer.write_all(b"Line 1\nLine 2").await.unwrap();
+        writer.flush().await.unwrap();
+
+        let mut writer = IndentWriter::new_skip_initial("\t", writer.into_inner());
+        writer.write_all(b" continued\nLine 3\n").await.unwrap();
+
+        assert_eq!(
+            from_utf8(writer.get_ref()),
+            Ok("\tLine 1\n\tLine 2 continued\n\tLine 3\n")
+        );
+    });
+}